@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{
     builder::{
         styling::{AnsiColor, Effects},
@@ -7,8 +9,20 @@ use clap::{
 };
 
 use crate::r#impl::{
-    amap::AMAPCommands, kmkc::KMKCCommands, musq::MUSQCommands, sjv::SJVCommands,
+    accounts::AccountsCommands,
+    amap::AMAPCommands,
+    batch::BatchCommands,
+    budget::BudgetCommands,
+    config::ConfigCommands,
+    kmkc::KMKCCommands,
+    ledger::report::{LedgerExport, LedgerGroup, LedgerPeriod},
+    musq::MUSQCommands,
+    notify::NotifyCommands,
+    queue::QueueCommands,
+    sjv::SJVCommands,
     tools::ToolsCommands,
+    watch::WatchCommands,
+    Implementations,
 };
 
 pub(crate) type ExitCode = u32;
@@ -30,6 +44,21 @@ pub(crate) struct ToshoCli {
     /// `http(s)://<username>:<password>@<ip>:<port>` or `socks5://<username>:<password>@<ip>:<port>`.
     #[arg(long)]
     pub(crate) proxy: Option<String>,
+    /// Use this folder to store the accounts, settings and other state
+    ///
+    /// Default to the `AIRPOPE_CONFIG_DIR` environment variable, then the user config folder.
+    #[arg(long = "config-dir", conflicts_with = "portable")]
+    pub(crate) config_dir: Option<PathBuf>,
+    /// Use a named profile with its own accounts, settings and other state
+    ///
+    /// Default to the `AIRPOPE_PROFILE` environment variable, `default` is the main profile.
+    #[arg(long)]
+    pub(crate) profile: Option<String>,
+    /// Store everything in the `airpope_data` folder next to the binary
+    ///
+    /// Also enabled by an `airpope.portable` file next to the binary.
+    #[arg(long)]
+    pub(crate) portable: bool,
 
     #[command(subcommand)]
     pub(crate) command: ToshoCommands,
@@ -92,8 +121,120 @@ pub(crate) enum ToshoCommands {
         #[command(subcommand)]
         subcommand: ToolsCommands,
     },
+    /// Compare the downloaded titles with the remote chapter list
+    ///
+    /// Every title is synced by default, the source of each title is taken from the
+    /// _info.json file.
+    Sync {
+        /// Only sync the titles from this source
+        #[arg(short = 's', long = "source", value_enum, default_value = None)]
+        source: Option<Implementations>,
+        /// Only sync the title with this title ID
+        #[arg(short = 't', long = "title", default_value = None)]
+        title_id: Option<String>,
+        /// Download every available chapter without purchasing
+        #[arg(short = 'd', long = "download")]
+        download: bool,
+        /// Download directory to use
+        #[arg(short = 'o', long = "output", default_value = None)]
+        output: Option<PathBuf>,
+    },
+    /// Manage the notifiers fired on download, purchase and error events
+    Notify {
+        #[command(subcommand)]
+        subcommand: NotifyCommands,
+    },
+    /// Manage the spending budgets enforced before purchasing chapters
+    Budget {
+        #[command(subcommand)]
+        subcommand: BudgetCommands,
+    },
+    /// Summarize or export the purchases recorded in the ledger
+    Ledger {
+        /// Only include purchases from this source
+        #[arg(short = 's', long = "source", value_enum, default_value = None)]
+        source: Option<Implementations>,
+        /// Only include purchases from this account ID
+        #[arg(short = 'a', long = "account", default_value = None)]
+        account_id: Option<String>,
+        /// Only include purchases of this title ID
+        #[arg(short = 't', long = "title", default_value = None)]
+        title_id: Option<String>,
+        /// Only include purchases made on or after this date (YYYY-MM-DD)
+        #[arg(long = "from", default_value = None)]
+        from: Option<chrono::NaiveDate>,
+        /// Only include purchases made on or before this date (YYYY-MM-DD)
+        #[arg(long = "to", default_value = None)]
+        to: Option<chrono::NaiveDate>,
+        /// How to group the summary
+        #[arg(short = 'g', long = "group-by", value_enum, default_value = "period")]
+        group_by: LedgerGroup,
+        /// The period length when grouping by period
+        #[arg(short = 'p', long = "period", value_enum, default_value = "month")]
+        period: LedgerPeriod,
+        /// Export the purchases instead of summarizing them
+        #[arg(short = 'e', long = "export", value_enum, default_value = None)]
+        export: Option<LedgerExport>,
+        /// File to export to, default to the standard output
+        #[arg(short = 'o', long = "output", default_value = None)]
+        output: Option<PathBuf>,
+    },
+    /// Run many downloads from a manifest file
+    Batch {
+        #[command(subcommand)]
+        subcommand: BatchCommands,
+    },
+    /// Manage the download queue to resume interrupted downloads
+    Queue {
+        #[command(subcommand)]
+        subcommand: QueueCommands,
+    },
+    /// Manage the saved accounts of every source
+    Accounts {
+        #[command(subcommand)]
+        subcommand: Option<AccountsCommands>,
+    },
+    /// Manage the settings and how the accounts are stored
+    Config {
+        #[command(subcommand)]
+        subcommand: ConfigCommands,
+    },
+    /// Start a local HTTP API server
+    ///
+    /// Every request needs the `Authorization: Bearer <TOKEN>` header.
+    Serve {
+        /// Address to bind to, only reachable from this machine by default
+        #[arg(long = "host", default_value = "127.0.0.1")]
+        host: String,
+        /// Port to listen on
+        #[arg(short = 'p', long = "port", default_value_t = 8190)]
+        port: u16,
+        /// API token, default to the AIRPOPE_SERVE_TOKEN environment variable or a random token
+        #[arg(long = "token", default_value = None)]
+        token: Option<String>,
+    },
     /// Update airpope to the latest version
     Update,
+    /// Manage the watchlist of followed titles
+    Watch {
+        #[command(subcommand)]
+        subcommand: WatchCommands,
+    },
+    /// Keep running and check the watchlist on schedule
+    ///
+    /// KM and MU titles are checked on their weekly release day, other titles
+    /// use their own schedule or the check interval.
+    Daemon {
+        /// Check interval in minutes for titles without any schedule
+        #[arg(short = 'i', long = "interval", default_value_t = 360)]
+        interval: u64,
+        /// Maximum random delay in seconds before checking each title
+        #[arg(short = 'j', long = "jitter", default_value_t = 300)]
+        jitter: u64,
+        /// Check every title once right away when starting
+        #[arg(long = "now")]
+        now: bool,
+    },
 }
 
 fn cli_styles() -> Styles {
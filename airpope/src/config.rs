@@ -1,30 +1,48 @@
 use directories::BaseDirs;
 use prost::Message;
 use std::{
-    io::{Cursor, Read, Write},
-    path::PathBuf,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
+use crate::r#impl::config::vault::{
+    read_config_file, write_config_file, VaultDecryptError, VaultLockedError,
+};
 use crate::r#impl::Implementations;
 
+/// Header of the config envelope, followed by the schema version as little endian `u32`.
+const ENVELOPE_MAGIC: &[u8; 6] = b"APCONF";
+/// The schema version of the protobuf inside the config envelope.
+pub(crate) const CONFIG_SCHEMA: u32 = 1;
+/// Extension appended to the unreadable config files, they are not picked up anymore.
+const QUARANTINE_EXT: &str = "corrupt";
+
+/// The environment variable used as the config directory when `--config-dir` is not given.
+pub(crate) const CONFIG_DIR_ENV: &str = "AIRPOPE_CONFIG_DIR";
+/// The environment variable used as the profile when `--profile` is not given.
+pub(crate) const PROFILE_ENV: &str = "AIRPOPE_PROFILE";
+/// Marker file next to the binary that enables the portable mode.
+const PORTABLE_MARKER: &str = "airpope.portable";
+/// The config directory next to the binary in portable mode.
+const PORTABLE_DIR: &str = "airpope_data";
+/// The folder of the named profiles inside the config directory.
+const PROFILES_DIR: &str = "profiles";
+
+static USER_PATH: OnceLock<PathBuf> = OnceLock::new();
+
 macro_rules! config_reader {
     (
         $read_func:ident,
         $get_func:ident,
         $config:ty,
-        $prefix:expr
+        $prefix:expr,
+        $impl:expr
     ) => {
         fn $read_func(user_conf: PathBuf) -> Option<$config> {
-            if !user_conf.exists() {
-                None
-            } else {
-                let mut file = std::fs::File::open(user_conf).unwrap();
-                let mut buffer = Vec::new();
-                file.read_to_end(&mut buffer).unwrap();
-                drop(file);
-                let conf = <$config>::decode(&mut Cursor::new(buffer)).unwrap();
-                Some(conf)
-            }
+            read_config_with(&user_conf, $impl, |buffer| {
+                <$config>::decode(&mut Cursor::new(buffer))
+            })
         }
 
         fn $get_func(id: &str, user_path: PathBuf) -> Option<$config> {
@@ -41,11 +59,9 @@ macro_rules! save_config_impl {
         let mut user_conf = $user_path.clone();
         user_conf.push(format!("{}.{}.tmconf", $prefix, $config.id));
 
-        let mut file = std::fs::File::create(user_conf).unwrap();
         let mut buffer = Vec::new();
         $config.encode(&mut buffer).unwrap();
-        file.write_all(&buffer).unwrap();
-        drop(file);
+        write_config_file_or_warn(&user_conf, &buffer);
     }};
 }
 
@@ -88,7 +104,133 @@ impl_from_config!(crate::r#impl::amap::config::Config, Amap);
 impl_from_config!(crate::r#impl::sjv::config::Config, Sjv);
 impl_from_config!(crate::r#impl::rbean::config::Config, Rbean);
 
+impl ConfigImpl {
+    /// Get the account ID of the config.
+    pub fn get_id(&self) -> &str {
+        match self {
+            ConfigImpl::Kmkc(c) => c.get_id(),
+            ConfigImpl::Musq(c) => &c.id,
+            ConfigImpl::Amap(c) => &c.id,
+            ConfigImpl::Sjv(c) => &c.id,
+            ConfigImpl::Rbean(c) => &c.id,
+        }
+    }
+
+    /// Get the source of the config.
+    pub(crate) fn implementation(&self) -> Implementations {
+        match self {
+            ConfigImpl::Kmkc(_) => Implementations::Kmkc,
+            ConfigImpl::Musq(_) => Implementations::Musq,
+            ConfigImpl::Amap(_) => Implementations::Amap,
+            ConfigImpl::Sjv(_) => Implementations::Sjv,
+            ConfigImpl::Rbean(_) => Implementations::Rbean,
+        }
+    }
+
+    /// Encode the config into the protobuf stored in `.tmconf` files.
+    pub(crate) fn encode_to_vec(&self) -> Vec<u8> {
+        match self {
+            ConfigImpl::Kmkc(crate::r#impl::kmkc::config::Config::Mobile(c)) => c.encode_to_vec(),
+            ConfigImpl::Kmkc(crate::r#impl::kmkc::config::Config::Web(c)) => c.encode_to_vec(),
+            ConfigImpl::Musq(c) => c.encode_to_vec(),
+            ConfigImpl::Amap(c) => c.encode_to_vec(),
+            ConfigImpl::Sjv(c) => c.encode_to_vec(),
+            ConfigImpl::Rbean(c) => c.encode_to_vec(),
+        }
+    }
+
+    /// Decode a config of the source from the protobuf stored in `.tmconf` files.
+    pub(crate) fn decode(r#impl: Implementations, buffer: Vec<u8>) -> anyhow::Result<Self> {
+        let mut cursor = Cursor::new(buffer);
+        let config = match r#impl {
+            Implementations::Kmkc => ConfigImpl::Kmkc(decode_kmkc_config(cursor.into_inner())?),
+            Implementations::Musq => {
+                ConfigImpl::Musq(crate::r#impl::musq::config::Config::decode(&mut cursor)?)
+            }
+            Implementations::Amap => {
+                ConfigImpl::Amap(crate::r#impl::amap::config::Config::decode(&mut cursor)?)
+            }
+            Implementations::Sjv => {
+                ConfigImpl::Sjv(crate::r#impl::sjv::config::Config::decode(&mut cursor)?)
+            }
+            Implementations::Rbean => {
+                ConfigImpl::Rbean(crate::r#impl::rbean::config::Config::decode(&mut cursor)?)
+            }
+        };
+        Ok(config)
+    }
+}
+
+/// The config directory of the current profile, see [`resolve_user_path`].
 pub(crate) fn get_user_path() -> std::path::PathBuf {
+    USER_PATH.get().cloned().unwrap_or_else(default_user_path)
+}
+
+/// Use the given config directory for the rest of the process.
+pub(crate) fn init_user_path(user_path: PathBuf) {
+    // only the first call is used, the directory can't change afterward
+    let _ = USER_PATH.set(user_path);
+}
+
+/// The `airpope_data` folder next to the binary.
+fn portable_user_path() -> anyhow::Result<PathBuf> {
+    let exe_path = std::env::current_exe()?;
+    match exe_path.parent() {
+        Some(exe_dir) => Ok(exe_dir.join(PORTABLE_DIR)),
+        None => anyhow::bail!("Unable to find the folder of {}", exe_path.display()),
+    }
+}
+
+fn is_portable_install() -> bool {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe_path| exe_path.parent().map(|dir| dir.join(PORTABLE_MARKER)))
+        .is_some_and(|marker| marker.exists())
+}
+
+fn env_value(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Resolve the config directory of the profile.
+///
+/// The base directory is taken from `--config-dir`, `--portable`, [`CONFIG_DIR_ENV`],
+/// an `airpope.portable` file next to the binary, then the platform default, in that order.
+/// A named profile lives in the `profiles` folder of the base directory.
+pub(crate) fn resolve_user_path(
+    config_dir: Option<PathBuf>,
+    profile: Option<String>,
+    portable: bool,
+) -> anyhow::Result<PathBuf> {
+    let base_path = match config_dir {
+        Some(config_dir) => config_dir,
+        None if portable => portable_user_path()?,
+        None => match env_value(CONFIG_DIR_ENV) {
+            Some(config_dir) => PathBuf::from(config_dir),
+            None if is_portable_install() => portable_user_path()?,
+            None => default_user_path(),
+        },
+    };
+
+    match profile.or_else(|| env_value(PROFILE_ENV)) {
+        None => Ok(base_path),
+        Some(profile) if profile == "default" => Ok(base_path),
+        Some(profile) => {
+            let is_valid = profile
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !is_valid {
+                anyhow::bail!(
+                    "Invalid profile name {}, only letters, numbers, - and _ are allowed",
+                    profile
+                );
+            }
+            Ok(base_path.join(PROFILES_DIR).join(profile))
+        }
+    }
+}
+
+fn default_user_path() -> std::path::PathBuf {
     #[cfg(windows)]
     let user_path = {
         let mut local_appdata: std::path::PathBuf =
@@ -105,33 +247,172 @@ pub(crate) fn get_user_path() -> std::path::PathBuf {
     user_path
 }
 
-//--> Reader <--//
-fn read_kmkc_config(user_conf: PathBuf) -> Option<crate::r#impl::kmkc::config::Config> {
-    if !user_conf.exists() {
-        None
+//--> Envelope <--//
+/// Wrap the config protobuf into the envelope with the current schema version.
+fn wrap_envelope(buffer: &[u8]) -> Vec<u8> {
+    let mut wrapped = Vec::with_capacity(ENVELOPE_MAGIC.len() + 4 + buffer.len());
+    wrapped.extend_from_slice(ENVELOPE_MAGIC);
+    wrapped.extend_from_slice(&CONFIG_SCHEMA.to_le_bytes());
+    wrapped.extend_from_slice(buffer);
+    wrapped
+}
+
+/// Split the envelope into the schema version and the config protobuf.
+///
+/// The files written before the envelope existed are the plain protobuf of schema 1.
+fn open_envelope(buffer: Vec<u8>) -> (Option<u32>, Vec<u8>) {
+    let header_len = ENVELOPE_MAGIC.len() + 4;
+    if buffer.len() >= header_len && buffer.starts_with(ENVELOPE_MAGIC) {
+        let mut version = [0u8; 4];
+        version.copy_from_slice(&buffer[ENVELOPE_MAGIC.len()..header_len]);
+        (
+            Some(u32::from_le_bytes(version)),
+            buffer[header_len..].to_vec(),
+        )
     } else {
-        let mut file = std::fs::File::open(user_conf).unwrap();
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer).unwrap();
-        drop(file);
-        let conf_temp =
-            crate::r#impl::kmkc::config::ConfigBase::decode(&mut Cursor::new(buffer.clone()))
-                .unwrap();
-
-        match conf_temp.r#type() {
-            crate::r#impl::kmkc::config::DeviceType::Web => {
-                let conf = crate::r#impl::kmkc::config::ConfigWeb::decode(&mut Cursor::new(buffer))
-                    .unwrap();
-                Some(conf.into())
-            }
-            crate::r#impl::kmkc::config::DeviceType::Mobile => {
-                let conf =
-                    crate::r#impl::kmkc::config::ConfigMobile::decode(&mut Cursor::new(buffer))
-                        .unwrap();
-                Some(conf.into())
-            }
+        (None, buffer)
+    }
+}
+
+/// Convert the config protobuf of the given schema to [`CONFIG_SCHEMA`].
+///
+/// Every schema change should add a step here so older configs keep working.
+pub(crate) fn migrate_config(
+    r#impl: Implementations,
+    schema: u32,
+    buffer: Vec<u8>,
+) -> anyhow::Result<Vec<u8>> {
+    match schema {
+        CONFIG_SCHEMA => Ok(buffer),
+        schema if schema > CONFIG_SCHEMA => anyhow::bail!(
+            "the {} config schema {} is newer than the supported schema {}, update airpope first",
+            r#impl.source_name(),
+            schema,
+            CONFIG_SCHEMA
+        ),
+        schema => anyhow::bail!(
+            "the {} config schema {} is not supported",
+            r#impl.source_name(),
+            schema
+        ),
+    }
+}
+
+/// Move an unreadable config file aside so it stops breaking every command.
+fn quarantine_config(user_conf: &Path, reason: &str) {
+    let console = crate::term::get_console(0);
+    let file_name = user_conf
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let quarantined = user_conf.with_file_name(format!("{}.{}", file_name, QUARANTINE_EXT));
+
+    match std::fs::rename(user_conf, &quarantined) {
+        Ok(_) => console.warn(&format!(
+            "Unable to read {}, moved to {}: {}",
+            user_conf.display(),
+            quarantined.display(),
+            reason
+        )),
+        Err(err) => console.warn(&format!(
+            "Unable to read {} ({}) and failed to move it aside: {}",
+            user_conf.display(),
+            reason,
+            err
+        )),
+    }
+}
+
+/// Read a config file, migrating it to the current envelope and schema.
+///
+/// An unreadable file is quarantined, and a file of a newer schema or encrypted with
+/// an unknown key is skipped.
+fn read_config_with<T>(
+    user_conf: &Path,
+    r#impl: Implementations,
+    decode: impl FnOnce(Vec<u8>) -> Result<T, prost::DecodeError>,
+) -> Option<T> {
+    if !user_conf.exists() {
+        return None;
+    }
+
+    let buffer = match read_config_file(user_conf) {
+        Ok(buffer) => buffer,
+        // already reported, the config is fine but can't be decrypted
+        Err(err) if err.is::<VaultLockedError>() => return None,
+        // the file may be fine, it's only encrypted with a key we don't have
+        Err(err) if err.is::<VaultDecryptError>() => {
+            crate::term::get_console(0).warn(&err.to_string());
+            return None;
         }
+        Err(err) => {
+            quarantine_config(user_conf, &err.to_string());
+            return None;
+        }
+    };
+
+    let (schema, buffer) = open_envelope(buffer);
+    let schema_version = schema.unwrap_or(CONFIG_SCHEMA);
+    if schema_version > CONFIG_SCHEMA {
+        crate::term::get_console(0).warn(&format!(
+            "Skipping {}: the config schema {} is newer than the supported schema {}, update airpope first",
+            user_conf.display(),
+            schema_version,
+            CONFIG_SCHEMA
+        ));
+        return None;
     }
+
+    let buffer = match migrate_config(r#impl, schema_version, buffer) {
+        Ok(buffer) => buffer,
+        Err(err) => {
+            quarantine_config(user_conf, &err.to_string());
+            return None;
+        }
+    };
+
+    let config = match decode(buffer.clone()) {
+        Ok(config) => config,
+        Err(err) => {
+            quarantine_config(user_conf, &err.to_string());
+            return None;
+        }
+    };
+
+    if schema != Some(CONFIG_SCHEMA) {
+        if let Err(err) = write_config_file(user_conf, &wrap_envelope(&buffer)) {
+            crate::term::get_console(0).warn(&format!(
+                "Failed to migrate {}: {}",
+                user_conf.display(),
+                err
+            ));
+        }
+    }
+
+    Some(config)
+}
+
+//--> Reader <--//
+fn decode_kmkc_config(
+    buffer: Vec<u8>,
+) -> Result<crate::r#impl::kmkc::config::Config, prost::DecodeError> {
+    let conf_temp =
+        crate::r#impl::kmkc::config::ConfigBase::decode(&mut Cursor::new(buffer.clone()))?;
+
+    match conf_temp.r#type() {
+        crate::r#impl::kmkc::config::DeviceType::Web => {
+            let conf = crate::r#impl::kmkc::config::ConfigWeb::decode(&mut Cursor::new(buffer))?;
+            Ok(conf.into())
+        }
+        crate::r#impl::kmkc::config::DeviceType::Mobile => {
+            let conf = crate::r#impl::kmkc::config::ConfigMobile::decode(&mut Cursor::new(buffer))?;
+            Ok(conf.into())
+        }
+    }
+}
+
+fn read_kmkc_config(user_conf: PathBuf) -> Option<crate::r#impl::kmkc::config::Config> {
+    read_config_with(&user_conf, Implementations::Kmkc, decode_kmkc_config)
 }
 
 fn get_config_kmkc(id: &str, user_path: PathBuf) -> Option<crate::r#impl::kmkc::config::Config> {
@@ -149,28 +430,32 @@ config_reader!(
     read_musq_config,
     get_config_musq,
     crate::r#impl::musq::config::Config,
-    crate::r#impl::musq::config::PREFIX
+    crate::r#impl::musq::config::PREFIX,
+    Implementations::Musq
 );
 
 config_reader!(
     read_amap_config,
     get_config_amap,
     crate::r#impl::amap::config::Config,
-    crate::r#impl::amap::config::PREFIX
+    crate::r#impl::amap::config::PREFIX,
+    Implementations::Amap
 );
 
 config_reader!(
     read_sjv_config,
     get_config_sjv,
     crate::r#impl::sjv::config::Config,
-    crate::r#impl::sjv::config::PREFIX
+    crate::r#impl::sjv::config::PREFIX,
+    Implementations::Sjv
 );
 
 config_reader!(
     read_rbean_config,
     get_config_rbean,
     crate::r#impl::rbean::config::Config,
-    crate::r#impl::rbean::config::PREFIX
+    crate::r#impl::rbean::config::PREFIX,
+    Implementations::Rbean
 );
 
 pub fn get_config(
@@ -263,6 +548,17 @@ pub fn get_all_config(r#impl: &Implementations, user_path: Option<PathBuf>) -> V
     matched_entries
 }
 
+/// Write the config in the envelope, a failure only warns.
+fn write_config_file_or_warn(user_conf: &Path, buffer: &[u8]) {
+    if let Err(err) = write_config_file(user_conf, &wrap_envelope(buffer)) {
+        crate::term::get_console(0).error(&format!(
+            "Failed to save {}: {}",
+            user_conf.display(),
+            err
+        ));
+    }
+}
+
 pub fn save_config(config: ConfigImpl, user_path: Option<PathBuf>) {
     let user_path = user_path.unwrap_or(get_user_path());
 
@@ -283,7 +579,6 @@ pub fn save_config(config: ConfigImpl, user_path: Option<PathBuf>) {
                 conf_id,
             ));
 
-            let mut file = std::fs::File::create(user_conf).unwrap();
             let mut buffer = Vec::new();
 
             match config {
@@ -294,8 +589,7 @@ pub fn save_config(config: ConfigImpl, user_path: Option<PathBuf>) {
                     config.encode(&mut buffer).unwrap();
                 }
             }
-            file.write_all(&buffer).unwrap();
-            drop(file);
+            write_config_file_or_warn(&user_conf, &buffer);
         }
         ConfigImpl::Musq(config) => {
             save_config_impl!(crate::r#impl::musq::config::PREFIX, user_path, config)
@@ -330,8 +624,71 @@ pub fn try_remove_config(
     user_conf.push(format!("{}.{}.tmconf", prefix, id));
 
     if user_conf.exists() {
-        std::fs::remove_file(user_conf)
-    } else {
-        Ok(())
+        std::fs::remove_file(user_conf)?;
+    }
+
+    // the alias and the default of the account are gone with it
+    let aliases_path = user_path.join(crate::r#impl::accounts::aliases::ALIASES_FILE);
+    if let Ok(mut aliases) = crate::r#impl::accounts::aliases::AccountAliases::load(&aliases_path) {
+        if aliases.forget(r#impl, id) {
+            aliases.save(&aliases_path).map_err(std::io::Error::other)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_envelope_migration_and_quarantine() {
+        let user_path = std::env::temp_dir().join(format!("airpope-config-{}", std::process::id()));
+        std::fs::create_dir_all(&user_path).unwrap();
+
+        let mut config = crate::r#impl::musq::config::Config::from_session(
+            "secret",
+            crate::r#impl::musq::config::DeviceType::Android,
+        );
+        config.apply_id("legacy");
+        let legacy_path = user_path.join("musq.legacy.tmconf");
+        std::fs::write(&legacy_path, config.encode_to_vec()).unwrap();
+
+        let corrupt_path = user_path.join("musq.corrupt.tmconf");
+        std::fs::write(&corrupt_path, [0xffu8, 0xff, 0xff]).unwrap();
+
+        let mut newer = ENVELOPE_MAGIC.to_vec();
+        newer.extend_from_slice(&(CONFIG_SCHEMA + 1).to_le_bytes());
+        let newer_path = user_path.join("musq.newer.tmconf");
+        std::fs::write(&newer_path, &newer).unwrap();
+
+        let configs = get_all_config(&Implementations::Musq, Some(user_path.clone()));
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].get_id(), "legacy");
+
+        // migrated in place, quarantined, and left alone for a newer airpope
+        let migrated = std::fs::read(&legacy_path).unwrap();
+        assert_eq!(open_envelope(migrated).0, Some(CONFIG_SCHEMA));
+        assert!(!corrupt_path.exists());
+        assert!(user_path.join("musq.corrupt.tmconf.corrupt").exists());
+        assert_eq!(std::fs::read(&newer_path).unwrap(), newer);
+
+        std::fs::remove_dir_all(&user_path).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_user_path() {
+        let base_path = PathBuf::from("/data/airpope");
+        let resolve = |profile: &str| {
+            resolve_user_path(Some(base_path.clone()), Some(profile.to_string()), true)
+        };
+
+        assert_eq!(resolve("default").unwrap(), base_path);
+        assert_eq!(
+            resolve("work").unwrap(),
+            base_path.join("profiles").join("work")
+        );
+        assert!(resolve("../escape").is_err());
     }
 }
@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{get_config, get_user_path},
+    r#impl::{config::vault::write_atomic, Implementations},
+};
+
+/// The aliases file name inside the config directory.
+pub(crate) const ALIASES_FILE: &str = "accounts.json";
+
+/// A human name for an account, unique per source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountAlias {
+    pub(crate) source: Implementations,
+    pub(crate) id: String,
+    pub(crate) alias: String,
+}
+
+/// When an account was last selected by a command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountUsage {
+    pub(crate) source: Implementations,
+    pub(crate) id: String,
+    /// Unix timestamp in seconds.
+    pub(crate) last_used: i64,
+}
+
+/// The account aliases, the default account of each source and the account usage,
+/// stored as `accounts.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountAliases {
+    #[serde(default)]
+    pub(crate) aliases: Vec<AccountAlias>,
+    /// The account ID used when no account is given.
+    #[serde(default)]
+    pub(crate) defaults: HashMap<Implementations, String>,
+    #[serde(default)]
+    pub(crate) usage: Vec<AccountUsage>,
+}
+
+impl AccountAliases {
+    pub(crate) fn default_path() -> PathBuf {
+        get_user_path().join(ALIASES_FILE)
+    }
+
+    /// Load the aliases, a missing file means no aliases.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let aliases: AccountAliases = serde_json::from_str(&content)?;
+        Ok(aliases)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        write_atomic(path, content.as_bytes())?;
+        Ok(())
+    }
+
+    /// The account ID of the alias.
+    pub(crate) fn resolve(&self, source: Implementations, alias: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|entry| entry.source == source && entry.alias == alias)
+            .map(|entry| entry.id.as_str())
+    }
+
+    /// The account ID of an account ID or alias, an existing account ID wins over an alias.
+    pub(crate) fn resolve_account_id(&self, source: Implementations, account: &str) -> String {
+        if get_config(account, &source, None).is_some() {
+            return account.to_string();
+        }
+
+        self.resolve(source, account).unwrap_or(account).to_string()
+    }
+
+    /// The alias of the account.
+    pub(crate) fn alias_of(&self, source: Implementations, id: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|entry| entry.source == source && entry.id == id)
+            .map(|entry| entry.alias.as_str())
+    }
+
+    /// Set the alias of the account, replacing its previous alias.
+    pub(crate) fn set_alias(
+        &mut self,
+        source: Implementations,
+        id: &str,
+        alias: &str,
+    ) -> anyhow::Result<()> {
+        if let Some(other) = self.resolve(source, alias) {
+            if other != id {
+                anyhow::bail!(
+                    "the alias {} is already used by {} account {}",
+                    alias,
+                    source.source_name(),
+                    other
+                );
+            }
+        }
+
+        self.aliases
+            .retain(|entry| !(entry.source == source && entry.id == id));
+        self.aliases.push(AccountAlias {
+            source,
+            id: id.to_string(),
+            alias: alias.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Remove the alias, returning the account ID it pointed to.
+    pub(crate) fn remove_alias(&mut self, source: Implementations, alias: &str) -> Option<String> {
+        let index = self
+            .aliases
+            .iter()
+            .position(|entry| entry.source == source && entry.alias == alias)?;
+        Some(self.aliases.remove(index).id)
+    }
+
+    pub(crate) fn default_of(&self, source: Implementations) -> Option<&str> {
+        self.defaults.get(&source).map(|id| id.as_str())
+    }
+
+    pub(crate) fn set_default(&mut self, source: Implementations, id: Option<String>) {
+        match id {
+            Some(id) => self.defaults.insert(source, id),
+            None => self.defaults.remove(&source),
+        };
+    }
+
+    /// The last time the account was used, as an unix timestamp.
+    pub(crate) fn last_used_of(&self, source: Implementations, id: &str) -> Option<i64> {
+        self.usage
+            .iter()
+            .find(|entry| entry.source == source && entry.id == id)
+            .map(|entry| entry.last_used)
+    }
+
+    /// Record that the account is used at the given unix timestamp.
+    pub(crate) fn touch(&mut self, source: Implementations, id: &str, timestamp: i64) {
+        match self
+            .usage
+            .iter_mut()
+            .find(|entry| entry.source == source && entry.id == id)
+        {
+            Some(entry) => entry.last_used = timestamp,
+            None => self.usage.push(AccountUsage {
+                source,
+                id: id.to_string(),
+                last_used: timestamp,
+            }),
+        }
+    }
+
+    /// Drop the alias, the default and the usage of a removed account,
+    /// returns `true` if anything changed.
+    pub(crate) fn forget(&mut self, source: Implementations, id: &str) -> bool {
+        let count = self.aliases.len() + self.usage.len();
+        self.aliases
+            .retain(|entry| !(entry.source == source && entry.id == id));
+        self.usage
+            .retain(|entry| !(entry.source == source && entry.id == id));
+        let was_default = self.default_of(source) == Some(id);
+        if was_default {
+            self.defaults.remove(&source);
+        }
+
+        was_default || count != self.aliases.len() + self.usage.len()
+    }
+}
+
+/// Load the aliases of the config directory, an invalid file only warns.
+pub(crate) fn get_aliases(console: &crate::term::Terminal) -> AccountAliases {
+    match AccountAliases::load(&AccountAliases::default_path()) {
+        Ok(aliases) => aliases,
+        Err(err) => {
+            console.warn(&format!("Failed to read {}: {}", ALIASES_FILE, err));
+            AccountAliases::default()
+        }
+    }
+}
+
+/// Record that the account is used now.
+///
+/// The file is read again so the changes of concurrent runs are kept, and it's left alone
+/// when it can't be read.
+pub(crate) fn record_account_usage(source: Implementations, id: &str) -> anyhow::Result<()> {
+    let path = AccountAliases::default_path();
+    let mut aliases = AccountAliases::load(&path)?;
+    aliases.touch(source, id, chrono::Utc::now().timestamp());
+    aliases.save(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases_and_defaults() {
+        let mut aliases = AccountAliases::default();
+        aliases
+            .set_alias(Implementations::Musq, "abc", "main")
+            .unwrap();
+        aliases
+            .set_alias(Implementations::Kmkc, "def", "main")
+            .unwrap();
+        assert!(aliases
+            .set_alias(Implementations::Musq, "ghi", "main")
+            .is_err());
+
+        // a new alias replaces the old one
+        aliases
+            .set_alias(Implementations::Musq, "abc", "alt")
+            .unwrap();
+        assert_eq!(aliases.resolve(Implementations::Musq, "main"), None);
+        assert_eq!(aliases.resolve(Implementations::Musq, "alt"), Some("abc"));
+        assert_eq!(aliases.resolve(Implementations::Kmkc, "main"), Some("def"));
+
+        aliases.set_default(Implementations::Musq, Some("abc".to_string()));
+        aliases.touch(Implementations::Musq, "abc", 100);
+        aliases.touch(Implementations::Musq, "abc", 200);
+        let json = serde_json::to_string(&aliases).unwrap();
+        let mut aliases: AccountAliases = serde_json::from_str(&json).unwrap();
+        assert_eq!(aliases.default_of(Implementations::Musq), Some("abc"));
+        assert_eq!(
+            aliases.last_used_of(Implementations::Musq, "abc"),
+            Some(200)
+        );
+
+        assert!(aliases.forget(Implementations::Musq, "abc"));
+        assert_eq!(aliases.default_of(Implementations::Musq), None);
+        assert_eq!(aliases.alias_of(Implementations::Musq, "abc"), None);
+        assert_eq!(aliases.last_used_of(Implementations::Musq, "abc"), None);
+        assert!(!aliases.forget(Implementations::Musq, "abc"));
+    }
+}
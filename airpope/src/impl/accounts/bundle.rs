@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{migrate_config, ConfigImpl, CONFIG_SCHEMA},
+    r#impl::{
+        config::vault::{Vault, VaultKey},
+        Implementations,
+    },
+};
+
+/// The bundle format version.
+const BUNDLE_VERSION: u32 = 1;
+
+/// A single account inside the bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BundleAccount {
+    pub(crate) source: Implementations,
+    pub(crate) id: String,
+    /// The config schema version of `data`.
+    pub(crate) schema: u32,
+    /// Base64 encoded `.tmconf` protobuf.
+    data: String,
+}
+
+impl BundleAccount {
+    pub(crate) fn new(config: &ConfigImpl) -> Self {
+        Self {
+            source: config.implementation(),
+            id: config.get_id().to_string(),
+            schema: CONFIG_SCHEMA,
+            data: STANDARD.encode(config.encode_to_vec()),
+        }
+    }
+
+    /// Decode the account, converting it from an older config schema if needed.
+    pub(crate) fn to_config(&self) -> anyhow::Result<ConfigImpl> {
+        let data = STANDARD.decode(&self.data)?;
+        let data = migrate_config(self.source, self.schema, data)?;
+
+        let config = ConfigImpl::decode(self.source, data)?;
+        if config.get_id() != self.id {
+            anyhow::bail!(
+                "the account ID {} does not match the bundle ID {}",
+                config.get_id(),
+                self.id
+            );
+        }
+        Ok(config)
+    }
+}
+
+/// The exported accounts, stored as JSON.
+///
+/// When encrypted, `accounts` is empty and `payload` holds the encrypted accounts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountBundle {
+    version: u32,
+    created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vault: Option<Vault>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payload: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) accounts: Vec<BundleAccount>,
+}
+
+impl AccountBundle {
+    pub(crate) fn new(configs: &[ConfigImpl]) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            created_at: chrono::Local::now().to_rfc3339(),
+            vault: None,
+            payload: None,
+            accounts: configs.iter().map(BundleAccount::new).collect(),
+        }
+    }
+
+    /// The key derivation settings, only for an encrypted bundle.
+    pub(crate) fn vault(&self) -> Option<&Vault> {
+        self.vault.as_ref()
+    }
+
+    /// Encrypt the accounts with a new key derived from the passphrase.
+    pub(crate) fn encrypt(&mut self, passphrase: &str) -> anyhow::Result<()> {
+        let (vault, key) = Vault::create(passphrase)?;
+        let accounts = serde_json::to_vec(&self.accounts)?;
+
+        self.payload = Some(STANDARD.encode(key.encrypt(&accounts)?));
+        self.vault = Some(vault);
+        self.accounts.clear();
+        Ok(())
+    }
+
+    /// Decrypt the accounts with the key of the bundle vault.
+    pub(crate) fn decrypt(&mut self, key: &VaultKey) -> anyhow::Result<()> {
+        let payload = match &self.payload {
+            Some(payload) => STANDARD.decode(payload)?,
+            None => anyhow::bail!("The bundle has no encrypted accounts"),
+        };
+
+        self.accounts = serde_json::from_slice(&key.decrypt(&payload)?)?;
+        self.payload = None;
+        self.vault = None;
+        Ok(())
+    }
+
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let bundle: AccountBundle = serde_json::from_str(&content)?;
+        if bundle.version > BUNDLE_VERSION {
+            anyhow::bail!(
+                "The bundle version {} is not supported, update airpope first",
+                bundle.version
+            );
+        }
+        Ok(bundle)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn musq_config(id: &str) -> ConfigImpl {
+        let mut config = crate::r#impl::musq::config::Config::from_session(
+            "secret",
+            crate::r#impl::musq::config::DeviceType::Android,
+        );
+        config.apply_id(id);
+        ConfigImpl::Musq(config)
+    }
+
+    #[test]
+    fn test_bundle_encrypt_roundtrip() {
+        let mut bundle = AccountBundle::new(&[musq_config("first"), musq_config("second")]);
+        bundle.encrypt("hunter2").unwrap();
+        assert!(bundle.vault().is_some() && bundle.accounts.is_empty());
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert!(!json.contains("\"accounts\""));
+
+        let mut bundle: AccountBundle = serde_json::from_str(&json).unwrap();
+        let key = bundle.vault().unwrap().unlock("hunter2").unwrap();
+        bundle.decrypt(&key).unwrap();
+        assert_eq!(bundle.accounts.len(), 2);
+        assert_eq!(bundle.accounts[1].to_config().unwrap().get_id(), "second");
+
+        let mut newer = bundle.accounts[0].clone();
+        newer.schema = CONFIG_SCHEMA + 1;
+        assert!(newer.to_config().is_err());
+    }
+}
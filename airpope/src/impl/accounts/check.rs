@@ -0,0 +1,247 @@
+use serde::Serialize;
+
+use crate::{
+    config::ConfigImpl,
+    r#impl::{config::proxy::ProxyRouter, notify::EventKind, Implementations},
+};
+
+/// The session state of an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AccountStatus {
+    /// The session works.
+    Valid,
+    /// The session is rejected by the source, it needs to be authenticated again.
+    Expired,
+    /// The check failed for other reason, ex: network error.
+    Error,
+}
+
+impl AccountStatus {
+    pub(crate) fn to_name(self) -> &'static str {
+        match self {
+            AccountStatus::Valid => "valid",
+            AccountStatus::Expired => "expired",
+            AccountStatus::Error => "error",
+        }
+    }
+}
+
+/// The result of checking an account.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountCheck {
+    pub(crate) source: Implementations,
+    pub(crate) id: String,
+    pub(crate) status: AccountStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reason: Option<String>,
+}
+
+/// Call the cheapest authenticated endpoint of the source with the account.
+async fn probe_account(config: ConfigImpl, proxy: Option<reqwest::Proxy>) -> anyhow::Result<()> {
+    match config {
+        ConfigImpl::Kmkc(config) => {
+            let client = crate::r#impl::client::make_kmkc_client(&config.into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            client.get_account().await?;
+        }
+        ConfigImpl::Musq(config) => {
+            let client = crate::r#impl::client::make_musq_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            client.get_account().await?;
+        }
+        ConfigImpl::Amap(config) => {
+            let client = crate::r#impl::client::make_amap_client(&config.into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            client.get_remainder().await?;
+        }
+        ConfigImpl::Sjv(config) => {
+            let client = crate::r#impl::client::make_sjv_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            client.get_entitlements().await?;
+        }
+        ConfigImpl::Rbean(config) => {
+            let client = crate::r#impl::client::make_rbean_client(&config);
+            let mut client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            client.get_user().await?;
+            crate::r#impl::rbean::common::save_session_config(&client, &config);
+        }
+    }
+
+    Ok(())
+}
+
+/// The refresh token errors of RB meaning the session is gone, from the Google secure token API.
+const RB_REJECTED_TOKEN_ERRORS: [&str; 4] = [
+    "TOKEN_EXPIRED",
+    "INVALID_REFRESH_TOKEN",
+    "USER_DISABLED",
+    "USER_NOT_FOUND",
+];
+
+/// Check if the source rejected the session, as opposed to any other error (maintenance,
+/// rate limit, geo block, network error, ...).
+///
+/// KM, AM and SJ answer every failure with the same error body and no code for a rejected
+/// session, so only an HTTP 401 counts for them.
+fn is_session_rejected(source: Implementations, err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<reqwest::Error>() {
+        Some(err) => err.status() == Some(reqwest::StatusCode::UNAUTHORIZED),
+        None => {
+            let message = err.to_string();
+            match source {
+                Implementations::Musq => message.starts_with("MU! request failed with status: 401"),
+                Implementations::Rbean => {
+                    message.starts_with("Request failed with status: 401")
+                        || (message.starts_with("Failed to refresh token")
+                            && RB_REJECTED_TOKEN_ERRORS
+                                .iter()
+                                .any(|code| message.ends_with(code)))
+                }
+                Implementations::Kmkc | Implementations::Amap | Implementations::Sjv => false,
+            }
+        }
+    }
+}
+
+/// The event to notify when an account request fails, only a rejected session is an
+/// authentication failure.
+pub(crate) fn account_error_event(source: Implementations, err: &anyhow::Error) -> EventKind {
+    if is_session_rejected(source, err) {
+        EventKind::AuthFailed
+    } else {
+        EventKind::DownloadError
+    }
+}
+
+/// Tell a session rejected by the source apart from the other errors.
+///
+/// Expired accounts can be revoked, so anything else is an error.
+fn classify_error(source: Implementations, err: &anyhow::Error) -> AccountStatus {
+    if is_session_rejected(source, err) {
+        AccountStatus::Expired
+    } else {
+        AccountStatus::Error
+    }
+}
+
+/// The reason of the error, without the request URL since some sources put the session in it.
+fn error_reason(err: anyhow::Error) -> String {
+    match err.downcast::<reqwest::Error>() {
+        Ok(err) => err.without_url().to_string(),
+        Err(err) => err.to_string(),
+    }
+}
+
+/// Check a single account, a panic while parsing the response is reported as an error.
+pub(crate) async fn check_account(config: ConfigImpl, proxies: &ProxyRouter) -> AccountCheck {
+    let source = config.implementation();
+    let id = config.get_id().to_string();
+    let proxy = proxies.for_account(source, &id).await;
+    let proxy_lease = proxies.lease(source, &id);
+
+    let (status, reason) = match tokio::spawn(probe_account(config, proxy)).await {
+        Ok(Ok(())) => (AccountStatus::Valid, None),
+        Ok(Err(err)) => {
+            proxy_lease.report_failure(&err);
+            (classify_error(source, &err), Some(error_reason(err)))
+        }
+        Err(err) if err.is_panic() => (
+            AccountStatus::Error,
+            Some("unexpected response from the source".to_string()),
+        ),
+        Err(err) => (AccountStatus::Error, Some(err.to_string())),
+    };
+
+    AccountCheck {
+        source,
+        id,
+        status,
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_error() {
+        let classify =
+            |source, message: &str| classify_error(source, &anyhow::anyhow!("{}", message));
+
+        let unauthorized = "MU! request failed with status: 401 Unauthorized";
+        assert_eq!(
+            classify(Implementations::Musq, unauthorized),
+            AccountStatus::Expired
+        );
+        let rb_refresh = "Failed to refresh token (400 Bad Request): TOKEN_EXPIRED";
+        assert_eq!(
+            classify(Implementations::Rbean, rb_refresh),
+            AccountStatus::Expired
+        );
+
+        // geo blocks, proxies, rate limits and maintenances are not a rejected session
+        let forbidden = "MU! request failed with status: 403 Forbidden";
+        assert_eq!(
+            classify(Implementations::Musq, forbidden),
+            AccountStatus::Error
+        );
+        let rate_limited = "Request failed with status: 429 Too Many Requests";
+        assert_eq!(
+            classify(Implementations::Rbean, rate_limited),
+            AccountStatus::Error
+        );
+        let sj = "Response is not OK: subscription expired";
+        assert_eq!(classify(Implementations::Sjv, sj), AccountStatus::Error);
+
+        let km = anyhow::Error::new(airpope_kmkc::models::KMAPIError {
+            error_code: 1,
+            message: "Under maintenance".to_string(),
+        });
+        assert_eq!(
+            classify_error(Implementations::Kmkc, &km),
+            AccountStatus::Error
+        );
+        let am = anyhow::Error::new(airpope_amap::models::AMAPIError {
+            message: "Too many requests".to_string(),
+        });
+        assert_eq!(
+            classify_error(Implementations::Amap, &am),
+            AccountStatus::Error
+        );
+
+        let network = anyhow::anyhow!("error sending request: connection refused");
+        assert_eq!(
+            classify_error(Implementations::Kmkc, &network),
+            AccountStatus::Error
+        );
+        assert_eq!(
+            account_error_event(Implementations::Musq, &network),
+            EventKind::DownloadError
+        );
+        assert_eq!(
+            account_error_event(
+                Implementations::Musq,
+                &anyhow::anyhow!("MU! request failed with status: 401 Unauthorized")
+            ),
+            EventKind::AuthFailed
+        );
+    }
+}
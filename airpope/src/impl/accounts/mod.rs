@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+
+use super::Implementations;
+
+pub(crate) mod aliases;
+pub(crate) mod bundle;
+pub(crate) mod check;
+pub(crate) mod runner;
+pub(crate) mod summary;
+
+/// Select the saved accounts across sources.
+#[derive(Args, Clone, Default)]
+pub(crate) struct AccountFilterArgs {
+    /// Only the accounts of the given source
+    #[arg(short = 's', long = "source", value_enum)]
+    pub(crate) sources: Vec<Implementations>,
+    /// Only the given account ID or alias
+    #[arg(short = 'a', long = "account")]
+    pub(crate) account_ids: Vec<String>,
+    /// Only the accounts with the given session status, the sessions are checked first
+    #[arg(long = "status", value_enum)]
+    pub(crate) status: Option<check::AccountStatus>,
+    /// Only the accounts not used for the given number of days, or never used
+    #[arg(long = "unused-for", value_name = "DAYS")]
+    pub(crate) unused_for: Option<u64>,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum AccountsCommands {
+    /// List the saved accounts of every source
+    ///
+    /// This is the default when no subcommand is given.
+    List {
+        #[command(flatten)]
+        filter: AccountFilterArgs,
+        /// Check the session of every account
+        #[arg(short = 'c', long = "check")]
+        check: bool,
+        /// Output the accounts as JSON instead of a table
+        #[arg(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Revoke the saved accounts across sources
+    ///
+    /// Without any filter, the accounts to revoke are prompted.
+    Revoke {
+        #[command(flatten)]
+        filter: AccountFilterArgs,
+        /// Revoke every account matching the filter without selecting them
+        #[arg(long = "all")]
+        all: bool,
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long = "yes")]
+        yes: bool,
+    },
+    /// Export the saved accounts into a single bundle file
+    ///
+    /// Without any filter, the accounts to export are prompted.
+    Export {
+        /// The bundle file to write
+        output: PathBuf,
+        #[command(flatten)]
+        filter: AccountFilterArgs,
+        /// Export every account matching the filter without selecting them
+        #[arg(long = "all")]
+        all: bool,
+        /// Encrypt the bundle with a passphrase
+        #[arg(short = 'e', long = "encrypt")]
+        encrypt: bool,
+    },
+    /// Import the accounts from a bundle file
+    Import {
+        /// The bundle file to read
+        input: PathBuf,
+        /// Replace the existing accounts with the same ID
+        #[arg(long = "overwrite")]
+        overwrite: bool,
+    },
+    /// Check if the saved sessions still work
+    ///
+    /// Every account is checked with a cheap authenticated request of its source.
+    Check {
+        /// Only check the accounts of the given source
+        #[arg(short = 's', long = "source", value_enum)]
+        sources: Vec<Implementations>,
+        /// Output the result as JSON instead of a table
+        #[arg(short = 'j', long = "json")]
+        json: bool,
+        /// Revoke the expired accounts without prompting
+        #[arg(long = "revoke")]
+        revoke: bool,
+    },
+    /// Give an account a name usable in place of its ID with `-a`
+    Alias {
+        /// The source of the account
+        #[arg(value_enum)]
+        source: Implementations,
+        /// The account ID or its current alias
+        account_id: String,
+        /// The new alias, replacing the current one
+        alias: String,
+    },
+    /// Remove an account alias
+    Unalias {
+        /// The source of the account
+        #[arg(value_enum)]
+        source: Implementations,
+        /// The alias to remove
+        alias: String,
+    },
+    /// Show or set the account used when no account is given
+    ///
+    /// The default account is used before prompting for an account.
+    Default {
+        /// The source of the account
+        #[arg(value_enum)]
+        source: Implementations,
+        /// The account ID or alias to use as the default
+        account_id: Option<String>,
+        /// Remove the default account of the source
+        #[arg(long = "unset", conflicts_with = "account_id")]
+        unset: bool,
+    },
+}
@@ -0,0 +1,607 @@
+use std::{collections::HashSet, path::Path};
+
+use clap::ValueEnum;
+use color_print::cformat;
+
+use crate::{
+    cli::ExitCode,
+    config::{get_all_config, get_config, save_config, try_remove_config, ConfigImpl},
+    r#impl::{
+        config::{
+            proxy::ProxyRouter,
+            vault::{ask_passphrase, prompt_new_passphrase},
+        },
+        Implementations,
+    },
+    term::ConsoleChoice,
+};
+
+use super::{
+    aliases::{get_aliases, AccountAliases, ALIASES_FILE},
+    bundle::AccountBundle,
+    check::{check_account, AccountCheck, AccountStatus},
+    summary::{collect_summaries, AccountSummary},
+    AccountFilterArgs,
+};
+
+/// Every saved account of the given sources, or of every source when empty.
+fn collect_accounts(sources: &[Implementations]) -> Vec<ConfigImpl> {
+    Implementations::value_variants()
+        .iter()
+        .filter(|source| sources.is_empty() || sources.contains(source))
+        .flat_map(|source| get_all_config(source, None))
+        .collect()
+}
+
+/// Prompt which of the accounts to use, `None` when aborted.
+fn prompt_summaries(
+    prompt: &str,
+    summaries: Vec<AccountSummary>,
+    console: &crate::term::Terminal,
+) -> Option<Vec<AccountSummary>> {
+    let choices: Vec<ConsoleChoice> = summaries
+        .iter()
+        .enumerate()
+        .map(|(idx, summary)| ConsoleChoice {
+            name: idx.to_string(),
+            value: summary.label(),
+        })
+        .collect();
+
+    let selected = console.select(prompt, choices)?;
+    let selected: HashSet<String> = selected.into_iter().map(|choice| choice.name).collect();
+    Some(
+        summaries
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| selected.contains(&idx.to_string()))
+            .map(|(_, summary)| summary)
+            .collect(),
+    )
+}
+
+fn format_last_used(last_used: Option<i64>) -> String {
+    match last_used.and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0)) {
+        Some(last_used) => last_used
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        None => "never".to_string(),
+    }
+}
+
+fn print_accounts_table(summaries: &[AccountSummary]) {
+    // the default account is marked with `*`
+    let id_width = summaries
+        .iter()
+        .map(|s| s.id.len() + usize::from(s.is_default))
+        .max()
+        .unwrap_or(0)
+        .max(2);
+    let alias_width = summaries
+        .iter()
+        .map(|s| s.alias.as_ref().map_or(0, |alias| alias.len()))
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let user_width = summaries
+        .iter()
+        .map(|s| s.user.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let device_width = summaries
+        .iter()
+        .map(|s| s.device.len())
+        .max()
+        .unwrap_or(0)
+        .max(6);
+
+    println!(
+        "{:<6} {:<id_width$} {:<alias_width$} {:<user_width$} {:<device_width$} {:<16} Status",
+        "Source", "ID", "Alias", "User", "Device", "Last used"
+    );
+    for summary in summaries.iter() {
+        let id = if summary.is_default {
+            format!("{}*", summary.id)
+        } else {
+            summary.id.clone()
+        };
+        let status = match (summary.status, &summary.reason) {
+            (Some(status), Some(reason)) => format!("{} ({})", status.to_name(), reason),
+            (Some(status), None) => status.to_name().to_string(),
+            (None, _) => "-".to_string(),
+        };
+
+        println!(
+            "{:<6} {:<id_width$} {:<alias_width$} {:<user_width$} {:<device_width$} {:<16} {}",
+            summary.source.source_name(),
+            id,
+            summary.alias.as_deref().unwrap_or("-"),
+            if summary.user.is_empty() {
+                "-"
+            } else {
+                &summary.user
+            },
+            summary.device,
+            format_last_used(summary.last_used),
+            status
+        );
+    }
+}
+
+pub(crate) async fn accounts_list(
+    filter: AccountFilterArgs,
+    check: bool,
+    json: bool,
+    proxies: &ProxyRouter,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let aliases = get_aliases(console);
+    let summaries = collect_summaries(&filter, check, proxies, &aliases, console).await;
+
+    if json {
+        return match serde_json::to_string_pretty(&summaries) {
+            Ok(content) => {
+                println!("{}", content);
+                0
+            }
+            Err(err) => {
+                console.error(&format!("Failed to serialize the accounts: {}", err));
+                1
+            }
+        };
+    }
+
+    if summaries.is_empty() {
+        console.warn("No accounts found!");
+        return 1;
+    }
+
+    console.info(&cformat!("Found <m,s>{}</> accounts:", summaries.len()));
+    print_accounts_table(&summaries);
+    if summaries.iter().any(|s| s.is_default) {
+        console.info("* is the default account of the source");
+    }
+
+    0
+}
+
+pub(crate) async fn accounts_revoke(
+    filter: AccountFilterArgs,
+    all: bool,
+    yes: bool,
+    proxies: &ProxyRouter,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let aliases = get_aliases(console);
+    let mut summaries = collect_summaries(&filter, false, proxies, &aliases, console).await;
+
+    if !all && filter.is_empty() && !summaries.is_empty() {
+        summaries = match prompt_summaries("Select the accounts to revoke", summaries, console) {
+            Some(summaries) => summaries,
+            None => {
+                console.warn("Aborted");
+                return 1;
+            }
+        };
+    }
+
+    if summaries.is_empty() {
+        console.warn("No accounts to revoke");
+        return 1;
+    }
+
+    console.info(&cformat!("Revoking <m,s>{}</> accounts:", summaries.len()));
+    for summary in summaries.iter() {
+        console.info(&cformat!("  - <m,s>{}</>", summary.label()));
+    }
+    if !yes && !console.confirm(Some("Are you sure?\nThis action is irreversible!")) {
+        console.warn("Aborted");
+        return 0;
+    }
+
+    let mut failed = 0;
+    for summary in summaries.iter() {
+        match try_remove_config(&summary.id, summary.source, None) {
+            Ok(_) => console.info(&cformat!("Revoked <m,s>{}</>", summary.label())),
+            Err(err) => {
+                console.error(&cformat!(
+                    "Failed to revoke <m,s>{}</>: {}",
+                    summary.label(),
+                    err
+                ));
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+pub(crate) async fn accounts_export(
+    output: &Path,
+    filter: AccountFilterArgs,
+    all: bool,
+    encrypt: bool,
+    proxies: &ProxyRouter,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let aliases = get_aliases(console);
+    let mut summaries = collect_summaries(&filter, false, proxies, &aliases, console).await;
+
+    if !all && filter.is_empty() && !summaries.is_empty() {
+        summaries = match prompt_summaries("Select the accounts to export", summaries, console) {
+            Some(summaries) => summaries,
+            None => {
+                console.warn("Aborted");
+                return 1;
+            }
+        };
+    }
+
+    if summaries.is_empty() {
+        console.warn("No accounts to export");
+        return 1;
+    }
+
+    let configs: Vec<ConfigImpl> = summaries.into_iter().map(|s| s.config).collect();
+    let mut bundle = AccountBundle::new(&configs);
+    if encrypt {
+        let passphrase = match prompt_new_passphrase(true, console) {
+            Some(passphrase) => passphrase,
+            None => return 1,
+        };
+
+        console.info("Encrypting the bundle...");
+        if let Err(err) = bundle.encrypt(&passphrase) {
+            console.error(&format!("Failed to encrypt the bundle: {}", err));
+            return 1;
+        }
+    }
+
+    if let Err(err) = bundle.save(output) {
+        console.error(&format!("Failed to write the bundle: {}", err));
+        return 1;
+    }
+
+    console.info(&cformat!(
+        "Exported <m,s>{}</> accounts to <s>{}</>",
+        configs.len(),
+        output.display()
+    ));
+    if !encrypt {
+        console.warn("The bundle is not encrypted, anyone with the file can use the accounts");
+    }
+
+    0
+}
+
+pub(crate) fn accounts_import(
+    input: &Path,
+    overwrite: bool,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let mut bundle = match AccountBundle::load(input) {
+        Ok(bundle) => bundle,
+        Err(err) => {
+            console.error(&format!("Failed to read the bundle: {}", err));
+            return 1;
+        }
+    };
+
+    if let Some(vault) = bundle.vault() {
+        let decrypted =
+            ask_passphrase(vault, "Passphrase of the bundle:").and_then(|key| bundle.decrypt(&key));
+        if let Err(err) = decrypted {
+            console.error(&format!("Failed to decrypt the bundle: {}", err));
+            return 1;
+        }
+    }
+
+    let total = bundle.accounts.len();
+    let mut seen = HashSet::new();
+    let (mut imported, mut skipped, mut failed) = (0, 0, 0);
+    for account in bundle.accounts.iter() {
+        let label = format!("{} {}", account.source.source_name(), account.id);
+        if !seen.insert((account.source, account.id.clone())) {
+            console.warn(&cformat!(
+                "  <m,s>{}</> is in the bundle more than once, skipping",
+                label
+            ));
+            skipped += 1;
+            continue;
+        }
+
+        let config = match account.to_config() {
+            Ok(config) => config,
+            Err(err) => {
+                console.error(&cformat!("  Failed to read <m,s>{}</>: {}", label, err));
+                failed += 1;
+                continue;
+            }
+        };
+
+        if !overwrite && get_config(&account.id, &account.source, None).is_some() {
+            console.warn(&cformat!(
+                "  <m,s>{}</> already exists, skipping (use --overwrite to replace it)",
+                label
+            ));
+            skipped += 1;
+            continue;
+        }
+
+        save_config(config, None);
+        console.info(&cformat!("  Imported <m,s>{}</>", label));
+        imported += 1;
+    }
+
+    console.info(&cformat!(
+        "Imported <m,s>{}</> of {} accounts, {} skipped, {} failed",
+        imported,
+        total,
+        skipped,
+        failed
+    ));
+
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn print_check_table(checks: &[AccountCheck]) {
+    let id_width = checks
+        .iter()
+        .map(|check| check.id.len())
+        .max()
+        .unwrap_or(0)
+        .max(2);
+
+    println!(
+        "{:<6} {:<id_width$} {:<8}  Reason",
+        "Source", "ID", "Status"
+    );
+    for check in checks.iter() {
+        println!(
+            "{:<6} {:<id_width$} {:<8}  {}",
+            check.source.source_name(),
+            check.id,
+            check.status.to_name(),
+            check.reason.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+pub(crate) async fn accounts_check(
+    sources: Vec<Implementations>,
+    json: bool,
+    revoke: bool,
+    proxies: &ProxyRouter,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let configs = collect_accounts(&sources);
+    if configs.is_empty() {
+        console.warn("No accounts found!");
+        return 1;
+    }
+
+    if !json {
+        console.info(&cformat!("Checking <m,s>{}</> accounts...", configs.len()));
+    }
+
+    let mut checks = vec![];
+    for config in configs {
+        checks.push(check_account(config, proxies).await);
+    }
+
+    if json {
+        match serde_json::to_string_pretty(&checks) {
+            Ok(content) => println!("{}", content),
+            Err(err) => {
+                console.error(&format!("Failed to serialize the result: {}", err));
+                return 1;
+            }
+        }
+    } else {
+        print_check_table(&checks);
+    }
+
+    let expired: Vec<&AccountCheck> = checks
+        .iter()
+        .filter(|check| check.status == AccountStatus::Expired)
+        .collect();
+    let has_error = checks
+        .iter()
+        .any(|check| check.status == AccountStatus::Error);
+
+    if expired.is_empty() {
+        return if has_error { 1 } else { 0 };
+    }
+
+    let revoke = revoke
+        || (!json
+            && console.confirm(Some(&format!(
+                "Revoke the {} expired accounts?\nThis action is irreversible!",
+                expired.len()
+            ))));
+    if !revoke {
+        return 1;
+    }
+
+    for check in expired {
+        let label = format!("{} {}", check.source.source_name(), check.id);
+        match try_remove_config(&check.id, check.source, None) {
+            Ok(_) => console.info(&cformat!("Revoked <m,s>{}</>", label)),
+            Err(err) => console.error(&cformat!("Failed to revoke <m,s>{}</>: {}", label, err)),
+        }
+    }
+
+    if has_error {
+        1
+    } else {
+        0
+    }
+}
+
+/// Load the aliases to change them, an invalid file is an error so it's never overwritten.
+fn load_aliases(console: &crate::term::Terminal) -> Option<AccountAliases> {
+    match AccountAliases::load(&AccountAliases::default_path()) {
+        Ok(aliases) => Some(aliases),
+        Err(err) => {
+            console.error(&format!("Failed to read {}: {}", ALIASES_FILE, err));
+            None
+        }
+    }
+}
+
+fn save_aliases(aliases: &AccountAliases, console: &crate::term::Terminal) -> ExitCode {
+    match aliases.save(&AccountAliases::default_path()) {
+        Ok(_) => 0,
+        Err(err) => {
+            console.error(&format!("Failed to save the aliases: {}", err));
+            1
+        }
+    }
+}
+
+pub(crate) fn accounts_alias(
+    source: Implementations,
+    account_id: &str,
+    alias: &str,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let mut aliases = match load_aliases(console) {
+        Some(aliases) => aliases,
+        None => return 1,
+    };
+    let account_id = aliases.resolve_account_id(source, account_id);
+    if get_config(&account_id, &source, None).is_none() {
+        console.error(&format!("Account ID {} not found!", account_id));
+        return 1;
+    }
+    if alias.is_empty() {
+        console.error("The alias can't be empty");
+        return 1;
+    }
+    if alias != account_id && get_config(alias, &source, None).is_some() {
+        console.error(&format!(
+            "The alias {} is already an account ID of {}",
+            alias,
+            source.source_name()
+        ));
+        return 1;
+    }
+
+    if let Err(err) = aliases.set_alias(source, &account_id, alias) {
+        console.error(&format!("Failed to set the alias: {}", err));
+        return 1;
+    }
+
+    let exit_code = save_aliases(&aliases, console);
+    if exit_code == 0 {
+        console.info(&cformat!(
+            "<m,s>{} {}</> is now known as <s>{}</>",
+            source.source_name(),
+            account_id,
+            alias
+        ));
+    }
+    exit_code
+}
+
+pub(crate) fn accounts_unalias(
+    source: Implementations,
+    alias: &str,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let mut aliases = match load_aliases(console) {
+        Some(aliases) => aliases,
+        None => return 1,
+    };
+    let account_id = match aliases.remove_alias(source, alias) {
+        Some(account_id) => account_id,
+        None => {
+            console.error(&format!(
+                "No {} account with the alias {}",
+                source.source_name(),
+                alias
+            ));
+            return 1;
+        }
+    };
+
+    let exit_code = save_aliases(&aliases, console);
+    if exit_code == 0 {
+        console.info(&cformat!(
+            "Removed the alias <s>{}</> of <m,s>{} {}</>",
+            alias,
+            source.source_name(),
+            account_id
+        ));
+    }
+    exit_code
+}
+
+pub(crate) fn accounts_default(
+    source: Implementations,
+    account_id: Option<String>,
+    unset: bool,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let mut aliases = match load_aliases(console) {
+        Some(aliases) => aliases,
+        None => return 1,
+    };
+
+    if unset {
+        aliases.set_default(source, None);
+        let exit_code = save_aliases(&aliases, console);
+        if exit_code == 0 {
+            console.info(&cformat!(
+                "Removed the default account of <m,s>{}</>",
+                source.source_name()
+            ));
+        }
+        return exit_code;
+    }
+
+    let account_id = match account_id {
+        Some(account_id) => aliases.resolve_account_id(source, &account_id),
+        None => {
+            match aliases.default_of(source) {
+                Some(default_id) => console.info(&cformat!(
+                    "The default account of <m,s>{}</> is <s>{}</>",
+                    source.source_name(),
+                    default_id
+                )),
+                None => console.info(&cformat!(
+                    "No default account for <m,s>{}</>",
+                    source.source_name()
+                )),
+            }
+            return 0;
+        }
+    };
+
+    if get_config(&account_id, &source, None).is_none() {
+        console.error(&format!("Account ID {} not found!", account_id));
+        return 1;
+    }
+
+    aliases.set_default(source, Some(account_id.clone()));
+    let exit_code = save_aliases(&aliases, console);
+    if exit_code == 0 {
+        console.info(&cformat!(
+            "The default account of <m,s>{}</> is now <s>{}</>",
+            source.source_name(),
+            account_id
+        ));
+    }
+    exit_code
+}
@@ -0,0 +1,142 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    config::{get_all_config, ConfigImpl},
+    r#impl::{config::proxy::ProxyRouter, kmkc::config::Config as KMConfig, Implementations},
+};
+
+use super::{
+    aliases::AccountAliases,
+    check::{check_account, AccountStatus},
+    AccountFilterArgs,
+};
+
+/// A saved account of any source, with its alias, usage and session status.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountSummary {
+    pub(crate) source: Implementations,
+    pub(crate) id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) alias: Option<String>,
+    /// The email or user name, empty when the source has none.
+    pub(crate) user: String,
+    /// The device type and platform.
+    pub(crate) device: String,
+    pub(crate) is_default: bool,
+    /// Unix timestamp in seconds, `None` when never used.
+    pub(crate) last_used: Option<i64>,
+    /// `None` when the session is not checked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) status: Option<AccountStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reason: Option<String>,
+    #[serde(skip)]
+    pub(crate) config: ConfigImpl,
+}
+
+impl AccountSummary {
+    pub(crate) fn new(config: ConfigImpl, aliases: &AccountAliases) -> Self {
+        let (user, device) = match &config {
+            ConfigImpl::Kmkc(KMConfig::Mobile(c)) => (
+                c.email.clone(),
+                format!("{} - {}", c.r#type().to_name(), c.platform().to_name()),
+            ),
+            ConfigImpl::Kmkc(KMConfig::Web(c)) => {
+                (c.email.clone(), c.r#type().to_name().to_string())
+            }
+            ConfigImpl::Musq(c) => (String::new(), c.r#type().to_name().to_string()),
+            ConfigImpl::Amap(c) => (c.email.clone(), c.r#type().to_name().to_string()),
+            ConfigImpl::Sjv(c) => (
+                c.email.clone(),
+                format!("{} - {}", c.r#type().to_name(), c.mode().to_name()),
+            ),
+            ConfigImpl::Rbean(c) => (c.email.clone(), c.platform().to_name().to_string()),
+        };
+
+        let source = config.implementation();
+        let id = config.get_id().to_string();
+        Self {
+            source,
+            alias: aliases.alias_of(source, &id).map(|alias| alias.to_string()),
+            user,
+            device,
+            is_default: aliases.default_of(source) == Some(id.as_str()),
+            last_used: aliases.last_used_of(source, &id),
+            status: None,
+            reason: None,
+            id,
+            config,
+        }
+    }
+
+    /// A short description of the account, ex: `MU abcdef (main)`
+    pub(crate) fn label(&self) -> String {
+        match &self.alias {
+            Some(alias) => format!("{} {} ({})", self.source.source_name(), self.id, alias),
+            None => format!("{} {}", self.source.source_name(), self.id),
+        }
+    }
+
+    fn matches_account(&self, account_id: &str) -> bool {
+        self.id == account_id || self.alias.as_deref() == Some(account_id)
+    }
+}
+
+impl AccountFilterArgs {
+    /// `true` when no filter is given.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+            && self.account_ids.is_empty()
+            && self.status.is_none()
+            && self.unused_for.is_none()
+    }
+}
+
+/// Every saved account matching the filter.
+///
+/// The sessions are checked when `check` is set or when filtering by status.
+pub(crate) async fn collect_summaries(
+    filter: &AccountFilterArgs,
+    check: bool,
+    proxies: &ProxyRouter,
+    aliases: &AccountAliases,
+    console: &crate::term::Terminal,
+) -> Vec<AccountSummary> {
+    let mut summaries: Vec<AccountSummary> = Implementations::value_variants()
+        .iter()
+        .filter(|source| filter.sources.is_empty() || filter.sources.contains(source))
+        .flat_map(|source| get_all_config(source, None))
+        .map(|config| AccountSummary::new(config, aliases))
+        .collect();
+
+    if !filter.account_ids.is_empty() {
+        for account_id in filter.account_ids.iter() {
+            if !summaries.iter().any(|s| s.matches_account(account_id)) {
+                console.warn(&format!("Account ID {} not found!", account_id));
+            }
+        }
+        summaries.retain(|s| filter.account_ids.iter().any(|id| s.matches_account(id)));
+    }
+
+    if let Some(days) = filter.unused_for {
+        // never used accounts count as unused
+        let cutoff = chrono::Utc::now().timestamp() - (days as i64) * 24 * 60 * 60;
+        summaries.retain(|s| s.last_used.is_none_or(|last_used| last_used < cutoff));
+    }
+
+    if check || filter.status.is_some() {
+        for summary in summaries.iter_mut() {
+            let result = check_account(summary.config.clone(), proxies).await;
+            summary.status = Some(result.status);
+            summary.reason = result.reason;
+        }
+    }
+
+    if let Some(status) = filter.status {
+        summaries.retain(|s| s.status == Some(status));
+    }
+
+    summaries
+}
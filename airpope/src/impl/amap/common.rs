@@ -11,6 +11,9 @@ use crate::r#impl::common::unix_timestamp_to_string;
 use crate::{
     config::save_config,
     linkify,
+    r#impl::notify::{notify, NotifyEvent},
+    r#impl::accounts::check::account_error_event,
+    r#impl::Implementations,
     term::{get_console, ConsoleChoice},
 };
 
@@ -197,13 +200,19 @@ pub(super) async fn common_purchase_select(
         }
         Err(e) => {
             console.error(&cformat!("Unable to connect to AM: {}", e));
+            notify(
+                NotifyEvent::new(account_error_event(Implementations::Amap, &e), "AM")
+                    .with_message(format!("{} ({})", e, account.email)),
+                console,
+            )
+            .await;
 
             (Err(e), None, None)
         }
     }
 }
 
-pub(super) fn save_session_config(client: &AMClient, config: &Config) {
+pub(crate) fn save_session_config(client: &AMClient, config: &Config) {
     let mut config = config.clone();
     let masked_cookie = SESSION_COOKIE_NAME.as_str();
     let store = client.get_cookie_store();
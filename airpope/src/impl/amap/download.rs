@@ -3,16 +3,31 @@ use std::path::{Path, PathBuf};
 use color_print::cformat;
 use airpope_amap::{
     helper::ComicPurchase,
-    models::{ComicEpisodeInfo, ComicInfo},
+    models::{ComicEpisodeInfo, ComicInfo, IAPInfo},
     AMClient,
 };
 
 use crate::{
     cli::ExitCode,
+    r#impl::batch::manifest::BatchJob,
+    r#impl::budget::{BudgetGuard, PurchaseCost},
     r#impl::models::{ChapterDetailDump, MangaDetailDump},
+    r#impl::common::is_shutdown_requested,
+    r#impl::hooks::PostHooks,
+    r#impl::ledger::record_purchase,
+    r#impl::notify::{notify, EventKind, NotifyEvent},
+    r#impl::plan::{DownloadPlan, PlanAction},
+    r#impl::queue::QueueTracker,
+    r#impl::config::proxy::ProxyLease,
+    r#impl::sync::report_title_sync,
+    r#impl::tools::trim::trim_downloaded_chapter,
+    r#impl::Implementations,
 };
 
-use super::{common::common_purchase_select, config::Config};
+use super::{
+    common::{common_purchase_select, save_session_config},
+    config::Config,
+};
 
 #[derive(Clone, Debug, Default)]
 pub(crate) struct AMDownloadCliConfig {
@@ -34,6 +49,16 @@ pub(crate) struct AMDownloadCliConfig {
     // Ticket related
     pub(crate) no_premium: bool,
     pub(crate) no_purchased: bool,
+    /// Trim uniform borders of the downloaded pages with the given tolerance.
+    pub(crate) trim: Option<u8>,
+    /// The commands to run after each chapter and the title are downloaded.
+    pub(crate) hooks: PostHooks,
+    /// How the download is recorded in the download queue.
+    pub(crate) queue: QueueTracker,
+    /// The proxy of the requests, to report its failures.
+    pub(crate) proxy: ProxyLease,
+    /// Only print the download plan without purchasing or downloading anything.
+    pub(crate) dry_run: bool,
 }
 
 fn check_downloaded_image_count(image_dir: &PathBuf) -> Option<usize> {
@@ -60,7 +85,7 @@ fn check_downloaded_image_count(image_dir: &PathBuf) -> Option<usize> {
     Some(count)
 }
 
-fn create_chapters_info(manga_detail: ComicInfo) -> MangaDetailDump {
+fn create_chapters_info(title_id: u64, manga_detail: ComicInfo) -> MangaDetailDump {
     let mut chapters: Vec<ChapterDetailDump> = vec![];
     for chapter in manga_detail.episodes {
         chapters.push(ChapterDetailDump::from(chapter));
@@ -74,9 +99,102 @@ fn create_chapters_info(manga_detail: ComicInfo) -> MangaDetailDump {
         .join(", ");
 
     MangaDetailDump::new(manga_detail.title, merged_authors, chapters)
+        .with_source("AM")
+        .with_title_id(title_id)
+}
+
+/// The cost of a purchase and the currency it's paid with.
+fn purchase_cost(consume: &ComicPurchase) -> (PurchaseCost, &'static str) {
+    match consume.point {
+        // the points are bought, so a free only budget must block them
+        Some(point) => (PurchaseCost::new(point, point), "point"),
+        None => (
+            PurchaseCost::new(
+                consume.bonus + consume.purchased + consume.premium,
+                consume.purchased,
+            ),
+            "ticket",
+        ),
+    }
+}
+
+/// The tickets or points spent by a purchase, per currency.
+fn consume_costs(consume: &ComicPurchase) -> Vec<(String, u64)> {
+    match consume.point {
+        Some(point) => vec![("point".to_string(), point)],
+        None => vec![
+            ("bonus ticket".to_string(), consume.bonus),
+            ("purchased ticket".to_string(), consume.purchased),
+            ("premium ticket".to_string(), consume.premium),
+        ],
+    }
+}
+
+/// Simulate the ticket purchases of the chapters for `--dry-run`.
+fn plan_download(
+    chapters: &[&ComicEpisodeInfo],
+    manga_detail: &ComicInfo,
+    ticket_purse: &IAPInfo,
+    dl_config: &AMDownloadCliConfig,
+) -> DownloadPlan {
+    // interactive download would prompt for every purchase, assume it got confirmed
+    let would_purchase = dl_config.auto_purchase || !dl_config.no_input;
+    let mut ticket_purse = ticket_purse.clone();
+
+    let mut plan = DownloadPlan::default();
+    for chapter in chapters {
+        let (title, id) = (&chapter.info.title, chapter.info.id);
+        if chapter.info.is_available() {
+            let action = if chapter.info.is_free() {
+                PlanAction::Free
+            } else {
+                PlanAction::Owned
+            };
+            plan.add(title, id, action);
+            continue;
+        }
+
+        let consume = match ComicPurchase::from_episode_and_comic(
+            manga_detail,
+            &chapter.info,
+            &mut ticket_purse,
+        ) {
+            Some(consume) => consume,
+            None => {
+                plan.add(
+                    title,
+                    id,
+                    PlanAction::Skip("not available for purchase".to_string()),
+                );
+                continue;
+            }
+        };
+
+        if !would_purchase {
+            plan.add(
+                title,
+                id,
+                PlanAction::Skip("purchase is disabled".to_string()),
+            );
+            continue;
+        }
+
+        let action = if consume.is_free_daily {
+            PlanAction::Ticket("free daily".to_string())
+        } else {
+            PlanAction::Purchase(consume_costs(&consume))
+        };
+
+        ticket_purse.bonus = ticket_purse.bonus.saturating_sub(consume.bonus);
+        ticket_purse.purchased = ticket_purse.purchased.saturating_sub(consume.purchased);
+        ticket_purse.premium = ticket_purse.premium.saturating_sub(consume.premium);
+        plan.add(title, id, action);
+    }
+
+    plan
 }
 
-fn get_output_directory(
+pub(crate) fn get_output_directory(
     output_dir: &Path,
     title_id: u64,
     chapter_id: Option<u64>,
@@ -141,12 +259,21 @@ pub(crate) async fn amap_download(
                             || dl_config.chapter_ids.contains(&(ch.info.id as usize))
                     }
                 })
+                .filter(|&ch| dl_config.queue.accepts(ch.info.id))
                 .collect();
 
             if results.is_empty() {
                 return 1;
             }
 
+            let mut budget = match BudgetGuard::load(Implementations::Amap, &account.id, title_id) {
+                Ok(budget) => budget,
+                Err(err) => {
+                    console.error(&format!("Failed to load the budgets: {}", err));
+                    return 1;
+                }
+            };
+
             let mut ticket_purse = coin_purse.clone();
 
             if dl_config.no_premium {
@@ -157,6 +284,11 @@ pub(crate) async fn amap_download(
                 ticket_purse.purchased = 0;
             }
 
+            if dl_config.dry_run {
+                plan_download(&results, &manga_detail, &ticket_purse, &dl_config).print(console);
+                return 0;
+            }
+
             console.info(&format!("Downloading {} chapters...", results.len()));
             let mut download_chapters = vec![];
             for chapter in results {
@@ -194,6 +326,11 @@ pub(crate) async fn amap_download(
                     should_purchase = console.confirm(Some(&prompt));
                 }
 
+                let (cost, currency) = purchase_cost(consume.as_ref().unwrap());
+                if should_purchase && !budget.allow(&cost, console) {
+                    continue;
+                }
+
                 if should_purchase {
                     console.info(&cformat!(
                         "  Purchasing chapter <m,s>{}</> (<s>{}</>) with consumption <s>{:?}</>...",
@@ -222,6 +359,20 @@ pub(crate) async fn amap_download(
                                     chapter.info.id
                                 ));
                             } else {
+                                let event = NotifyEvent::new(EventKind::PurchaseMade, "AM")
+                                    .with_title(&manga_detail.title)
+                                    .with_chapter(&chapter.info.title, chapter.info.id)
+                                    .with_cost(cost.amount, currency);
+                                record_purchase(
+                                    Implementations::Amap,
+                                    &account.id,
+                                    title_id,
+                                    &event,
+                                    &consume_costs(&consume),
+                                    console,
+                                );
+                                notify(event, console).await;
+                                budget.record(&cost);
                                 download_chapters.push(chapter);
                                 ticket_purse.bonus -= consume.bonus;
                                 ticket_purse.purchased -= consume.purchased;
@@ -233,6 +384,8 @@ pub(crate) async fn amap_download(
                 }
             }
 
+            budget.report(console);
+
             if download_chapters.is_empty() {
                 console.warn("No chapters to be download after filtering, aborting");
                 return 1;
@@ -241,19 +394,40 @@ pub(crate) async fn amap_download(
             download_chapters.sort_by(|&a, &b| a.info.id.cmp(&b.info.id));
 
             let title_dir = get_output_directory(&output_dir, title_id, None, true);
-            let dump_info = create_chapters_info(manga_detail.clone());
+            let dump_info = create_chapters_info(title_id, manga_detail.clone());
 
             let title_dump_path = title_dir.join("_info.json");
             dump_info
                 .dump(&title_dump_path)
                 .expect("Failed to dump title info");
 
+            let queue = dl_config.queue.start(
+                BatchJob {
+                    account: Some(account.id.clone()),
+                    output: Some(output_dir.clone()),
+                    trim: dl_config.trim,
+                    ..BatchJob::new(Implementations::Amap, title_id)
+                },
+                &manga_detail.title,
+                download_chapters
+                    .iter()
+                    .map(|ch| (ch.info.id.to_string(), ch.info.title.clone()))
+                    .collect(),
+                console,
+            );
+
             for chapter in download_chapters {
+                if is_shutdown_requested() {
+                    console.warn("Shutdown requested, skipping the remaining chapters");
+                    break;
+                }
+
                 console.info(&cformat!(
                     "  Downloading chapter <m,s>{}</> ({})...",
                     chapter.info.title,
                     chapter.info.id
                 ));
+                queue.in_progress(chapter.info.id, console);
 
                 let rent_term = manga_detail.rental_term.clone();
 
@@ -266,12 +440,22 @@ pub(crate) async fn amap_download(
 
                 let ch_view = client.get_comic_viewer(title_id, &consume).await;
                 if let Err(err) = ch_view {
+                    dl_config.proxy.report_failure(&err);
                     console.error(&format!("Failed to download chapter: {}", err));
+                    queue.failed(chapter.info.id, &err, console);
                     console.error(&cformat!(
                         "   Skipping chapter <m,s>{}</> (<s>{}</>)",
                         chapter.info.title,
                         chapter.info.id
                     ));
+                    notify(
+                        NotifyEvent::new(EventKind::DownloadError, "AM")
+                            .with_title(&manga_detail.title)
+                            .with_chapter(&chapter.info.title, chapter.info.id)
+                            .with_message(err),
+                        console,
+                    )
+                    .await;
                     continue;
                 }
 
@@ -282,6 +466,7 @@ pub(crate) async fn amap_download(
                         chapter.info.title,
                         chapter.info.id
                     ));
+                    queue.failed(chapter.info.id, "empty image block", console);
                     continue;
                 }
 
@@ -298,6 +483,7 @@ pub(crate) async fn amap_download(
                             chapter.info.title,
                             chapter.info.id
                         ));
+                        queue.done(chapter.info.id, console);
                         continue;
                     }
                 }
@@ -306,8 +492,12 @@ pub(crate) async fn amap_download(
                 std::fs::create_dir_all(&ch_dir).unwrap();
 
                 // download images
+                let mut failed_pages = 0;
                 let total_image_count = ch_pages.len() as u64;
                 for (idx, image) in ch_pages.iter().enumerate() {
+                    if is_shutdown_requested() {
+                        break;
+                    }
                     let img_fn = format!("p{:03}.jpg", idx);
                     let img_dl_path = ch_dir.join(&img_fn);
                     // async download
@@ -329,6 +519,8 @@ pub(crate) async fn amap_download(
                         Ok(_) => {}
                         Err(err) => {
                             console.error(&format!("    Failed to download image: {}", err));
+                            failed_pages += 1;
+                            dl_config.proxy.report_failure(&err);
                             // silently delete the file
                             tokio::fs::remove_file(&img_dl_path)
                                 .await
@@ -337,6 +529,55 @@ pub(crate) async fn amap_download(
                     }
                 }
                 console.stop_progress(Some("Downloaded".to_string()));
+                if is_shutdown_requested() {
+                    // the next chapter stops the download
+                    queue.interrupted(chapter.info.id, console);
+                    continue;
+                }
+                if failed_pages > 0 {
+                    console.warn(&cformat!(
+                        "   Failed to download {} pages of chapter <m,s>{}</> (<s>{}</>)",
+                        failed_pages,
+                        chapter.info.title,
+                        chapter.info.id
+                    ));
+                    queue.failed(
+                        chapter.info.id,
+                        format!("{} pages failed to download", failed_pages),
+                        console,
+                    );
+                    continue;
+                }
+                if let Some(tolerance) = dl_config.trim {
+                    trim_downloaded_chapter(&ch_dir, Implementations::Amap, tolerance, console)
+                        .await;
+                }
+                queue.done(chapter.info.id, console);
+                notify(
+                    NotifyEvent::new(EventKind::ChapterDownloaded, "AM")
+                        .with_title(&manga_detail.title)
+                        .with_chapter(&chapter.info.title, chapter.info.id),
+                    console,
+                )
+                .await;
+                dl_config
+                    .hooks
+                    .run_chapter(
+                        "AM",
+                        title_id,
+                        &title_dir,
+                        chapter.info.id,
+                        &ch_dir,
+                        console,
+                    )
+                    .await;
+            }
+
+            if !is_shutdown_requested() {
+                dl_config
+                    .hooks
+                    .run_title("AM", title_id, &title_dir, console)
+                    .await;
             }
 
             0
@@ -344,3 +585,54 @@ pub(crate) async fn amap_download(
         _ => 1,
     }
 }
+
+/// Fetch the title and every chapters of it as [`MangaDetailDump`].
+pub(crate) async fn amap_fetch_title_info(
+    title_id: u64,
+    client: &AMClient,
+    account: &Config,
+) -> anyhow::Result<MangaDetailDump> {
+    let manga_detail = client.get_comic(title_id).await?;
+    save_session_config(client, account);
+
+    Ok(create_chapters_info(title_id, manga_detail.info))
+}
+
+/// Compare the remote chapter list of a title with the local downloads.
+///
+/// With `download`, every available chapter will be downloaded without purchasing.
+pub(crate) async fn amap_sync(
+    title_id: u64,
+    download: bool,
+    output_dir: PathBuf,
+    client: &AMClient,
+    account: &Config,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    console.info(&cformat!(
+        "Fetching chapters for ID <m,s>{}</>...",
+        title_id
+    ));
+
+    let remote_info = match amap_fetch_title_info(title_id, client, account).await {
+        Ok(remote_info) => remote_info,
+        Err(e) => {
+            console.error(&format!("Failed to fetch manga: {}", e));
+            return 1;
+        }
+    };
+
+    let title_dir = get_output_directory(&output_dir, title_id, None, false);
+    let report = report_title_sync(&title_dir, &remote_info, console);
+
+    if download && report.has_downloadable() {
+        let dl_config = AMDownloadCliConfig {
+            no_input: true,
+            ..Default::default()
+        };
+
+        return amap_download(title_id, dl_config, output_dir, client, account, console).await;
+    }
+
+    0
+}
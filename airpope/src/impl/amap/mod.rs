@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
 use super::parser::{parse_comma_number, CommaSeparatedNumber};
+use super::tools::trim::DEFAULT_TRIM_TOLERANCE;
 use clap::Subcommand;
 
 pub(crate) mod accounts;
@@ -31,8 +32,11 @@ pub(crate) enum AMAPCommands {
         /// Title ID to use
         title_id: u64,
         /// Disable the auto purchase feature and only download free/purchased chapter(s).
-        #[arg(short = 'n', long)]
+        #[arg(short = 'n', long, overrides_with = "purchase")]
         no_purchase: bool,
+        /// Enable the auto purchase feature, overriding the settings
+        #[arg(long = "purchase", overrides_with = "no_purchase")]
+        purchase: bool,
         /// Specify the starting chapter ID to download
         #[arg(short = 's', long, default_value = None)]
         start_from: Option<u64>,
@@ -40,14 +44,32 @@ pub(crate) enum AMAPCommands {
         #[arg(short = 'e', long, default_value = None)]
         end_until: Option<u64>,
         /// Disable the use of paid ticket to purchase chapters
-        #[arg(long = "no-paid")]
+        #[arg(long = "no-paid", overrides_with = "paid_ticket")]
         no_paid_ticket: bool,
+        /// Allow the use of paid ticket, overriding the settings
+        #[arg(long = "paid", overrides_with = "no_paid_ticket")]
+        paid_ticket: bool,
         /// Disable the use of premium ticket to purchase chapters
-        #[arg(long = "no-premium")]
+        #[arg(long = "no-premium", overrides_with = "premium_ticket")]
         no_premium_ticket: bool,
+        /// Allow the use of premium ticket, overriding the settings
+        #[arg(long = "premium", overrides_with = "no_premium_ticket")]
+        premium_ticket: bool,
         /// Output directory to use
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Get your account ticket balance
     Balance,
@@ -69,6 +91,18 @@ pub(crate) enum AMAPCommands {
         /// Output directory to use
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Get your account favorites list
     Favorites,
@@ -97,4 +131,15 @@ pub(crate) enum AMAPCommands {
         /// Query to search for
         query: String,
     },
+    /// Compare the chapters of a title with the local downloads
+    Sync {
+        /// Title ID to use
+        title_id: u64,
+        /// Download every available chapter without purchasing
+        #[arg(short = 'd', long = "download")]
+        download: bool,
+        /// Output directory to use
+        #[arg(short = 'o', long = "output", default_value = None)]
+        output: Option<PathBuf>,
+    },
 }
@@ -0,0 +1,301 @@
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::r#impl::{
+    config::settings::{get_settings, SourceSettings},
+    musq::download::DownloadImageQuality,
+    rbean::download::CLIDownloadFormat,
+    watch::watchlist::{PurchasePolicy, WatchEntry},
+    Implementations,
+};
+
+/// Accept both `titleId = 123` and `titleId = "123"` in the manifest.
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(u64),
+    }
+
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(value) => Ok(value),
+        StringOrNumber::Number(value) => Ok(value.to_string()),
+    }
+}
+
+/// The values used by every job that does not set them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct BatchDefaults {
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) purchase: Option<PurchasePolicy>,
+    pub(crate) trim: Option<u8>,
+    pub(crate) parallel: Option<bool>,
+}
+
+/// A single title to download.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct BatchJob {
+    pub(crate) source: Implementations,
+    /// The title ID, UUID or slug depending on the source.
+    #[serde(deserialize_with = "string_or_number")]
+    pub(crate) title_id: String,
+    /// The account ID, can be omitted when the source only has one account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) account: Option<String>,
+    /// The first chapter ID to download.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) start: Option<u64>,
+    /// The last chapter ID to download.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) end: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) purchase: Option<PurchasePolicy>,
+    /// The image quality, only for MU.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) quality: Option<String>,
+    /// The image format, only for RB.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) format: Option<String>,
+    /// The output directory, `{source}` and `{account}` are replaced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) trim: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) parallel: Option<bool>,
+}
+
+impl From<&WatchEntry> for BatchJob {
+    fn from(entry: &WatchEntry) -> Self {
+        Self {
+            account: Some(entry.account_id.clone()),
+            purchase: Some(entry.purchase),
+            output: entry.output.clone(),
+            trim: entry.trim,
+            ..Self::new(entry.source, &entry.title_id)
+        }
+    }
+}
+
+impl BatchJob {
+    pub(crate) fn new(source: Implementations, title_id: impl ToString) -> Self {
+        Self {
+            source,
+            title_id: title_id.to_string(),
+            account: None,
+            start: None,
+            end: None,
+            purchase: None,
+            quality: None,
+            format: None,
+            output: None,
+            trim: None,
+            parallel: None,
+        }
+    }
+
+    /// A short description of the job, ex: `KM 12345`
+    pub(crate) fn label(&self) -> String {
+        format!("{} {}", self.source.source_name(), self.title_id)
+    }
+
+    pub(crate) fn purchase_policy(&self) -> PurchasePolicy {
+        self.purchase.unwrap_or_default()
+    }
+
+    /// The settings of the job source, used for every option the job does not set.
+    pub(crate) fn settings(&self) -> SourceSettings {
+        get_settings().resolve(self.source)
+    }
+
+    pub(crate) fn image_quality(&self) -> DownloadImageQuality {
+        self.quality
+            .as_deref()
+            .and_then(|quality| DownloadImageQuality::from_str(quality, true).ok())
+            .unwrap_or_else(|| self.settings().image_quality())
+    }
+
+    pub(crate) fn image_format(&self) -> CLIDownloadFormat {
+        self.format
+            .as_deref()
+            .and_then(|format| CLIDownloadFormat::from_str(format, true).ok())
+            .unwrap_or_else(|| self.settings().image_format())
+    }
+
+    /// The output directory with the template replaced.
+    pub(crate) fn output_dir(&self, account_id: &str) -> PathBuf {
+        match &self.output {
+            Some(output) => PathBuf::from(
+                output
+                    .to_string_lossy()
+                    .replace("{source}", self.source.source_name())
+                    .replace("{account}", account_id),
+            ),
+            None => self.settings().output_dir(None),
+        }
+    }
+
+    /// Check the options that can be checked without the network.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.title_id.trim().is_empty() {
+            return Err("the title ID is empty".to_string());
+        }
+
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if start > end {
+                return Err(format!("start {} is greater than end {}", start, end));
+            }
+        }
+        let max_chapter = match self.source {
+            Implementations::Kmkc => i32::MAX as u64,
+            Implementations::Sjv => u32::MAX as u64,
+            _ => u64::MAX,
+        };
+        if self.start.max(self.end).is_some_and(|id| id > max_chapter) {
+            return Err("the chapter range is out of bounds".to_string());
+        }
+        if self.source == Implementations::Rbean && (self.start.is_some() || self.end.is_some()) {
+            return Err("RB does not support a chapter range".to_string());
+        }
+
+        if let Some(quality) = &self.quality {
+            if self.source != Implementations::Musq {
+                return Err("quality is only supported by MU".to_string());
+            }
+            DownloadImageQuality::from_str(quality, true)?;
+        }
+        if let Some(format) = &self.format {
+            if self.source != Implementations::Rbean {
+                return Err("format is only supported by RB".to_string());
+            }
+            CLIDownloadFormat::from_str(format, true)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_defaults(&mut self, defaults: &BatchDefaults) {
+        if self.output.is_none() {
+            self.output = defaults.output.clone();
+        }
+        if self.purchase.is_none() {
+            self.purchase = defaults.purchase;
+        }
+        if self.trim.is_none() {
+            self.trim = defaults.trim;
+        }
+        if self.parallel.is_none() {
+            self.parallel = defaults.parallel;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ManifestFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ManifestFormat {
+    fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "toml" => Ok(ManifestFormat::Toml),
+            "json" => Ok(ManifestFormat::Json),
+            "yaml" | "yml" => Ok(ManifestFormat::Yaml),
+            _ => anyhow::bail!("unknown manifest format, use a .toml, .json or .yaml file"),
+        }
+    }
+}
+
+/// A list of download jobs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct BatchManifest {
+    #[serde(default)]
+    pub(crate) defaults: BatchDefaults,
+    pub(crate) jobs: Vec<BatchJob>,
+}
+
+impl BatchManifest {
+    fn parse(content: &str, format: ManifestFormat) -> anyhow::Result<Self> {
+        let mut manifest: BatchManifest = match format {
+            ManifestFormat::Toml => toml::from_str(content)?,
+            ManifestFormat::Json => serde_json::from_str(content)?,
+            ManifestFormat::Yaml => serde_yaml::from_str(content)?,
+        };
+
+        let defaults = manifest.defaults.clone();
+        for job in manifest.jobs.iter_mut() {
+            job.apply_defaults(&defaults);
+        }
+
+        Ok(manifest)
+    }
+
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let format = ManifestFormat::from_path(path)?;
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content, format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let content = r#"
+            [defaults]
+            output = "downloads/{source}"
+            purchase = "free"
+
+            [[jobs]]
+            source = "km"
+            titleId = 12345
+            start = 10
+            end = 20
+
+            [[jobs]]
+            source = "mu"
+            titleId = "678"
+            account = "abc"
+            quality = "normal"
+            purchase = "never"
+            output = "mu/{account}"
+        "#;
+
+        let manifest = BatchManifest::parse(content, ManifestFormat::Toml).unwrap();
+        assert_eq!(manifest.jobs.len(), 2);
+        assert_eq!(manifest.jobs[0].title_id, "12345");
+        assert_eq!(manifest.jobs[0].purchase_policy(), PurchasePolicy::Free);
+        assert_eq!(
+            manifest.jobs[0].output_dir("x"),
+            PathBuf::from("downloads/KM")
+        );
+        assert_eq!(manifest.jobs[1].purchase_policy(), PurchasePolicy::Never);
+        assert_eq!(manifest.jobs[1].output_dir("abc"), PathBuf::from("mu/abc"));
+        assert!(manifest.jobs.iter().all(|job| job.validate().is_ok()));
+
+        let yaml = "jobs:\n  - source: rb\n    titleId: some-uuid\n    start: 1\n";
+        let manifest = BatchManifest::parse(yaml, ManifestFormat::Yaml).unwrap();
+        assert!(manifest.jobs[0].validate().is_err());
+
+        let json = r#"{"jobs": [{"source": "sj", "titleId": "slug", "tilte": 1}]}"#;
+        assert!(BatchManifest::parse(json, ManifestFormat::Json).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+
+use clap::Subcommand;
+
+pub(crate) mod manifest;
+pub(crate) mod runner;
+
+#[derive(Subcommand)]
+pub(crate) enum BatchCommands {
+    /// Run every job of a manifest file (TOML, JSON or YAML)
+    Run {
+        /// The manifest file, the format is guessed from the extension
+        manifest: PathBuf,
+    },
+    /// Validate a manifest file and resolve its accounts without downloading
+    Check {
+        /// The manifest file, the format is guessed from the extension
+        manifest: PathBuf,
+    },
+}
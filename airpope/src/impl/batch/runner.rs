@@ -0,0 +1,327 @@
+use std::path::Path;
+
+use color_print::cformat;
+
+use crate::{
+    cli::ExitCode,
+    config::ConfigImpl,
+    r#impl::{
+        amap::download::{amap_download, AMDownloadCliConfig},
+        config::proxy::ProxyRouter,
+        client::{
+            make_amap_client, make_kmkc_client, make_musq_client, make_rbean_client,
+            make_sjv_client, resolve_single_account,
+        },
+        kmkc::download::{kmkc_download, KMDownloadCliConfig},
+        musq::download::{musq_download, MUDownloadCliConfig},
+        parser::NumberOrString,
+        rbean::download::{rbean_download, RBDownloadConfigCli},
+        sjv::download::{sjv_download, SJDownloadCliConfig},
+        queue::QueueTracker,
+        watch::watchlist::PurchasePolicy,
+    },
+};
+
+use super::manifest::{BatchJob, BatchManifest};
+
+fn invalid_title_id(job: &BatchJob, console: &crate::term::Terminal) -> ExitCode {
+    console.error(&format!(
+        "Invalid title ID {} for {}",
+        job.title_id,
+        job.source.source_name()
+    ));
+    1
+}
+
+/// Process a single job through the autodownload path of its source.
+pub(crate) async fn run_batch_job(
+    job: &BatchJob,
+    queue: QueueTracker,
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    let config = match resolve_single_account(job.account.as_deref(), job.source, console) {
+        Ok(config) => config,
+        Err(err) => {
+            console.error(&err.to_string());
+            return 1;
+        }
+    };
+
+    let proxy = proxies.for_account(job.source, config.get_id()).await;
+    let proxy_lease = proxies.lease(job.source, config.get_id());
+    let output_dir = job.output_dir(config.get_id());
+    let auto_purchase = job.purchase_policy() != PurchasePolicy::Never;
+    let free_only = job.purchase_policy() == PurchasePolicy::Free;
+    let settings = job.settings();
+    let parallel = job.parallel.or(settings.parallel).unwrap_or(false);
+    let trim = job.trim.or(settings.trim);
+
+    match config {
+        ConfigImpl::Kmkc(config) => {
+            let title_id = match job.title_id.parse::<i32>() {
+                Ok(title_id) => title_id,
+                Err(_) => return invalid_title_id(job, console),
+            };
+            let client = make_kmkc_client(&config.clone().into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let dl_config = KMDownloadCliConfig {
+                no_input: true,
+                auto_purchase,
+                no_point: free_only,
+                parallel,
+                start_from: job.start.and_then(|id| i32::try_from(id).ok()),
+                end_at: job.end.and_then(|id| i32::try_from(id).ok()),
+                trim,
+                queue,
+                proxy: proxy_lease,
+                ..Default::default()
+            };
+
+            kmkc_download(title_id, dl_config, output_dir, &client, &config, console).await
+        }
+        ConfigImpl::Musq(config) => {
+            let title_id = match job.title_id.parse::<u64>() {
+                Ok(title_id) => title_id,
+                Err(_) => return invalid_title_id(job, console),
+            };
+            let client = make_musq_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let dl_config = MUDownloadCliConfig {
+                no_input: true,
+                auto_purchase,
+                no_paid_point: free_only,
+                quality: job.image_quality(),
+                start_from: job.start,
+                end_at: job.end,
+                trim,
+                queue,
+                proxy: proxy_lease,
+                ..Default::default()
+            };
+
+            musq_download(title_id, dl_config, output_dir, &client, &config, console).await
+        }
+        ConfigImpl::Amap(config) => {
+            let title_id = match job.title_id.parse::<u64>() {
+                Ok(title_id) => title_id,
+                Err(_) => return invalid_title_id(job, console),
+            };
+            let client = make_amap_client(&config.clone().into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let dl_config = AMDownloadCliConfig {
+                no_input: true,
+                auto_purchase,
+                no_purchased: free_only,
+                start_from: job.start,
+                end_at: job.end,
+                trim,
+                queue,
+                proxy: proxy_lease,
+                ..Default::default()
+            };
+
+            amap_download(title_id, dl_config, output_dir, &client, &config, console).await
+        }
+        ConfigImpl::Sjv(config) => {
+            let title_or_slug = match job.title_id.parse::<usize>() {
+                Ok(title_id) => NumberOrString::Number(title_id),
+                Err(_) => NumberOrString::Str(job.title_id.clone()),
+            };
+            let client = make_sjv_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let dl_config = SJDownloadCliConfig {
+                no_input: true,
+                parallel,
+                start_from: job.start.and_then(|id| u32::try_from(id).ok()),
+                end_at: job.end.and_then(|id| u32::try_from(id).ok()),
+                trim,
+                queue,
+                proxy: proxy_lease,
+                ..Default::default()
+            };
+
+            sjv_download(
+                title_or_slug,
+                dl_config,
+                output_dir,
+                &client,
+                &config,
+                console,
+            )
+            .await
+        }
+        ConfigImpl::Rbean(config) => {
+            let client = make_rbean_client(&config);
+            let mut client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let dl_config = RBDownloadConfigCli {
+                no_input: true,
+                format: job.image_format(),
+                parallel,
+                trim,
+                queue,
+                proxy: proxy_lease,
+                ..Default::default()
+            };
+
+            rbean_download(
+                &job.title_id,
+                dl_config,
+                output_dir,
+                &mut client,
+                &config,
+                console,
+            )
+            .await
+        }
+    }
+}
+
+/// Validate every job of the manifest, reporting all the problems at once.
+fn check_manifest(manifest_path: &Path, console: &crate::term::Terminal) -> Option<BatchManifest> {
+    let manifest = match BatchManifest::load(manifest_path) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            console.error(&format!(
+                "Failed to read manifest {}: {}",
+                manifest_path.display(),
+                err
+            ));
+            return None;
+        }
+    };
+
+    if manifest.jobs.is_empty() {
+        console.warn("The manifest has no jobs!");
+        return None;
+    }
+
+    let mut valid = true;
+    for (i, job) in manifest.jobs.iter().enumerate() {
+        let result = job.validate().and_then(|_| {
+            resolve_single_account(job.account.as_deref(), job.source, console)
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        });
+
+        if let Err(err) = result {
+            console.error(&cformat!(
+                "Job #{} (<m,s>{}</>): {}",
+                i + 1,
+                job.label(),
+                err
+            ));
+            valid = false;
+        }
+    }
+
+    if valid {
+        Some(manifest)
+    } else {
+        None
+    }
+}
+
+struct BatchResult {
+    label: String,
+    account: String,
+    exit_code: ExitCode,
+    elapsed: std::time::Duration,
+}
+
+fn print_summary(results: &[BatchResult]) {
+    const LABEL_WIDTH: usize = 32;
+
+    println!();
+    println!(
+        "{:>3}  {:<LABEL_WIDTH$} {:<24} {:<8} {:>10}",
+        "#", "Job", "Account", "Result", "Time"
+    );
+    for (i, result) in results.iter().enumerate() {
+        println!(
+            "{:>3}  {:<LABEL_WIDTH$} {:<24} {:<8} {:>9}s",
+            i + 1,
+            result.label,
+            result.account,
+            if result.exit_code == 0 {
+                "ok"
+            } else {
+                "failed"
+            },
+            result.elapsed.as_secs()
+        );
+    }
+}
+
+pub(crate) fn batch_check(manifest_path: &Path, console: &crate::term::Terminal) -> ExitCode {
+    match check_manifest(manifest_path, console) {
+        Some(manifest) => {
+            console.info(&cformat!(
+                "Manifest is valid, <m,s>{}</> jobs ready to run",
+                manifest.jobs.len()
+            ));
+            0
+        }
+        None => 1,
+    }
+}
+
+pub(crate) async fn batch_run(
+    manifest_path: &Path,
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    let manifest = match check_manifest(manifest_path, console) {
+        Some(manifest) => manifest,
+        None => return 1,
+    };
+
+    let total = manifest.jobs.len();
+    let mut results = vec![];
+    for (i, job) in manifest.jobs.iter().enumerate() {
+        console.info(&cformat!(
+            "[{}/{}] Processing <m,s>{}</>...",
+            i + 1,
+            total,
+            job.label()
+        ));
+
+        let started = std::time::Instant::now();
+        let exit_code = run_batch_job(job, QueueTracker::default(), proxies, console).await;
+        results.push(BatchResult {
+            label: job.label(),
+            account: job.account.clone().unwrap_or_else(|| "-".to_string()),
+            exit_code,
+            elapsed: started.elapsed(),
+        });
+    }
+
+    print_summary(&results);
+
+    let failed = results.iter().filter(|r| r.exit_code != 0).count();
+    if failed > 0 {
+        console.warn(&format!(
+            "{} of {} jobs did not finish successfully",
+            failed, total
+        ));
+        return 1;
+    }
+
+    console.info(&format!("Finished {} jobs", total));
+    0
+}
@@ -0,0 +1,305 @@
+use std::path::{Path, PathBuf};
+
+use color_print::cformat;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::ExitCode,
+    config::get_user_path,
+    r#impl::{
+        accounts::aliases::{get_aliases, AccountAliases},
+        Implementations,
+    },
+};
+
+use super::guard::Spending;
+
+/// The budgets file name inside the config directory.
+pub(crate) const BUDGETS_FILE: &str = "budgets.json";
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// A spending limit for a source, an account or a title.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Budget {
+    pub(crate) source: Implementations,
+    /// Only apply to this account, `None` means every account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) account_id: Option<String>,
+    /// Only apply to this title, `None` means every title.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) title_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) daily: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) monthly: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_per_chapter: Option<u64>,
+    /// Only allow free or event currency to be used.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub(crate) free_only: bool,
+}
+
+impl Budget {
+    pub(crate) fn matches(
+        &self,
+        source: Implementations,
+        account_id: &str,
+        title_id: &str,
+    ) -> bool {
+        self.source == source
+            && self.account_id.as_ref().is_none_or(|id| id == account_id)
+            && self.title_id.as_ref().is_none_or(|id| id == title_id)
+    }
+
+    fn is_same_scope(&self, other: &Budget) -> bool {
+        self.source == other.source
+            && self.account_id == other.account_id
+            && self.title_id == other.title_id
+    }
+
+    /// A short description of what the budget applies to.
+    pub(crate) fn scope(&self) -> String {
+        let mut scope = self.source.source_name().to_string();
+        if let Some(account_id) = &self.account_id {
+            scope.push_str(&format!(", account {}", account_id));
+        }
+        if let Some(title_id) = &self.title_id {
+            scope.push_str(&format!(", title {}", title_id));
+        }
+        scope
+    }
+
+    /// A short description of the limits of the budget.
+    pub(crate) fn limits(&self) -> String {
+        let mut limits = vec![];
+        if let Some(daily) = self.daily {
+            limits.push(format!("{} per day", daily));
+        }
+        if let Some(monthly) = self.monthly {
+            limits.push(format!("{} per month", monthly));
+        }
+        if let Some(max_per_chapter) = self.max_per_chapter {
+            limits.push(format!("{} per chapter", max_per_chapter));
+        }
+        if self.free_only {
+            limits.push("free currency only".to_string());
+        }
+
+        if limits.is_empty() {
+            "no limit".to_string()
+        } else {
+            limits.join(", ")
+        }
+    }
+}
+
+/// The configured budgets, stored as `budgets.json` in the config directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Budgets {
+    pub(crate) budgets: Vec<Budget>,
+}
+
+impl Budgets {
+    pub(crate) fn default_path() -> PathBuf {
+        get_user_path().join(BUDGETS_FILE)
+    }
+
+    /// Load the budgets, a missing file means no budgets.
+    ///
+    /// A budget saved with an account alias is matched by the account ID of the alias.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let mut budgets: Budgets = serde_json::from_str(&content)?;
+        if let Ok(aliases) = AccountAliases::load(&AccountAliases::default_path()) {
+            budgets.resolve_aliases(&aliases);
+        }
+        Ok(budgets)
+    }
+
+    /// Replace the account aliases of the budgets with their account ID.
+    pub(crate) fn resolve_aliases(&mut self, aliases: &AccountAliases) {
+        for budget in self.budgets.iter_mut() {
+            let resolved = budget
+                .account_id
+                .as_deref()
+                .and_then(|account| aliases.resolve(budget.source, account));
+            if let Some(id) = resolved {
+                budget.account_id = Some(id.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Add the budget, replacing the one with the same source, account and title.
+    pub(crate) fn set(&mut self, budget: Budget) {
+        match self.budgets.iter_mut().find(|b| b.is_same_scope(&budget)) {
+            Some(existing) => *existing = budget,
+            None => self.budgets.push(budget),
+        }
+    }
+}
+
+fn load_budgets(console: &crate::term::Terminal) -> Option<Budgets> {
+    match Budgets::load(&Budgets::default_path()) {
+        Ok(budgets) => Some(budgets),
+        Err(err) => {
+            console.error(&format!("Failed to read the budgets: {}", err));
+            None
+        }
+    }
+}
+
+fn save_budgets(budgets: &Budgets, console: &crate::term::Terminal) -> ExitCode {
+    match budgets.save(&Budgets::default_path()) {
+        Ok(_) => 0,
+        Err(err) => {
+            console.error(&format!("Failed to save the budgets: {}", err));
+            1
+        }
+    }
+}
+
+/// Save the budget, its account can be given as an alias.
+pub(crate) fn budget_set(mut budget: Budget, console: &crate::term::Terminal) -> ExitCode {
+    if budget.daily.is_none()
+        && budget.monthly.is_none()
+        && budget.max_per_chapter.is_none()
+        && !budget.free_only
+    {
+        console.error("A budget needs at least one limit");
+        return 1;
+    }
+
+    let mut budgets = match load_budgets(console) {
+        Some(budgets) => budgets,
+        None => return 1,
+    };
+
+    if let Some(account) = budget.account_id.take() {
+        budget.account_id = Some(get_aliases(console).resolve_account_id(budget.source, &account));
+    }
+
+    console.info(&cformat!(
+        "Set budget for <m,s>{}</>: {}",
+        budget.scope(),
+        budget.limits()
+    ));
+    budgets.set(budget);
+
+    save_budgets(&budgets, console)
+}
+
+pub(crate) fn budget_remove(index: usize, console: &crate::term::Terminal) -> ExitCode {
+    let mut budgets = match load_budgets(console) {
+        Some(budgets) => budgets,
+        None => return 1,
+    };
+
+    if index == 0 || index > budgets.budgets.len() {
+        console.error(&format!("Budget #{} does not exist", index));
+        return 1;
+    }
+
+    let removed = budgets.budgets.remove(index - 1);
+    let exit_code = save_budgets(&budgets, console);
+    if exit_code == 0 {
+        console.info(&cformat!("Removed budget for <m,s>{}</>", removed.scope()));
+    }
+
+    exit_code
+}
+
+pub(crate) fn budget_list(console: &crate::term::Terminal) -> ExitCode {
+    let budgets = match load_budgets(console) {
+        Some(budgets) => budgets,
+        None => return 1,
+    };
+
+    if budgets.budgets.is_empty() {
+        console.warn("No budgets configured!");
+        return 0;
+    }
+
+    let spending = match Spending::load() {
+        Ok(spending) => spending,
+        Err(err) => {
+            console.error(&format!("Failed to read the purchase ledger: {}", err));
+            return 1;
+        }
+    };
+
+    let now = chrono::Local::now();
+    console.info(&format!("Found {} budgets:", budgets.budgets.len()));
+    for (i, budget) in budgets.budgets.iter().enumerate() {
+        let (today, month) = spending.spent_by(budget, now);
+        console.info(&cformat!(
+            "{:02}. <m,s>{}</>: {}",
+            i + 1,
+            budget.scope(),
+            budget.limits()
+        ));
+        console.info(&format!(
+            "    Spent {} today and {} this month",
+            today, month
+        ));
+    }
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#impl::accounts::aliases::AccountAlias;
+
+    #[test]
+    fn test_budget_resolve_aliases() {
+        let budget = |source, account_id: &str| Budget {
+            source,
+            account_id: Some(account_id.to_string()),
+            title_id: None,
+            daily: Some(100),
+            monthly: None,
+            max_per_chapter: None,
+            free_only: false,
+        };
+        let aliases = AccountAliases {
+            aliases: vec![AccountAlias {
+                source: Implementations::Musq,
+                id: "abc123".to_string(),
+                alias: "main".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let mut budgets = Budgets {
+            budgets: vec![
+                budget(Implementations::Musq, "main"),
+                budget(Implementations::Musq, "other"),
+                budget(Implementations::Amap, "main"),
+            ],
+        };
+        budgets.resolve_aliases(&aliases);
+
+        assert!(budgets.budgets[0].matches(Implementations::Musq, "abc123", "1"));
+        assert_eq!(budgets.budgets[1].account_id.as_deref(), Some("other"));
+        assert_eq!(budgets.budgets[2].account_id.as_deref(), Some("main"));
+    }
+}
@@ -0,0 +1,330 @@
+use chrono::Datelike;
+use color_print::cformat;
+
+use crate::r#impl::{
+    ledger::{Ledger, LedgerEntry},
+    Implementations,
+};
+
+use super::config::{Budget, Budgets};
+
+/// The price of a single chapter purchase.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PurchaseCost {
+    /// The total amount, in the source currency.
+    pub(crate) amount: u64,
+    /// The part of the amount paid with paid currency.
+    pub(crate) paid: u64,
+}
+
+impl PurchaseCost {
+    pub(crate) fn new(amount: u64, paid: u64) -> Self {
+        Self { amount, paid }
+    }
+}
+
+/// The past purchases counted against the budgets, read from the purchase ledger.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Spending {
+    pub(crate) entries: Vec<LedgerEntry>,
+}
+
+impl Spending {
+    /// Load the spending from the ledger.
+    pub(crate) fn load() -> anyhow::Result<Self> {
+        let (entries, _) = Ledger::new(&Ledger::default_path()).read()?;
+        Ok(Self { entries })
+    }
+
+    /// The amount spent under the budget on the day and the month of `now`.
+    pub(crate) fn spent_by(
+        &self,
+        budget: &Budget,
+        now: chrono::DateTime<chrono::Local>,
+    ) -> (u64, u64) {
+        let mut today = 0;
+        let mut month = 0;
+        for entry in self.entries.iter() {
+            if !is_budgeted(entry)
+                || !budget.matches(entry.source, &entry.account_id, &entry.title_id)
+            {
+                continue;
+            }
+
+            if let Some(dt) = entry.local_time() {
+                if dt.year() == now.year() && dt.month() == now.month() {
+                    month += entry.amount;
+                    if dt.day() == now.day() {
+                        today += entry.amount;
+                    }
+                }
+            }
+        }
+
+        (today, month)
+    }
+}
+
+/// Whether the purchase counts against the budgets, the KM tickets are not limited.
+fn is_budgeted(entry: &LedgerEntry) -> bool {
+    !(entry.source == Implementations::Kmkc && entry.currency == "ticket")
+}
+
+/// Why a purchase got refused by a budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum BudgetStop {
+    FreeOnly {
+        scope: String,
+    },
+    MaxPerChapter {
+        scope: String,
+        limit: u64,
+    },
+    Daily {
+        scope: String,
+        limit: u64,
+        spent: u64,
+    },
+    Monthly {
+        scope: String,
+        limit: u64,
+        spent: u64,
+    },
+}
+
+impl BudgetStop {
+    /// A reached cap stops every later purchase, the other limits only skip a chapter.
+    pub(crate) fn is_cap(&self) -> bool {
+        matches!(self, BudgetStop::Daily { .. } | BudgetStop::Monthly { .. })
+    }
+}
+
+impl std::fmt::Display for BudgetStop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetStop::FreeOnly { scope } => {
+                write!(f, "{} only allows free currency", scope)
+            }
+            BudgetStop::MaxPerChapter { scope, limit } => {
+                write!(f, "{} allows at most {} per chapter", scope, limit)
+            }
+            BudgetStop::Daily {
+                scope,
+                limit,
+                spent,
+            } => write!(
+                f,
+                "{} daily cap of {} reached ({} spent)",
+                scope, limit, spent
+            ),
+            BudgetStop::Monthly {
+                scope,
+                limit,
+                spent,
+            } => write!(
+                f,
+                "{} monthly cap of {} reached ({} spent)",
+                scope, limit, spent
+            ),
+        }
+    }
+}
+
+/// Enforce the budgets matching a single title download.
+pub(crate) struct BudgetGuard {
+    budgets: Vec<Budget>,
+    spending: Spending,
+    spent: u64,
+    purchases: usize,
+    stopped: Option<BudgetStop>,
+}
+
+impl BudgetGuard {
+    pub(crate) fn load(
+        source: Implementations,
+        account_id: &str,
+        title_id: impl ToString,
+    ) -> anyhow::Result<Self> {
+        let title_id = title_id.to_string();
+        let budgets = Budgets::load(&Budgets::default_path())?
+            .budgets
+            .into_iter()
+            .filter(|budget| budget.matches(source, account_id, &title_id))
+            .collect();
+
+        Ok(Self {
+            budgets,
+            spending: Spending::load()?,
+            spent: 0,
+            purchases: 0,
+            stopped: None,
+        })
+    }
+
+    fn check(
+        &self,
+        cost: &PurchaseCost,
+        now: chrono::DateTime<chrono::Local>,
+    ) -> Result<(), BudgetStop> {
+        for budget in self.budgets.iter() {
+            let scope = budget.scope();
+            if budget.free_only && cost.paid > 0 {
+                return Err(BudgetStop::FreeOnly { scope });
+            }
+            if let Some(limit) = budget.max_per_chapter {
+                if cost.amount > limit {
+                    return Err(BudgetStop::MaxPerChapter { scope, limit });
+                }
+            }
+
+            let (today, month) = self.spending.spent_by(budget, now);
+            if let Some(limit) = budget.daily {
+                if today + cost.amount > limit {
+                    return Err(BudgetStop::Daily {
+                        scope,
+                        limit,
+                        spent: today,
+                    });
+                }
+            }
+            if let Some(limit) = budget.monthly {
+                if month + cost.amount > limit {
+                    return Err(BudgetStop::Monthly {
+                        scope,
+                        limit,
+                        spent: month,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if the purchase fits in the budgets.
+    ///
+    /// Once a daily or monthly cap is reached, every later purchase is refused.
+    pub(crate) fn allow(&mut self, cost: &PurchaseCost, console: &crate::term::Terminal) -> bool {
+        if self.stopped.is_some() {
+            return false;
+        }
+
+        if !self.budgets.is_empty() {
+            // catch up with the purchases made since, by this run or another one
+            match Spending::load() {
+                Ok(spending) => self.spending = spending,
+                Err(err) => {
+                    console.warn(&format!("   Failed to read the purchase ledger: {}", err));
+                    console.warn("   Skipping purchase, the budgets cannot be checked");
+                    return false;
+                }
+            }
+        }
+
+        match self.check(cost, chrono::Local::now()) {
+            Ok(_) => true,
+            Err(stop) if stop.is_cap() => {
+                console.warn(&format!("   Budget reached, {}", stop));
+                console.warn("   Stopping all purchases for this run");
+                self.stopped = Some(stop);
+                false
+            }
+            Err(stop) => {
+                console.warn(&format!("   Skipping purchase, {}", stop));
+                false
+            }
+        }
+    }
+
+    /// Count a successful purchase in this run's report.
+    ///
+    /// The purchase itself is counted against the budgets through the ledger.
+    pub(crate) fn record(&mut self, cost: &PurchaseCost) {
+        self.spent += cost.amount;
+        self.purchases += 1;
+    }
+
+    /// Print what has been spent, and why the purchases stopped if they did.
+    pub(crate) fn report(&self, console: &crate::term::Terminal) {
+        if self.budgets.is_empty() {
+            return;
+        }
+
+        console.info(&cformat!(
+            "Spent <m,s>{}</> on <s>{}</> purchases in this run",
+            self.spent,
+            self.purchases
+        ));
+        if let Some(stop) = &self.stopped {
+            console.warn(&format!("Purchases stopped, {}", stop));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_budget_check() {
+        let now = chrono::Local
+            .with_ymd_and_hms(2024, 3, 15, 12, 0, 0)
+            .unwrap();
+        let record =
+            |dt: chrono::DateTime<chrono::Local>, title_id: &str, amount: u64| LedgerEntry {
+                timestamp: dt.to_rfc3339(),
+                source: Implementations::Musq,
+                account_id: "acc".to_string(),
+                title_id: title_id.to_string(),
+                title: "Title".to_string(),
+                chapter_id: "10".to_string(),
+                chapter: "Chapter".to_string(),
+                currency: "free coin".to_string(),
+                amount,
+            };
+
+        let budget = Budget {
+            source: Implementations::Musq,
+            account_id: Some("acc".to_string()),
+            title_id: None,
+            daily: Some(100),
+            monthly: Some(200),
+            max_per_chapter: Some(60),
+            free_only: false,
+        };
+        let spending = Spending {
+            entries: vec![
+                record(now, "1", 50),
+                record(now - chrono::Duration::days(3), "2", 80),
+                record(now - chrono::Duration::days(40), "1", 500),
+            ],
+        };
+        assert_eq!(spending.spent_by(&budget, now), (50, 130));
+
+        let mut guard = BudgetGuard {
+            budgets: vec![budget.clone()],
+            spending,
+            spent: 0,
+            purchases: 0,
+            stopped: None,
+        };
+        assert!(guard.check(&PurchaseCost::new(40, 40), now).is_ok());
+        assert!(matches!(
+            guard.check(&PurchaseCost::new(70, 0), now),
+            Err(BudgetStop::MaxPerChapter { limit: 60, .. })
+        ));
+        assert!(matches!(
+            guard.check(&PurchaseCost::new(55, 0), now),
+            Err(BudgetStop::Daily { spent: 50, .. })
+        ));
+
+        guard.budgets[0].free_only = true;
+        assert!(matches!(
+            guard.check(&PurchaseCost::new(10, 5), now),
+            Err(BudgetStop::FreeOnly { .. })
+        ));
+        assert!(guard.check(&PurchaseCost::new(10, 0), now).is_ok());
+    }
+}
@@ -0,0 +1,45 @@
+use clap::Subcommand;
+
+use super::Implementations;
+
+pub(crate) mod config;
+pub(crate) mod guard;
+
+pub(crate) use guard::{BudgetGuard, PurchaseCost};
+
+#[derive(Subcommand)]
+pub(crate) enum BudgetCommands {
+    /// Add or replace a budget
+    ///
+    /// A budget without an account or a title applies to the whole source.
+    Set {
+        /// The source the budget applies to
+        #[arg(value_enum)]
+        source: Implementations,
+        /// Only apply to this account ID
+        #[arg(short = 'a', long = "account", default_value = None)]
+        account_id: Option<String>,
+        /// Only apply to this title ID
+        #[arg(short = 't', long = "title", default_value = None)]
+        title_id: Option<String>,
+        /// Maximum amount to spend per day
+        #[arg(short = 'd', long = "daily", default_value = None)]
+        daily: Option<u64>,
+        /// Maximum amount to spend per month
+        #[arg(short = 'm', long = "monthly", default_value = None)]
+        monthly: Option<u64>,
+        /// Maximum price of a single chapter
+        #[arg(short = 'c', long = "max-per-chapter", default_value = None)]
+        max_per_chapter: Option<u64>,
+        /// Only purchase with free or event currency
+        #[arg(short = 'f', long = "free-only")]
+        free_only: bool,
+    },
+    /// Remove a budget by its number in the list
+    Remove {
+        /// The budget number, see `budget list`
+        index: usize,
+    },
+    /// List all the budgets with the current spending
+    List,
+}
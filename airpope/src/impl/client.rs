@@ -1,5 +1,6 @@
 use crate::{
     config::{get_all_config, get_config},
+    r#impl::accounts::aliases::{get_aliases, record_account_usage, AccountAliases},
     term::ConsoleChoice,
 };
 
@@ -8,14 +9,103 @@ pub(crate) fn select_single_account(
     implementation: super::Implementations,
     term: &crate::term::Terminal,
 ) -> Option<crate::config::ConfigImpl> {
+    let aliases = get_aliases(term);
+    let config = select_account_config(account_id, implementation, &aliases, term)?;
+
+    if let Err(err) = record_account_usage(implementation, config.get_id()) {
+        term.warn(&format!("Failed to save the account usage: {}", err));
+    }
+
+    Some(config)
+}
+
+/// Why an account could not be selected without prompting.
+#[derive(Debug)]
+pub(crate) enum AccountLookupError {
+    /// The account, or any account of the source, does not exist.
+    NotFound(String),
+    /// The source has multiple accounts and none of them got selected.
+    Ambiguous(String),
+}
+
+impl std::fmt::Display for AccountLookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountLookupError::NotFound(message) => write!(f, "{}", message),
+            AccountLookupError::Ambiguous(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AccountLookupError {}
+
+/// Select an account without prompting, for the batch jobs and the API server.
+///
+/// Like [`select_single_account`], the account ID can be an alias and the default account
+/// is used when none is given, then the only account of the source.
+pub(crate) fn resolve_single_account(
+    account_id: Option<&str>,
+    implementation: super::Implementations,
+    term: &crate::term::Terminal,
+) -> Result<crate::config::ConfigImpl, AccountLookupError> {
+    let aliases = get_aliases(term);
+    let source_name = implementation.source_name();
     if let Some(account_id) = account_id {
-        let config = get_config(account_id, &implementation, None);
+        let resolved_id = aliases.resolve_account_id(implementation, account_id);
+        return get_config(&resolved_id, &implementation, None).ok_or_else(|| {
+            AccountLookupError::NotFound(format!(
+                "Account {} for {} not found!",
+                account_id, source_name
+            ))
+        });
+    }
 
-        if let Some(config) = config {
-            return Some(config.clone());
+    if let Some(default_id) = aliases.default_of(implementation) {
+        match get_config(default_id, &implementation, None) {
+            Some(config) => return Ok(config),
+            None => term.warn(&format!("Default account ID {} not found!", default_id)),
         }
+    }
+
+    let mut configs = get_all_config(&implementation, None);
+    match configs.len() {
+        0 => Err(AccountLookupError::NotFound(format!(
+            "No account found for {}!",
+            source_name
+        ))),
+        1 => Ok(configs.remove(0)),
+        _ => Err(AccountLookupError::Ambiguous(format!(
+            "There are multiple {} accounts, select one with `account`",
+            source_name
+        ))),
+    }
+}
+
+fn select_account_config(
+    account_id: Option<&str>,
+    implementation: super::Implementations,
+    aliases: &AccountAliases,
+    term: &crate::term::Terminal,
+) -> Option<crate::config::ConfigImpl> {
+    match account_id {
+        Some(account_id) => {
+            let resolved_id = aliases.resolve_account_id(implementation, account_id);
+            let config = get_config(&resolved_id, &implementation, None);
+
+            if let Some(config) = config {
+                return Some(config.clone());
+            }
 
-        term.warn(&format!("Account ID {} not found!", account_id));
+            term.warn(&format!("Account ID {} not found!", account_id));
+        }
+        None => {
+            if let Some(default_id) = aliases.default_of(implementation) {
+                match get_config(default_id, &implementation, None) {
+                    Some(config) => return Some(config),
+                    None => term.warn(&format!("Default account ID {} not found!", default_id)),
+                }
+            }
+        }
     }
 
     let all_configs = get_all_config(&implementation, None);
@@ -117,5 +207,10 @@ pub(crate) fn make_sjv_client(config: &super::sjv::config::Config) -> airpope_sj
 }
 
 pub(crate) fn make_rbean_client(config: &super::rbean::config::Config) -> airpope_rbean::RBClient {
-    airpope_rbean::RBClient::new(config.clone().into())
+    let mut client = airpope_rbean::RBClient::new(config.clone().into());
+    // skip the token refresh until the saved token expires
+    if config.expiry > 0 {
+        client.set_expiry_at(Some(config.expiry));
+    }
+    client
 }
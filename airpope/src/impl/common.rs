@@ -13,3 +13,31 @@ pub(super) fn unix_timestamp_to_string(timestamp: i64) -> Option<String> {
         None => None,
     }
 }
+
+static SHUTDOWN_REQUESTED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Ask the running downloads to stop once the in-flight pages are finished.
+pub(crate) fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+/// Check if a graceful shutdown has been requested.
+pub(crate) fn is_shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+/// Build a command that runs `command` through the platform shell.
+pub(crate) fn shell_command(command: &str) -> tokio::process::Command {
+    #[cfg(windows)]
+    let mut cmd = tokio::process::Command::new("cmd");
+    #[cfg(windows)]
+    cmd.arg("/C");
+    #[cfg(not(windows))]
+    let mut cmd = tokio::process::Command::new("sh");
+    #[cfg(not(windows))]
+    cmd.arg("-c");
+
+    cmd.arg(command);
+    cmd
+}
@@ -0,0 +1,51 @@
+use clap::Subcommand;
+
+pub(crate) mod proxy;
+pub(crate) mod runner;
+pub(crate) mod settings;
+pub(crate) mod vault;
+
+#[derive(Subcommand)]
+pub(crate) enum ConfigCommands {
+    /// Encrypt every saved account with a passphrase
+    ///
+    /// The passphrase is asked once per run, or read from the `AIRPOPE_PASSPHRASE` environment variable.
+    Encrypt,
+    /// Remove the encryption and store every saved account as plain files again
+    Decrypt,
+    /// Change the passphrase of the encrypted accounts
+    ChangePassphrase,
+    /// Show a setting from `settings.toml`, or every setting
+    Get {
+        /// The setting key, ex: `mu.quality` or `default.output`
+        key: Option<String>,
+    },
+    /// Change a setting in `settings.toml`
+    ///
+    /// The `default` section applies to every source, the `km`, `mu`, `am`, `sj` and `rb`
+    /// sections override it. The CLI flags always override the settings.
+    ///
+    /// Available fields: output, proxy, proxyRotation, parallel, trim, trimSpread,
+    /// trimSpreadOffset, noPurchase, noPaid, noXp, noTicket, noPoint, noPremium, quality
+    /// and format.
+    ///
+    /// The proxy can be a list to rotate between, ex: `'["http://a:8080", "http://b:8080"]'`,
+    /// and each account can have its own proxy with `<section>.accounts.<account>.proxy`.
+    Set {
+        /// The setting key, ex: `mu.quality` or `default.output`
+        key: String,
+        /// The new value
+        value: String,
+    },
+    /// Remove a setting from `settings.toml`
+    Unset {
+        /// The setting key, ex: `mu.quality` or `default.output`
+        key: String,
+    },
+    /// Show the configured proxies and the health of each proxy
+    Proxies {
+        /// Forget the health of every proxy
+        #[arg(long)]
+        reset: bool,
+    },
+}
@@ -0,0 +1,588 @@
+use std::path::{Path, PathBuf};
+
+use color_print::cformat;
+
+use crate::{cli::ExitCode, config::get_user_path};
+
+use super::proxy::{redact_proxy_url, ProxyHealthStore, PROXY_HEALTH_FILE};
+use super::settings::{try_get_settings, Settings, SETTINGS_FILE};
+use super::vault::{
+    decrypt_config, is_encrypted, prompt_new_passphrase, set_unlocked, unlock, Vault, VaultKey,
+};
+
+/// Read every config file in memory, decrypted with the given key.
+fn read_all_configs(
+    user_path: &Path,
+    key: Option<&VaultKey>,
+) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut glob_path = user_path.to_path_buf();
+    glob_path.push("*.tmconf");
+
+    let mut configs = vec![];
+    for entry in glob::glob(&glob_path.to_string_lossy())?.flatten() {
+        let buffer = std::fs::read(&entry)?;
+        let buffer = match (is_encrypted(&buffer), key) {
+            (true, Some(key)) => decrypt_config(&entry, &buffer, key)?,
+            (true, None) => anyhow::bail!("{} is encrypted", entry.display()),
+            (false, _) => buffer,
+        };
+        configs.push((entry, buffer));
+    }
+
+    Ok(configs)
+}
+
+/// The file a config is written to before it replaces the config.
+fn staged_config_path(path: &Path) -> PathBuf {
+    path.with_extension("tmconf.new")
+}
+
+/// Write every config next to the old one, encrypted with the key if any.
+fn stage_configs(configs: &[(PathBuf, Vec<u8>)], key: Option<&VaultKey>) -> anyhow::Result<()> {
+    for (path, buffer) in configs.iter() {
+        let data = match key {
+            Some(key) => key.encrypt(buffer)?,
+            None => buffer.clone(),
+        };
+        std::fs::write(staged_config_path(path), data)?;
+    }
+
+    Ok(())
+}
+
+fn remove_staged_configs(configs: &[(PathBuf, Vec<u8>)]) {
+    for (path, _) in configs.iter() {
+        let _ = std::fs::remove_file(staged_config_path(path));
+    }
+}
+
+/// Keep the current vault as `vault.json.old` and replace it, `None` removes the vault.
+fn swap_vault(user_path: &Path, new_vault: Option<&Vault>) -> anyhow::Result<()> {
+    let vault_path = Vault::path(user_path);
+    if vault_path.exists() {
+        std::fs::copy(&vault_path, Vault::old_path(user_path))?;
+    }
+
+    match new_vault {
+        Some(vault) => vault.save(user_path),
+        None if vault_path.exists() => Ok(std::fs::remove_file(&vault_path)?),
+        None => Ok(()),
+    }
+}
+
+/// Write every config with the new key and replace the vault, `None` removes the encryption.
+///
+/// The configs are written next to the old ones before the vault is touched, and the old vault
+/// is kept as `vault.json.old` until every config is replaced, so a failure never leaves
+/// a config encrypted with a key that can't be derived anymore.
+fn replace_vault(
+    user_path: &Path,
+    configs: &[(PathBuf, Vec<u8>)],
+    new_vault: Option<(&Vault, &VaultKey)>,
+) -> anyhow::Result<()> {
+    let old_vault_path = Vault::old_path(user_path);
+    let staged = stage_configs(configs, new_vault.map(|(_, key)| key))
+        .and_then(|_| swap_vault(user_path, new_vault.map(|(vault, _)| vault)));
+    if let Err(err) = staged {
+        remove_staged_configs(configs);
+        let _ = std::fs::remove_file(&old_vault_path);
+        return Err(err);
+    }
+
+    for (path, _) in configs.iter() {
+        std::fs::rename(staged_config_path(path), path).map_err(|err| {
+            anyhow::anyhow!(
+                "{}: {}, the previous key is kept as {} for the accounts not replaced yet",
+                path.display(),
+                err,
+                old_vault_path.display()
+            )
+        })?;
+    }
+
+    if old_vault_path.exists() {
+        std::fs::remove_file(&old_vault_path)?;
+    }
+    Ok(())
+}
+
+/// Unlock the current vault, failing when the accounts are not encrypted.
+fn unlock_current(user_path: &Path, console: &crate::term::Terminal) -> Result<VaultKey, ExitCode> {
+    match unlock(user_path) {
+        Ok(Some(key)) => Ok(key),
+        Ok(None) => {
+            console.warn("The accounts are not encrypted");
+            Err(1)
+        }
+        Err(err) => {
+            console.error(&format!("Failed to unlock the accounts: {}", err));
+            Err(1)
+        }
+    }
+}
+
+/// Create a new vault and encrypt the configs with it.
+fn encrypt_with_new_vault(
+    user_path: &Path,
+    configs: &[(PathBuf, Vec<u8>)],
+    passphrase: &str,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    console.info("Deriving the encryption key...");
+    let (vault, key) = match Vault::create(passphrase) {
+        Ok(vault) => vault,
+        Err(err) => {
+            console.error(&format!("Failed to create the encryption key: {}", err));
+            return 1;
+        }
+    };
+
+    match replace_vault(user_path, configs, Some((&vault, &key))) {
+        Ok(_) => {
+            set_unlocked(user_path, Some(key));
+            console.info(&cformat!("Encrypted <m,s>{}</> accounts", configs.len()));
+            0
+        }
+        Err(err) => {
+            console.error(&format!("Failed to encrypt the accounts: {}", err));
+            1
+        }
+    }
+}
+
+pub(crate) fn config_encrypt(console: &crate::term::Terminal) -> ExitCode {
+    let user_path = get_user_path();
+    match Vault::load(&user_path) {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            console
+                .warn("The accounts are already encrypted, use `config change-passphrase` instead");
+            return 1;
+        }
+        Err(err) => {
+            console.error(&format!("Failed to read the encryption key: {}", err));
+            return 1;
+        }
+    }
+
+    let configs = match read_all_configs(&user_path, None) {
+        Ok(configs) => configs,
+        Err(err) => {
+            console.error(&format!("Failed to read the accounts: {}", err));
+            return 1;
+        }
+    };
+
+    let passphrase = match prompt_new_passphrase(true, console) {
+        Some(passphrase) => passphrase,
+        None => return 1,
+    };
+
+    encrypt_with_new_vault(&user_path, &configs, &passphrase, console)
+}
+
+pub(crate) fn config_decrypt(console: &crate::term::Terminal) -> ExitCode {
+    let user_path = get_user_path();
+    let key = match unlock_current(&user_path, console) {
+        Ok(key) => key,
+        Err(code) => return code,
+    };
+
+    let configs = match read_all_configs(&user_path, Some(&key)) {
+        Ok(configs) => configs,
+        Err(err) => {
+            console.error(&format!("Failed to read the accounts: {}", err));
+            return 1;
+        }
+    };
+
+    match replace_vault(&user_path, &configs, None) {
+        Ok(_) => {
+            set_unlocked(&user_path, None);
+            console.info(&cformat!("Decrypted <m,s>{}</> accounts", configs.len()));
+            0
+        }
+        Err(err) => {
+            console.error(&format!("Failed to decrypt the accounts: {}", err));
+            1
+        }
+    }
+}
+
+pub(crate) fn config_change_passphrase(console: &crate::term::Terminal) -> ExitCode {
+    let user_path = get_user_path();
+    let key = match unlock_current(&user_path, console) {
+        Ok(key) => key,
+        Err(code) => return code,
+    };
+
+    let configs = match read_all_configs(&user_path, Some(&key)) {
+        Ok(configs) => configs,
+        Err(err) => {
+            console.error(&format!("Failed to read the accounts: {}", err));
+            return 1;
+        }
+    };
+
+    let passphrase = match prompt_new_passphrase(false, console) {
+        Some(passphrase) => passphrase,
+        None => return 1,
+    };
+
+    encrypt_with_new_vault(&user_path, &configs, &passphrase, console)
+}
+
+/// The sections of the settings file.
+const SECTIONS: [&str; 6] = ["default", "km", "mu", "am", "sj", "rb"];
+
+/// Split a `section.field` key into its parts, ex: `mu.quality` or `mu.accounts.main.proxy`
+fn split_settings_key(key: &str) -> anyhow::Result<Vec<&str>> {
+    let parts: Vec<&str> = key.split('.').collect();
+    match parts.as_slice() {
+        [section, field @ ..]
+            if SECTIONS.contains(section)
+                && !field.is_empty()
+                && field.iter().all(|part| !part.is_empty()) =>
+        {
+            Ok(parts)
+        }
+        _ => anyhow::bail!(
+            "Invalid key `{}`, expected `<section>.<field>` with section one of: {}",
+            key,
+            SECTIONS.join(", ")
+        ),
+    }
+}
+
+fn get_settings_value<'a>(table: &'a toml::Table, path: &[&str]) -> Option<&'a toml::Value> {
+    let (first, rest) = path.split_first()?;
+    let mut value = table.get(*first)?;
+    for key in rest {
+        value = value.get(key)?;
+    }
+    Some(value)
+}
+
+/// Set the value at the path, creating the missing tables.
+fn set_settings_value(table: &mut toml::Table, path: &[&str], value: toml::Value) {
+    match path {
+        [] => {}
+        [key] => {
+            table.insert(key.to_string(), value);
+        }
+        [key, rest @ ..] => {
+            let values = table
+                .entry(key.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let toml::Value::Table(values) = values {
+                set_settings_value(values, rest, value);
+            }
+        }
+    }
+}
+
+/// Remove the value at the path and the tables left empty, returns `true` if removed.
+fn remove_settings_value(table: &mut toml::Table, path: &[&str]) -> bool {
+    match path {
+        [] => false,
+        [key] => table.remove(*key).is_some(),
+        [key, rest @ ..] => {
+            let (removed, is_empty) = match table.get_mut(*key) {
+                Some(toml::Value::Table(values)) => {
+                    let removed = remove_settings_value(values, rest);
+                    (removed, values.is_empty())
+                }
+                _ => (false, false),
+            };
+            if is_empty {
+                table.remove(*key);
+            }
+            removed
+        }
+    }
+}
+
+fn load_settings_table(path: &Path) -> anyhow::Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    Ok(content.parse::<toml::Table>()?)
+}
+
+/// Save the settings table if it's valid.
+fn save_settings_table(path: &Path, table: &toml::Table) -> anyhow::Result<()> {
+    let content = toml::to_string_pretty(table)?;
+    Settings::parse(&content)?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Parse the value as a TOML value, or a plain string.
+fn parse_settings_value(value: &str) -> Vec<toml::Value> {
+    let mut candidates = vec![];
+    if let Ok(table) = format!("value = {}", value).parse::<toml::Table>() {
+        if let Some(value) = table.get("value") {
+            candidates.push(value.clone());
+        }
+    }
+    candidates.push(toml::Value::String(value.to_string()));
+    candidates
+}
+
+pub(crate) fn config_get(key: Option<String>, console: &crate::term::Terminal) -> ExitCode {
+    let path = Settings::default_path();
+    let table = match load_settings_table(&path) {
+        Ok(table) => table,
+        Err(err) => {
+            console.error(&format!("Failed to read {}: {}", SETTINGS_FILE, err));
+            return 1;
+        }
+    };
+
+    let key = match key {
+        Some(key) => key,
+        None => {
+            if table.is_empty() {
+                console.info("No settings configured");
+            } else {
+                console.info(&cformat!("Settings in <s>{}</>:", path.display()));
+                println!("{}", toml::to_string_pretty(&table).unwrap_or_default());
+            }
+            return 0;
+        }
+    };
+
+    let path = match split_settings_key(&key) {
+        Ok(path) => path,
+        Err(err) => {
+            console.error(&err.to_string());
+            return 1;
+        }
+    };
+
+    let field = &path[1..];
+    let default_path: Vec<&str> = std::iter::once("default")
+        .chain(field.iter().copied())
+        .collect();
+    match (
+        get_settings_value(&table, &path),
+        get_settings_value(&table, &default_path),
+    ) {
+        (Some(value), _) => console.info(&cformat!("<m,s>{}</> = {}", key, value)),
+        (None, Some(value)) => console.info(&cformat!(
+            "<m,s>{}</> = {} (from <s>{}</>)",
+            key,
+            value,
+            default_path.join(".")
+        )),
+        (None, None) => console.info(&cformat!("<m,s>{}</> is not set", key)),
+    }
+
+    0
+}
+
+pub(crate) fn config_set(key: String, value: String, console: &crate::term::Terminal) -> ExitCode {
+    let key_path = match split_settings_key(&key) {
+        Ok(key_path) => key_path,
+        Err(err) => {
+            console.error(&err.to_string());
+            return 1;
+        }
+    };
+
+    let path = Settings::default_path();
+    let table = match load_settings_table(&path) {
+        Ok(table) => table,
+        Err(err) => {
+            console.error(&format!("Failed to read {}: {}", SETTINGS_FILE, err));
+            return 1;
+        }
+    };
+
+    // try the value as a boolean or number first, then as a string
+    let mut first_error = None;
+    for candidate in parse_settings_value(&value) {
+        let mut updated = table.clone();
+        set_settings_value(&mut updated, &key_path, candidate.clone());
+
+        match save_settings_table(&path, &updated) {
+            Ok(_) => {
+                console.info(&cformat!("Set <m,s>{}</> to {}", key, candidate));
+                return 0;
+            }
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+
+    if let Some(err) = first_error {
+        console.error(&format!("Invalid value for {}: {}", key, err));
+    }
+    1
+}
+
+pub(crate) fn config_unset(key: String, console: &crate::term::Terminal) -> ExitCode {
+    let key_path = match split_settings_key(&key) {
+        Ok(key_path) => key_path,
+        Err(err) => {
+            console.error(&err.to_string());
+            return 1;
+        }
+    };
+
+    let path = Settings::default_path();
+    let mut table = match load_settings_table(&path) {
+        Ok(table) => table,
+        Err(err) => {
+            console.error(&format!("Failed to read {}: {}", SETTINGS_FILE, err));
+            return 1;
+        }
+    };
+
+    if !remove_settings_value(&mut table, &key_path) {
+        console.warn(&cformat!("<m,s>{}</> is not set", key));
+        return 0;
+    }
+
+    match save_settings_table(&path, &table) {
+        Ok(_) => {
+            console.info(&cformat!("Removed <m,s>{}</>", key));
+            0
+        }
+        Err(err) => {
+            console.error(&format!("Failed to save {}: {}", SETTINGS_FILE, err));
+            1
+        }
+    }
+}
+
+pub(crate) fn config_proxies(reset: bool, console: &crate::term::Terminal) -> ExitCode {
+    let path = ProxyHealthStore::default_path();
+    if reset {
+        if path.exists() {
+            if let Err(err) = std::fs::remove_file(&path) {
+                console.error(&format!("Failed to remove {}: {}", PROXY_HEALTH_FILE, err));
+                return 1;
+            }
+        }
+        console.info("Forgot the health of every proxy");
+        return 0;
+    }
+
+    let health = match ProxyHealthStore::load(&path) {
+        Ok(health) => health,
+        Err(err) => {
+            console.error(&format!("Failed to read {}: {}", PROXY_HEALTH_FILE, err));
+            return 1;
+        }
+    };
+
+    let settings = match try_get_settings() {
+        Ok(settings) => settings,
+        Err(err) => {
+            console.error(&err.to_string());
+            return 1;
+        }
+    };
+    let mut proxy_lists = vec![];
+    for (section, source) in settings.sections() {
+        let rotation = source
+            .proxy_rotation
+            .or(settings.default.proxy_rotation)
+            .unwrap_or_default();
+        if let Some(proxy) = &source.proxy {
+            proxy_lists.push((section.to_string(), proxy.urls(), rotation));
+        }
+        for (account, account_settings) in source.accounts.iter() {
+            if let Some(proxy) = &account_settings.proxy {
+                proxy_lists.push((
+                    format!("{}.accounts.{}", section, account),
+                    proxy.urls(),
+                    account_settings.proxy_rotation.unwrap_or(rotation),
+                ));
+            }
+        }
+    }
+
+    if proxy_lists.is_empty() {
+        console.info("No proxies configured");
+        return 0;
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    for (key, urls, rotation) in proxy_lists {
+        console.info(&cformat!(
+            "<m,s>{}</> (rotate on <s>{}</>):",
+            key,
+            rotation.to_name()
+        ));
+        for url in urls {
+            let status = match health.health_of(&url) {
+                None => "unused".to_string(),
+                Some(proxy) => {
+                    let state = if proxy.is_cooling_down(now) {
+                        "failing"
+                    } else {
+                        "ok"
+                    };
+                    let mut status = format!(
+                        "{}, {} ok / {} failed",
+                        state, proxy.successes, proxy.failures
+                    );
+                    let last_error = proxy
+                        .last_error
+                        .as_ref()
+                        .filter(|_| proxy.consecutive_failures > 0);
+                    if let Some(error) = last_error {
+                        status.push_str(&format!(", last error: {}", error));
+                    }
+                    status
+                }
+            };
+            console.info(&format!("  {} - {}", redact_proxy_url(&url), status));
+        }
+    }
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_vault() {
+        let user_path = std::env::temp_dir().join(format!("airpope-vault-{}", std::process::id()));
+        std::fs::create_dir_all(&user_path).unwrap();
+        std::fs::write(user_path.join("musq.a.tmconf"), b"account a").unwrap();
+        std::fs::write(user_path.join("musq.b.tmconf"), b"account b").unwrap();
+
+        let configs = read_all_configs(&user_path, None).unwrap();
+        let (vault, key) = Vault::create("hunter2").unwrap();
+        replace_vault(&user_path, &configs, Some((&vault, &key))).unwrap();
+        assert!(Vault::path(&user_path).exists());
+        assert!(!Vault::old_path(&user_path).exists());
+        assert!(read_all_configs(&user_path, None).is_err());
+        assert_eq!(read_all_configs(&user_path, Some(&key)).unwrap(), configs);
+
+        // a failure before the swap leaves the vault and the configs alone
+        let unwritable = vec![(user_path.join("missing/musq.c.tmconf"), b"c".to_vec())];
+        assert!(replace_vault(&user_path, &unwritable, None).is_err());
+        assert!(Vault::path(&user_path).exists());
+        assert!(!Vault::old_path(&user_path).exists());
+        assert_eq!(read_all_configs(&user_path, Some(&key)).unwrap(), configs);
+
+        replace_vault(&user_path, &configs, None).unwrap();
+        assert!(!Vault::path(&user_path).exists());
+        assert_eq!(read_all_configs(&user_path, None).unwrap(), configs);
+
+        std::fs::remove_dir_all(&user_path).unwrap();
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::get_user_path,
+    r#impl::{
+        musq::download::DownloadImageQuality, rbean::download::CLIDownloadFormat, Implementations,
+    },
+};
+
+/// The settings file name inside the config directory.
+pub(crate) const SETTINGS_FILE: &str = "settings.toml";
+
+static SETTINGS: OnceLock<Result<Settings, String>> = OnceLock::new();
+static DEFAULT_SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// A single proxy, or a list of proxies to rotate between.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ProxyList {
+    Single(String),
+    Many(Vec<String>),
+}
+
+impl ProxyList {
+    pub(crate) fn urls(&self) -> Vec<String> {
+        match self {
+            ProxyList::Single(url) => vec![url.clone()],
+            ProxyList::Many(urls) => urls.clone(),
+        }
+    }
+
+    fn validate(&self, key: &str) -> anyhow::Result<()> {
+        let urls = self.urls();
+        if urls.is_empty() {
+            anyhow::bail!("{}: the proxy list is empty", key);
+        }
+        for url in urls {
+            reqwest::Proxy::all(&url).map_err(|err| anyhow::anyhow!("{}: {}", key, err))?;
+        }
+        Ok(())
+    }
+}
+
+/// When to switch to the next proxy of a proxy list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ProxyRotation {
+    /// Keep the same proxy until it fails.
+    #[default]
+    Failure,
+    /// Use the least recently used proxy for each batch of requests (a command, a batch job,
+    /// a watched title or an API request).
+    Batch,
+}
+
+impl ProxyRotation {
+    pub(crate) fn to_name(self) -> &'static str {
+        match self {
+            ProxyRotation::Failure => "failure",
+            ProxyRotation::Batch => "batch",
+        }
+    }
+}
+
+/// The settings of a single account, by account ID or alias.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct AccountSettings {
+    /// The proxy of the account, overrides the proxy of the source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) proxy: Option<ProxyList>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) proxy_rotation: Option<ProxyRotation>,
+}
+
+/// The defaults of a source, every value is overridden by the CLI flags.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct SourceSettings {
+    /// The download directory, default to the current directory DOWNLOADS folder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output: Option<PathBuf>,
+    /// The proxy used for every request, or a list of proxies to rotate between.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) proxy: Option<ProxyList>,
+    /// When to switch to the next proxy, default to `failure`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) proxy_rotation: Option<ProxyRotation>,
+    /// Download the pages in parallel, only for KM, SJ and RB.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) parallel: Option<bool>,
+    /// Trim uniform borders of the downloaded pages with the given tolerance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) trim: Option<u8>,
+    /// Keep the facing pages at the same height when trimming, like `tools trim --spread`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) trim_spread: Option<bool>,
+    /// The pages before the first facing pages, like `tools trim --spread-offset`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) trim_spread_offset: Option<u32>,
+    /// Never purchase in `autodownload`, only for KM, MU and AM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) no_purchase: Option<bool>,
+    /// Do not use the paid coins or tickets in `autodownload`, only for MU and AM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) no_paid: Option<bool>,
+    /// Do not use the XP coins in `autodownload`, only for MU.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) no_xp: Option<bool>,
+    /// Do not use the tickets in `autodownload`, only for KM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) no_ticket: Option<bool>,
+    /// Do not use the points in `autodownload`, only for KM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) no_point: Option<bool>,
+    /// Do not use the premium tickets in `autodownload`, only for AM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) no_premium: Option<bool>,
+    /// The image quality, only for MU.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) quality: Option<String>,
+    /// The image format, only for RB.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) format: Option<String>,
+    /// The per-account settings, by account ID or alias, only in the source sections.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) accounts: BTreeMap<String, AccountSettings>,
+}
+
+impl SourceSettings {
+    /// Fill the unset values with the values of `other`.
+    fn or(&self, other: &SourceSettings) -> SourceSettings {
+        SourceSettings {
+            output: self.output.clone().or(other.output.clone()),
+            proxy: self.proxy.clone().or(other.proxy.clone()),
+            proxy_rotation: self.proxy_rotation.or(other.proxy_rotation),
+            parallel: self.parallel.or(other.parallel),
+            trim: self.trim.or(other.trim),
+            trim_spread: self.trim_spread.or(other.trim_spread),
+            trim_spread_offset: self.trim_spread_offset.or(other.trim_spread_offset),
+            no_purchase: self.no_purchase.or(other.no_purchase),
+            no_paid: self.no_paid.or(other.no_paid),
+            no_xp: self.no_xp.or(other.no_xp),
+            no_ticket: self.no_ticket.or(other.no_ticket),
+            no_point: self.no_point.or(other.no_point),
+            no_premium: self.no_premium.or(other.no_premium),
+            quality: self.quality.clone().or(other.quality.clone()),
+            format: self.format.clone().or(other.format.clone()),
+            accounts: self.accounts.clone(),
+        }
+    }
+
+    /// The output directory of the CLI, or the configured one.
+    pub(crate) fn output_dir(&self, output: Option<PathBuf>) -> PathBuf {
+        output
+            .or(self.output.clone())
+            .unwrap_or_else(crate::get_default_download_dir)
+    }
+
+    pub(crate) fn image_quality(&self) -> DownloadImageQuality {
+        self.quality
+            .as_deref()
+            .and_then(|quality| DownloadImageQuality::from_str(quality, true).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn image_format(&self) -> CLIDownloadFormat {
+        self.format
+            .as_deref()
+            .and_then(|format| CLIDownloadFormat::from_str(format, true).ok())
+            .unwrap_or_default()
+    }
+
+    /// The proxies of the account and how to rotate them, the account proxy overrides the
+    /// source proxy.
+    pub(crate) fn proxies_for(
+        &self,
+        account_id: &str,
+        alias: Option<&str>,
+    ) -> Option<(Vec<String>, ProxyRotation)> {
+        let account = self
+            .accounts
+            .get(account_id)
+            .or_else(|| alias.and_then(|alias| self.accounts.get(alias)));
+        let account_proxy = account.and_then(|account| account.proxy.as_ref());
+        let proxy = account_proxy.or(self.proxy.as_ref())?;
+        let rotation = account
+            .and_then(|account| account.proxy_rotation)
+            .or(self.proxy_rotation)
+            .unwrap_or_default();
+        Some((proxy.urls(), rotation))
+    }
+
+    fn validate(&self, section: &str) -> anyhow::Result<()> {
+        if let Some(quality) = &self.quality {
+            DownloadImageQuality::from_str(quality, true)
+                .map_err(|err| anyhow::anyhow!("{}.quality: {}", section, err))?;
+        }
+        if let Some(format) = &self.format {
+            CLIDownloadFormat::from_str(format, true)
+                .map_err(|err| anyhow::anyhow!("{}.format: {}", section, err))?;
+        }
+        if let Some(proxy) = &self.proxy {
+            proxy.validate(&format!("{}.proxy", section))?;
+        }
+        if section == "default" && !self.accounts.is_empty() {
+            anyhow::bail!(
+                "default.accounts: the account settings are only allowed in a source section"
+            );
+        }
+        for (account, settings) in self.accounts.iter() {
+            if let Some(proxy) = &settings.proxy {
+                proxy.validate(&format!("{}.accounts.{}.proxy", section, account))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The CLI defaults, stored as `settings.toml` in the config directory.
+///
+/// The `default` section applies to every source, and each source section overrides it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Settings {
+    #[serde(default)]
+    pub(crate) default: SourceSettings,
+    #[serde(default)]
+    pub(crate) km: SourceSettings,
+    #[serde(default)]
+    pub(crate) mu: SourceSettings,
+    #[serde(default)]
+    pub(crate) am: SourceSettings,
+    #[serde(default)]
+    pub(crate) sj: SourceSettings,
+    #[serde(default)]
+    pub(crate) rb: SourceSettings,
+}
+
+impl Settings {
+    pub(crate) fn default_path() -> PathBuf {
+        get_user_path().join(SETTINGS_FILE)
+    }
+
+    /// Parse and validate the settings.
+    pub(crate) fn parse(content: &str) -> anyhow::Result<Self> {
+        let settings: Settings = toml::from_str(content)?;
+        for (section, source) in settings.sections() {
+            source.validate(section)?;
+        }
+        Ok(settings)
+    }
+
+    /// Load the settings, a missing file is the default settings.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    pub(crate) fn sections(&self) -> [(&'static str, &SourceSettings); 6] {
+        [
+            ("default", &self.default),
+            ("km", &self.km),
+            ("mu", &self.mu),
+            ("am", &self.am),
+            ("sj", &self.sj),
+            ("rb", &self.rb),
+        ]
+    }
+
+    /// The settings of the source, with the unset values taken from the `default` section.
+    pub(crate) fn resolve(&self, source: Implementations) -> SourceSettings {
+        let section = match source {
+            Implementations::Kmkc => &self.km,
+            Implementations::Musq => &self.mu,
+            Implementations::Amap => &self.am,
+            Implementations::Sjv => &self.sj,
+            Implementations::Rbean => &self.rb,
+        };
+        section.or(&self.default)
+    }
+}
+
+/// The settings of the config directory, loaded once, fails if the settings file is invalid.
+pub(crate) fn try_get_settings() -> anyhow::Result<&'static Settings> {
+    SETTINGS
+        .get_or_init(|| Settings::load(&Settings::default_path()).map_err(|err| err.to_string()))
+        .as_ref()
+        .map_err(|err| anyhow::anyhow!("Failed to read {}: {}", SETTINGS_FILE, err))
+}
+
+/// The settings of the config directory.
+///
+/// The commands stop on an invalid settings file before getting here,
+/// except the `config` commands that use the default settings.
+pub(crate) fn get_settings() -> &'static Settings {
+    match try_get_settings() {
+        Ok(settings) => settings,
+        Err(_) => DEFAULT_SETTINGS.get_or_init(Settings::default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_layering() {
+        let settings = Settings::parse(
+            r#"
+            [default]
+            output = "/data/manga"
+            parallel = true
+
+            [mu]
+            quality = "normal"
+            output = "/data/mu"
+
+            [rb]
+            parallel = false
+            "#,
+        )
+        .unwrap();
+
+        let mu = settings.resolve(Implementations::Musq);
+        assert_eq!(mu.output, Some(PathBuf::from("/data/mu")));
+        assert_eq!(mu.parallel, Some(true));
+        assert!(matches!(mu.image_quality(), DownloadImageQuality::Normal));
+        assert_eq!(
+            mu.output_dir(Some(PathBuf::from("cli"))),
+            PathBuf::from("cli")
+        );
+
+        let rb = settings.resolve(Implementations::Rbean);
+        assert_eq!(rb.output, Some(PathBuf::from("/data/manga")));
+        assert_eq!(rb.parallel, Some(false));
+
+        assert!(Settings::parse("[mu]\nquality = \"ultra\"").is_err());
+        assert!(Settings::parse("[mu]\nproxy = []").is_err());
+        assert!(Settings::parse("[default.accounts.main]\nproxy = \"http://a:1\"").is_err());
+        assert!(Settings::parse("[mu]\nunknown = 1").is_err());
+
+        let settings = Settings::parse(
+            r#"
+            [default]
+            proxy = "http://default:8080"
+
+            [km]
+            proxy = ["http://km-1:8080", "http://km-2:8080"]
+            proxyRotation = "batch"
+
+            [km.accounts.main]
+            proxy = "http://jp:3128"
+            "#,
+        )
+        .unwrap();
+
+        let km = settings.resolve(Implementations::Kmkc);
+        assert_eq!(
+            km.proxies_for("123", None),
+            Some((
+                vec![
+                    "http://km-1:8080".to_string(),
+                    "http://km-2:8080".to_string()
+                ],
+                ProxyRotation::Batch
+            ))
+        );
+        assert_eq!(
+            km.proxies_for("456", Some("main")),
+            Some((vec!["http://jp:3128".to_string()], ProxyRotation::Batch))
+        );
+        let sj = settings.resolve(Implementations::Sjv);
+        assert_eq!(
+            sj.proxies_for("789", None),
+            Some((
+                vec!["http://default:8080".to_string()],
+                ProxyRotation::Failure
+            ))
+        );
+        assert_eq!(
+            Settings::default()
+                .resolve(Implementations::Sjv)
+                .proxies_for("789", None),
+            None
+        );
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// The vault file name inside the config directory, encryption is enabled when it exists.
+pub(crate) const VAULT_FILE: &str = "vault.json";
+/// The previous vault, kept until every config is encrypted with the new key.
+pub(crate) const OLD_VAULT_FILE: &str = "vault.json.old";
+/// The environment variable used to unlock the vault without prompting.
+pub(crate) const PASSPHRASE_ENV: &str = "AIRPOPE_PASSPHRASE";
+
+/// Header of every encrypted config file.
+const MAGIC: &[u8; 8] = b"APVAULT1";
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+/// Encrypted into the vault file to tell a wrong passphrase apart.
+const CHECK_TEXT: &[u8] = b"airpope-vault";
+
+/// The unlocked key of the vault and the config directory it belongs to.
+static UNLOCKED: Mutex<Option<(PathBuf, VaultKey)>> = Mutex::new(None);
+/// The config directory that failed to unlock and why, to only ask once per process.
+static UNLOCK_FAILED: Mutex<Option<(PathBuf, String)>> = Mutex::new(None);
+/// The key of `vault.json.old` and its config directory, `None` when it failed to unlock.
+static PREVIOUS_UNLOCKED: Mutex<Option<(PathBuf, Option<VaultKey>)>> = Mutex::new(None);
+/// Set when nobody can answer a passphrase prompt anymore, like in the API server.
+static PROMPT_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// The vault can't be unlocked, so the encrypted configs can't be read or written.
+#[derive(Debug)]
+pub(crate) struct VaultLockedError(String);
+
+impl std::fmt::Display for VaultLockedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to unlock the accounts: {}", self.0)
+    }
+}
+
+impl std::error::Error for VaultLockedError {}
+
+/// The config is encrypted with neither the current nor the previous key.
+#[derive(Debug)]
+pub(crate) struct VaultDecryptError(String);
+
+impl std::fmt::Display for VaultDecryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for VaultDecryptError {}
+
+/// The key derivation settings of the vault, stored as `vault.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Vault {
+    version: u32,
+    /// Base64 encoded salt of the key derivation.
+    salt: String,
+    /// Argon2id memory cost in KiB.
+    memory_cost: u32,
+    /// Argon2id iterations.
+    time_cost: u32,
+    /// Argon2id parallelism.
+    parallelism: u32,
+    /// Base64 encoded [`CHECK_TEXT`] encrypted with the key.
+    check: String,
+}
+
+/// A key derived from the vault passphrase.
+#[derive(Clone)]
+pub(crate) struct VaultKey(Key<Aes256Gcm>);
+
+impl VaultKey {
+    /// Encrypt the data, prefixed with the header and a random nonce.
+    pub(crate) fn encrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let cipher = Aes256Gcm::new(&self.0);
+        let encrypted = cipher
+            .encrypt(Nonce::from_slice(&nonce), data)
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt the data"))?;
+
+        let mut buffer = Vec::with_capacity(MAGIC.len() + NONCE_LEN + encrypted.len());
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&nonce);
+        buffer.extend_from_slice(&encrypted);
+        Ok(buffer)
+    }
+
+    /// Decrypt data created by [`VaultKey::encrypt`].
+    pub(crate) fn decrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if !is_encrypted(data) || data.len() < MAGIC.len() + NONCE_LEN {
+            anyhow::bail!("The data is not encrypted");
+        }
+
+        let (nonce, encrypted) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        let cipher = Aes256Gcm::new(&self.0);
+        cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| {
+                anyhow::anyhow!("Failed to decrypt the data, wrong passphrase or corrupted")
+            })
+    }
+}
+
+impl Vault {
+    pub(crate) fn path(user_path: &Path) -> PathBuf {
+        user_path.join(VAULT_FILE)
+    }
+
+    pub(crate) fn old_path(user_path: &Path) -> PathBuf {
+        user_path.join(OLD_VAULT_FILE)
+    }
+
+    /// Load the vault of the config directory, `None` when encryption is disabled.
+    pub(crate) fn load(user_path: &Path) -> anyhow::Result<Option<Self>> {
+        Self::load_file(&Self::path(user_path))
+    }
+
+    /// Load the vault left by an interrupted passphrase change, if any.
+    pub(crate) fn load_old(user_path: &Path) -> anyhow::Result<Option<Self>> {
+        Self::load_file(&Self::old_path(user_path))
+    }
+
+    fn load_file(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let vault: Vault = serde_json::from_str(&content)?;
+        Ok(Some(vault))
+    }
+
+    pub(crate) fn save(&self, user_path: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(user_path)?;
+        let content = serde_json::to_string_pretty(self)?;
+        write_atomic(&Self::path(user_path), content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Create a new vault with a random salt, and the key of the passphrase.
+    pub(crate) fn create(passphrase: &str) -> anyhow::Result<(Self, VaultKey)> {
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+
+        let params = argon2::Params::default();
+        let mut vault = Vault {
+            version: 1,
+            salt: STANDARD.encode(salt),
+            memory_cost: params.m_cost(),
+            time_cost: params.t_cost(),
+            parallelism: params.p_cost(),
+            check: String::new(),
+        };
+
+        let key = vault.derive(passphrase)?;
+        vault.check = STANDARD.encode(key.encrypt(CHECK_TEXT)?);
+        Ok((vault, key))
+    }
+
+    fn derive(&self, passphrase: &str) -> anyhow::Result<VaultKey> {
+        let salt = STANDARD.decode(&self.salt)?;
+        let params =
+            argon2::Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(32))
+                .map_err(|err| anyhow::anyhow!("Invalid vault parameters: {}", err))?;
+        let argon =
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+        let mut key = [0u8; 32];
+        argon
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|err| anyhow::anyhow!("Failed to derive the vault key: {}", err))?;
+        Ok(VaultKey(key.into()))
+    }
+
+    /// Derive the key of the passphrase, failing if it's the wrong passphrase.
+    pub(crate) fn unlock(&self, passphrase: &str) -> anyhow::Result<VaultKey> {
+        let key = self.derive(passphrase)?;
+        let check = STANDARD.decode(&self.check)?;
+        match key.decrypt(&check) {
+            Ok(text) if text == CHECK_TEXT => Ok(key),
+            _ => anyhow::bail!("Wrong passphrase"),
+        }
+    }
+}
+
+/// Check if the data is an encrypted config.
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Ask for the passphrase of the vault, from [`PASSPHRASE_ENV`] first.
+pub(crate) fn ask_passphrase(vault: &Vault, prompt: &str) -> anyhow::Result<VaultKey> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return vault
+            .unlock(&passphrase)
+            .map_err(|err| anyhow::anyhow!("{} from {}", err, PASSPHRASE_ENV));
+    }
+
+    prompt_passphrase(vault, prompt)
+}
+
+/// Prompt the passphrase of the vault, with up to three tries.
+fn prompt_passphrase(vault: &Vault, prompt: &str) -> anyhow::Result<VaultKey> {
+    if PROMPT_DISABLED.load(Ordering::Relaxed) {
+        anyhow::bail!(
+            "Unable to prompt the passphrase, set {} instead",
+            PASSPHRASE_ENV
+        );
+    }
+
+    for _ in 0..3 {
+        let passphrase = inquire::Password::new(prompt)
+            .without_confirmation()
+            .prompt()
+            .map_err(|err| {
+                anyhow::anyhow!(
+                    "Unable to prompt the passphrase ({}), set {} instead",
+                    err,
+                    PASSPHRASE_ENV
+                )
+            })?;
+
+        match vault.unlock(&passphrase) {
+            Ok(key) => return Ok(key),
+            Err(err) => crate::term::get_console(0).warn(&format!("{}, try again", err)),
+        }
+    }
+
+    anyhow::bail!("Wrong passphrase")
+}
+
+/// Ask for a new passphrase with confirmation, [`PASSPHRASE_ENV`] is used first if allowed.
+pub(crate) fn prompt_new_passphrase(
+    allow_env: bool,
+    console: &crate::term::Terminal,
+) -> Option<String> {
+    if allow_env {
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+            console.info(&format!("Using the passphrase from {}", PASSPHRASE_ENV));
+            return Some(passphrase);
+        }
+    }
+
+    let passphrase = inquire::Password::new("New passphrase:")
+        .with_custom_confirmation_message("Confirm the passphrase:")
+        .with_custom_confirmation_error_message("The passphrases don't match")
+        .prompt();
+
+    match passphrase {
+        Ok(passphrase) if passphrase.is_empty() => {
+            console.error("The passphrase can't be empty");
+            None
+        }
+        Ok(passphrase) => Some(passphrase),
+        Err(err) => {
+            console.error(&format!("Failed to prompt the passphrase: {}", err));
+            None
+        }
+    }
+}
+
+/// Get the key of the config directory, asking for the passphrase once per process.
+///
+/// Returns `None` when encryption is disabled.
+pub(crate) fn unlock(user_path: &Path) -> anyhow::Result<Option<VaultKey>> {
+    let vault = match Vault::load(user_path)? {
+        Some(vault) => vault,
+        None => return Ok(None),
+    };
+
+    let mut unlocked = UNLOCKED.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((path, key)) = unlocked.as_ref() {
+        if path == user_path {
+            return Ok(Some(key.clone()));
+        }
+    }
+
+    let key = ask_passphrase(&vault, "Passphrase to unlock the accounts:")?;
+    *unlocked = Some((user_path.to_path_buf(), key.clone()));
+    Ok(Some(key))
+}
+
+/// Never prompt for a passphrase for the rest of the process.
+pub(crate) fn disable_passphrase_prompt() {
+    PROMPT_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Check if the config directory has a vault that is not unlocked yet.
+pub(crate) fn is_locked(user_path: &Path) -> bool {
+    if !Vault::path(user_path).exists() {
+        return false;
+    }
+
+    let unlocked = UNLOCKED.lock().unwrap_or_else(|err| err.into_inner());
+    !matches!(unlocked.as_ref(), Some((path, _)) if path == user_path)
+}
+
+/// Replace the cached key after the vault is created, changed or removed.
+pub(crate) fn set_unlocked(user_path: &Path, key: Option<VaultKey>) {
+    let mut unlocked = UNLOCKED.lock().unwrap_or_else(|err| err.into_inner());
+    *unlocked = key.map(|key| (user_path.to_path_buf(), key));
+    *UNLOCK_FAILED.lock().unwrap_or_else(|err| err.into_inner()) = None;
+    *PREVIOUS_UNLOCKED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+/// Same as [`unlock`] but the failure is a [`VaultLockedError`], reported and returned
+/// again without asking for the rest of the process.
+fn unlock_for_config(user_path: &Path) -> Result<Option<VaultKey>, VaultLockedError> {
+    let mut failed = UNLOCK_FAILED.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((path, reason)) = failed.as_ref() {
+        if path == user_path {
+            return Err(VaultLockedError(reason.clone()));
+        }
+    }
+
+    match unlock(user_path) {
+        Ok(key) => Ok(key),
+        Err(err) => {
+            let err = VaultLockedError(err.to_string());
+            crate::term::get_console(0).error(&err.to_string());
+            *failed = Some((user_path.to_path_buf(), err.0.clone()));
+            Err(err)
+        }
+    }
+}
+
+/// Get the key of `vault.json.old`, asking for the previous passphrase once per process.
+///
+/// The old vault is only left behind by an interrupted passphrase change.
+fn unlock_previous(user_path: &Path) -> Option<VaultKey> {
+    let mut previous = PREVIOUS_UNLOCKED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    if let Some((path, key)) = previous.as_ref() {
+        if path == user_path {
+            return key.clone();
+        }
+    }
+
+    let vault = match Vault::load_old(user_path) {
+        Ok(Some(vault)) => vault,
+        _ => return None,
+    };
+
+    let console = crate::term::get_console(0);
+    console.warn(&format!(
+        "Some accounts are still encrypted with the previous passphrase kept in {}",
+        OLD_VAULT_FILE
+    ));
+    // the environment passphrase is usually the new one, so only use it if it matches
+    let from_env = std::env::var(PASSPHRASE_ENV)
+        .ok()
+        .and_then(|passphrase| vault.unlock(&passphrase).ok());
+    let key = match from_env {
+        Some(key) => Ok(key),
+        None => prompt_passphrase(&vault, "Previous passphrase of the accounts:"),
+    };
+    let key = match key {
+        Ok(key) => Some(key),
+        Err(err) => {
+            console.warn(&format!("Failed to unlock {}: {}", OLD_VAULT_FILE, err));
+            None
+        }
+    };
+    *previous = Some((user_path.to_path_buf(), key.clone()));
+    key
+}
+
+/// Decrypt a config file with the key, falling back to the key of `vault.json.old`.
+///
+/// A config decrypted with the previous key is encrypted again with the current key in place.
+pub(crate) fn decrypt_config(
+    path: &Path,
+    buffer: &[u8],
+    key: &VaultKey,
+) -> anyhow::Result<Vec<u8>> {
+    let err = match key.decrypt(buffer) {
+        Ok(decrypted) => return Ok(decrypted),
+        Err(err) => err,
+    };
+
+    let user_path = path.parent().unwrap_or(Path::new("."));
+    let decrypted = match unlock_previous(user_path).map(|previous| previous.decrypt(buffer)) {
+        Some(Ok(decrypted)) => decrypted,
+        _ => {
+            return Err(VaultDecryptError(format!(
+                "Unable to decrypt {}, the file is kept as is: {}",
+                path.display(),
+                err
+            ))
+            .into())
+        }
+    };
+
+    write_atomic(path, &key.encrypt(&decrypted)?)?;
+    crate::term::get_console(0).info(&format!(
+        "Recovered {} with the previous passphrase",
+        path.display()
+    ));
+    Ok(decrypted)
+}
+
+/// Write the file through a temporary file so a crash never leaves it half written.
+///
+/// The temporary file is per process since some files are shared by concurrent runs.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&temp_path, data)?;
+    std::fs::rename(&temp_path, path)
+}
+
+/// Read a config file, decrypting it if needed.
+///
+/// A plain config is encrypted in place when encryption is enabled.
+pub(crate) fn read_config_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let buffer = std::fs::read(path)?;
+    let user_path = path.parent().unwrap_or(Path::new("."));
+
+    if is_encrypted(&buffer) {
+        match unlock_for_config(user_path)? {
+            Some(key) => decrypt_config(path, &buffer, &key),
+            None => anyhow::bail!(
+                "{} is encrypted but {} is missing",
+                path.display(),
+                VAULT_FILE
+            ),
+        }
+    } else {
+        if let Some(key) = unlock_for_config(user_path)? {
+            write_atomic(path, &key.encrypt(&buffer)?)?;
+        }
+        Ok(buffer)
+    }
+}
+
+/// Write a config file, encrypting it when encryption is enabled.
+pub(crate) fn write_config_file(path: &Path, buffer: &[u8]) -> anyhow::Result<()> {
+    let user_path = path.parent().unwrap_or(Path::new("."));
+    match unlock_for_config(user_path)? {
+        Some(key) => write_atomic(path, &key.encrypt(buffer)?)?,
+        None => write_atomic(path, buffer)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vault_roundtrip() {
+        let (vault, key) = Vault::create("hunter2").unwrap();
+        assert!(vault.unlock("hunter3").is_err());
+
+        let key2 = vault.unlock("hunter2").unwrap();
+        let encrypted = key.encrypt(b"secret session").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!is_encrypted(b"secret session"));
+        assert_eq!(key2.decrypt(&encrypted).unwrap(), b"secret session");
+
+        let mut tampered = encrypted.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(key2.decrypt(&tampered).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use color_print::cformat;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::get_user_path, r#impl::common::shell_command};
+
+/// The hooks file name inside the config directory.
+pub(crate) const HOOKS_FILE: &str = "hooks.json";
+
+lazy_static::lazy_static! {
+    static ref HOOKS_CONFIG: HooksConfig = load_hooks();
+}
+
+/// Load the default hooks, a malformed file is reported and disables the hooks.
+fn load_hooks() -> HooksConfig {
+    match HooksConfig::load(&HooksConfig::default_path()) {
+        Ok(hooks) => hooks,
+        Err(err) => {
+            crate::term::get_console(0).warn(&format!("Failed to read {}: {}", HOOKS_FILE, err));
+            HooksConfig::default()
+        }
+    }
+}
+
+/// The default hooks, stored as `hooks.json` in the config directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HooksConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) post_chapter_hook: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) post_title_hook: Option<String>,
+}
+
+impl HooksConfig {
+    pub(crate) fn default_path() -> PathBuf {
+        get_user_path().join(HOOKS_FILE)
+    }
+
+    /// Load the hooks, a missing file means no hooks.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let hooks: HooksConfig = serde_json::from_str(&content)?;
+        Ok(hooks)
+    }
+}
+
+/// The commands to run after a chapter or a title finished downloading.
+///
+/// Anything not given falls back to the hooks in `hooks.json`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PostHooks {
+    pub(crate) chapter: Option<String>,
+    pub(crate) title: Option<String>,
+}
+
+impl PostHooks {
+    pub(crate) fn new(chapter: Option<String>, title: Option<String>) -> Self {
+        Self { chapter, title }
+    }
+
+    fn chapter_hook(&self) -> Option<&str> {
+        self.chapter
+            .as_deref()
+            .or(HOOKS_CONFIG.post_chapter_hook.as_deref())
+    }
+
+    fn title_hook(&self) -> Option<&str> {
+        self.title
+            .as_deref()
+            .or(HOOKS_CONFIG.post_title_hook.as_deref())
+    }
+
+    /// Run the post-chapter hook for a downloaded chapter, if any.
+    pub(crate) async fn run_chapter(
+        &self,
+        source: &str,
+        title_id: impl ToString,
+        title_dir: &Path,
+        chapter_id: impl ToString,
+        chapter_dir: &Path,
+        console: &crate::term::Terminal,
+    ) {
+        if let Some(command) = self.chapter_hook() {
+            let mut envs = hook_envs("post-chapter", source, title_id, title_dir);
+            envs.push(("AIRPOPE_CHAPTER_ID", chapter_id.to_string()));
+            envs.push(("AIRPOPE_CHAPTER_DIR", chapter_dir.display().to_string()));
+            run_hook("post-chapter", command, envs, console).await;
+        }
+    }
+
+    /// Run the post-title hook once every chapter is downloaded, if any.
+    pub(crate) async fn run_title(
+        &self,
+        source: &str,
+        title_id: impl ToString,
+        title_dir: &Path,
+        console: &crate::term::Terminal,
+    ) {
+        if let Some(command) = self.title_hook() {
+            let envs = hook_envs("post-title", source, title_id, title_dir);
+            run_hook("post-title", command, envs, console).await;
+        }
+    }
+}
+
+fn hook_envs(
+    hook: &str,
+    source: &str,
+    title_id: impl ToString,
+    title_dir: &Path,
+) -> Vec<(&'static str, String)> {
+    vec![
+        ("AIRPOPE_HOOK", hook.to_string()),
+        ("AIRPOPE_SOURCE", source.to_string()),
+        ("AIRPOPE_TITLE_ID", title_id.to_string()),
+        ("AIRPOPE_TITLE_DIR", title_dir.display().to_string()),
+        (
+            "AIRPOPE_INFO_JSON",
+            title_dir.join("_info.json").display().to_string(),
+        ),
+    ]
+}
+
+/// Run the hook, a failure is only reported so the rest of the batch continues.
+async fn run_hook(
+    hook: &str,
+    command: &str,
+    envs: Vec<(&'static str, String)>,
+    console: &crate::term::Terminal,
+) {
+    console.info(&cformat!("   Running {} hook: <s>{}</>", hook, command));
+
+    let mut cmd = shell_command(command);
+    cmd.envs(envs).stdin(std::process::Stdio::null());
+
+    match cmd.status().await {
+        Ok(status) if status.success() => {}
+        Ok(status) => console.warn(&format!("   The {} hook exited with {}", hook, status)),
+        Err(err) => console.warn(&format!("   Unable to run the {} hook: {}", hook, err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_config_and_envs() {
+        let hooks: HooksConfig =
+            serde_json::from_str(r#"{"postChapterHook": "cbz \"$AIRPOPE_CHAPTER_DIR\""}"#).unwrap();
+        assert!(hooks.post_chapter_hook.is_some());
+        assert!(hooks.post_title_hook.is_none());
+
+        let envs = hook_envs("post-title", "KM", 1234, Path::new("/manga/1234"));
+        let get_env = |key: &str| {
+            envs.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(get_env("AIRPOPE_TITLE_ID"), Some("1234"));
+        assert_eq!(
+            get_env("AIRPOPE_INFO_JSON"),
+            Some(Path::new("/manga/1234/_info.json").to_str().unwrap())
+        );
+    }
+}
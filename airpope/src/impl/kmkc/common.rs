@@ -10,6 +10,9 @@ use airpope_kmkc::{
 
 use crate::{
     linkify,
+    r#impl::notify::{notify, NotifyEvent},
+    r#impl::accounts::check::account_error_event,
+    r#impl::Implementations,
     term::{get_console, ConsoleChoice},
 };
 
@@ -98,6 +101,12 @@ pub(super) async fn common_purchase_select(
     let user_point = client.get_user_point().await;
     if let Err(error) = user_point {
         console.error(&format!("Unable to get user point: {}", error));
+        notify(
+            NotifyEvent::new(account_error_event(Implementations::Kmkc, &error), "KM")
+                .with_message(format!("{} ({})", error, account.get_username())),
+            console,
+        )
+        .await;
         return (Err(error), None, vec![], None);
     }
     let user_point = user_point.unwrap();
@@ -2,7 +2,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use color_print::cformat;
-use airpope_kmkc::models::ImagePageNode;
+use airpope_kmkc::models::{EpisodeBadge, ImagePageNode};
 use airpope_kmkc::{
     models::{EpisodeNode, EpisodeViewerResponse, TicketInfoType, TitleNode},
     KMClient,
@@ -11,10 +11,25 @@ use airpope_kmkc::{
 use crate::term::Terminal;
 use crate::{
     cli::ExitCode,
+    r#impl::batch::manifest::BatchJob,
+    r#impl::budget::{BudgetGuard, PurchaseCost},
     r#impl::models::{ChapterDetailDump, MangaDetailDump},
+    r#impl::common::is_shutdown_requested,
+    r#impl::hooks::PostHooks,
+    r#impl::ledger::record_purchase,
+    r#impl::notify::{notify, EventKind, NotifyEvent},
+    r#impl::plan::{DownloadPlan, PlanAction},
+    r#impl::queue::QueueTracker,
+    r#impl::config::proxy::ProxyLease,
+    r#impl::sync::report_title_sync,
+    r#impl::tools::trim::trim_downloaded_chapter,
+    r#impl::Implementations,
 };
 
-use super::{common::common_purchase_select, config::Config};
+use super::{
+    common::{common_purchase_select, PurchasePoint},
+    config::Config,
+};
 
 #[derive(Clone, Debug, Default)]
 pub(crate) struct KMDownloadCliConfig {
@@ -40,6 +55,16 @@ pub(crate) struct KMDownloadCliConfig {
 
     pub(crate) no_ticket: bool,
     pub(crate) no_point: bool,
+    /// Trim uniform borders of the downloaded pages with the given tolerance.
+    pub(crate) trim: Option<u8>,
+    /// The commands to run after each chapter and the title are downloaded.
+    pub(crate) hooks: PostHooks,
+    /// How the download is recorded in the download queue.
+    pub(crate) queue: QueueTracker,
+    /// The proxy of the requests, to report its failures.
+    pub(crate) proxy: ProxyLease,
+    /// Only print the download plan without purchasing or downloading anything.
+    pub(crate) dry_run: bool,
 }
 
 fn check_downloaded_image_count(image_dir: &PathBuf, extension: &str) -> Option<usize> {
@@ -73,9 +98,11 @@ fn create_chapters_info(title: &TitleNode, chapters: Vec<EpisodeNode>) -> MangaD
     }
 
     MangaDetailDump::new(title.title.clone(), title.author.clone(), dumped_chapters)
+        .with_source("KM")
+        .with_title_id(title.id)
 }
 
-fn get_output_directory(
+pub(crate) fn get_output_directory(
     output_dir: &Path,
     title_id: i32,
     chapter_id: Option<i32>,
@@ -127,17 +154,105 @@ async fn kmkc_actual_downloader(
         .stream_download(&node.image.url, node.seed, writer)
         .await
     {
-        Ok(_) => {}
+        Ok(_) => {
+            progress.inc(1);
+            Ok(())
+        }
         Err(err) => {
-            console.error(&format!("    Failed to download image: {}", err));
             // silent delete the file
-            tokio::fs::remove_file(&img_dl_path).await?;
+            tokio::fs::remove_file(&img_dl_path)
+                .await
+                .unwrap_or_default();
+            Err(err)
         }
     }
+}
+
+/// The free and paid points spent by a purchase, the free points are used first.
+fn point_costs(free_point: u64, price: u64) -> Vec<(String, u64)> {
+    let free_point = free_point.min(price);
+    vec![
+        ("free point".to_string(), free_point),
+        ("paid point".to_string(), price - free_point),
+    ]
+}
+
+/// Simulate the ticket and point purchases of the chapters for `--dry-run`.
+fn plan_download(
+    chapters: &[&EpisodeNode],
+    user_point: &PurchasePoint,
+    dl_config: &KMDownloadCliConfig,
+) -> DownloadPlan {
+    // interactive download would prompt for every purchase, assume it got confirmed
+    let would_purchase = dl_config.auto_purchase || !dl_config.no_input;
+    let mut wallet_copy = user_point.point.point.clone();
+    let mut ticket_entry = user_point.ticket.clone();
+
+    let mut plan = DownloadPlan::default();
+    for chapter in chapters {
+        if chapter.is_available() {
+            let action = match chapter.badge {
+                EpisodeBadge::Free => PlanAction::Free,
+                _ => PlanAction::Owned,
+            };
+            plan.add(&chapter.title, chapter.id, action);
+            continue;
+        }
+
+        if !would_purchase {
+            plan.add(
+                &chapter.title,
+                chapter.id,
+                PlanAction::Skip("purchase is disabled".to_string()),
+            );
+            continue;
+        }
 
-    progress.inc(1);
+        if chapter.is_ticketable() && !dl_config.no_ticket {
+            if ticket_entry.is_title_available() {
+                ticket_entry.subtract_title();
+                plan.add(
+                    &chapter.title,
+                    chapter.id,
+                    PlanAction::Ticket("title ticket".to_string()),
+                );
+                continue;
+            } else if ticket_entry.is_premium_available() {
+                ticket_entry.subtract_premium();
+                plan.add(
+                    &chapter.title,
+                    chapter.id,
+                    PlanAction::Ticket("premium ticket".to_string()),
+                );
+                continue;
+            }
+        }
 
-    Ok(())
+        let price: u64 = chapter.point.try_into().unwrap_or(0);
+        if dl_config.no_point {
+            plan.add(
+                &chapter.title,
+                chapter.id,
+                PlanAction::Skip(format!("need {}P, point purchase is disabled", price)),
+            );
+            continue;
+        }
+
+        if !wallet_copy.can_purchase(price) {
+            let mut reason = format!("need {}P", price);
+            if chapter.is_ticketable() {
+                reason += " or ticket";
+            }
+            plan.add(&chapter.title, chapter.id, PlanAction::Skip(reason));
+            continue;
+        }
+
+        let costs = point_costs(wallet_copy.free_point, price);
+        wallet_copy.subtract(price);
+        plan.add(&chapter.title, chapter.id, PlanAction::Purchase(costs));
+    }
+
+    plan
 }
 
 pub(crate) async fn kmkc_download(
@@ -191,6 +306,7 @@ pub(crate) async fn kmkc_download(
                             || dl_config.chapter_ids.contains(&(ch.id as usize))
                     }
                 })
+                .filter(|&ch| dl_config.queue.accepts(ch.id))
                 .collect();
 
             if results.is_empty() {
@@ -198,6 +314,20 @@ pub(crate) async fn kmkc_download(
                 return 1;
             }
 
+            if dl_config.dry_run {
+                plan_download(&results, &user_point, &dl_config).print(console);
+                return 0;
+            }
+
+            let mut budget =
+                match BudgetGuard::load(Implementations::Kmkc, account.get_id(), title_id) {
+                    Ok(budget) => budget,
+                    Err(err) => {
+                        console.error(&format!("Failed to load the budgets: {}", err));
+                        return 1;
+                    }
+                };
+
             let mut wallet_copy = user_point.point.point.clone();
             let mut ticket_entry = user_point.ticket.clone();
             console.info(&format!("Downloading {} chapters...", results.len()));
@@ -248,6 +378,19 @@ pub(crate) async fn kmkc_download(
                         if let Some(ticket) = ticket_info {
                             match client.claim_episode_with_ticket(chapter.id, &ticket).await {
                                 Ok(_) => {
+                                    let event = NotifyEvent::new(EventKind::PurchaseMade, "KM")
+                                        .with_title(&title_detail.title)
+                                        .with_chapter(&chapter.title, chapter.id)
+                                        .with_cost(1, "ticket");
+                                    record_purchase(
+                                        Implementations::Kmkc,
+                                        account.get_id(),
+                                        title_id,
+                                        &event,
+                                        &[("ticket".to_string(), 1)],
+                                        console,
+                                    );
+                                    notify(event, console).await;
                                     download_chapters.push(chapter);
                                     // if chapter.bonus_point > 0 {
                                     //     chapters_with_bonus.push(chapter.id);
@@ -282,6 +425,14 @@ pub(crate) async fn kmkc_download(
                         continue;
                     }
 
+                    let price: u64 = chapter.point.try_into().unwrap_or(0);
+                    let cost =
+                        PurchaseCost::new(price, price.saturating_sub(wallet_copy.free_point));
+                    let costs = point_costs(wallet_copy.free_point, price);
+                    if !budget.allow(&cost, console) {
+                        continue;
+                    }
+
                     console.info(&cformat!(
                         "  Purchasing chapter <m,s>{}</> (<s>{}</>) for {}P...",
                         chapter.title,
@@ -290,6 +441,20 @@ pub(crate) async fn kmkc_download(
                     ));
                     match client.claim_episode(chapter, &mut wallet_copy).await {
                         Ok(_) => {
+                            let event = NotifyEvent::new(EventKind::PurchaseMade, "KM")
+                                .with_title(&title_detail.title)
+                                .with_chapter(&chapter.title, chapter.id)
+                                .with_cost(price, "point");
+                            record_purchase(
+                                Implementations::Kmkc,
+                                account.get_id(),
+                                title_id,
+                                &event,
+                                &costs,
+                                console,
+                            );
+                            notify(event, console).await;
+                            budget.record(&cost);
                             download_chapters.push(chapter);
                             // if chapter.bonus_point > 0 {
                             //     chapters_with_bonus.push(chapter.id);
@@ -303,6 +468,8 @@ pub(crate) async fn kmkc_download(
                 }
             }
 
+            budget.report(console);
+
             if download_chapters.is_empty() {
                 console.warn("No chapters to download after filtering, aborting");
                 return 1;
@@ -318,17 +485,49 @@ pub(crate) async fn kmkc_download(
                 .dump(&title_dump_path)
                 .expect("Failed to dump title info");
 
+            let queue = dl_config.queue.start(
+                BatchJob {
+                    account: Some(account.get_id().to_string()),
+                    output: Some(output_dir.clone()),
+                    trim: dl_config.trim,
+                    parallel: Some(dl_config.parallel),
+                    ..BatchJob::new(Implementations::Kmkc, title_id)
+                },
+                &title_detail.title,
+                download_chapters
+                    .iter()
+                    .map(|ch| (ch.id.to_string(), ch.title.clone()))
+                    .collect(),
+                console,
+            );
+
             for chapter in download_chapters {
+                if is_shutdown_requested() {
+                    console.warn("Shutdown requested, skipping the remaining chapters");
+                    break;
+                }
+
                 console.info(&cformat!(
                     "  Downloading chapter <m,s>{}</> ({})...",
                     chapter.title,
                     chapter.id
                 ));
+                queue.in_progress(chapter.id, console);
 
                 let viewer_info = client.get_episode_viewer(chapter).await;
 
                 if let Err(e) = viewer_info {
+                    dl_config.proxy.report_failure(&e);
                     console.error(&format!("Failed to get viewer info, ignoring: {}", e));
+                    queue.failed(chapter.id, &e, console);
+                    notify(
+                        NotifyEvent::new(EventKind::DownloadError, "KM")
+                            .with_title(&title_detail.title)
+                            .with_chapter(&chapter.title, chapter.id)
+                            .with_message(e),
+                        console,
+                    )
+                    .await;
                     continue;
                 }
 
@@ -345,6 +544,7 @@ pub(crate) async fn kmkc_download(
                                 chapter.title,
                                 chapter.id
                             ));
+                            queue.failed(chapter.id, "no pages", console);
                             continue;
                         }
 
@@ -355,6 +555,7 @@ pub(crate) async fn kmkc_download(
                                     chapter.title,
                                     chapter.id
                                 ));
+                                queue.done(chapter.id, console);
                                 continue;
                             }
                         }
@@ -370,6 +571,7 @@ pub(crate) async fn kmkc_download(
                                 chapter.title,
                                 chapter.id
                             ));
+                            queue.failed(chapter.id, "no pages", console);
                             continue;
                         }
 
@@ -380,6 +582,7 @@ pub(crate) async fn kmkc_download(
                                     chapter.title,
                                     chapter.id
                                 ));
+                                queue.done(chapter.id, console);
                                 continue;
                             }
                         }
@@ -420,7 +623,7 @@ pub(crate) async fn kmkc_download(
                 );
                 progress.set_message("Downloading");
 
-                if dl_config.parallel {
+                let failed_pages = if dl_config.parallel {
                     let tasks: Vec<_> = image_blocks
                         .iter()
                         .enumerate()
@@ -431,6 +634,7 @@ pub(crate) async fn kmkc_download(
                             let cnsl = console.clone();
                             let image = image.clone();
                             let progress = Arc::clone(&progress);
+                            let proxy = dl_config.proxy.clone();
 
                             tokio::spawn(async move {
                                 match kmkc_actual_downloader(
@@ -447,18 +651,28 @@ pub(crate) async fn kmkc_download(
                                 )
                                 .await
                                 {
-                                    Ok(_) => {}
+                                    Ok(_) => true,
                                     Err(e) => {
                                         cnsl.error(&format!("    Failed to download image: {}", e));
+                                        proxy.report_failure(&e);
+                                        false
                                     }
                                 }
                             })
                         })
                         .collect();
 
-                    futures::future::join_all(tasks).await;
+                    futures::future::join_all(tasks)
+                        .await
+                        .into_iter()
+                        .filter(|task| !matches!(task, Ok(true)))
+                        .count()
                 } else {
+                    let mut failed_pages = 0;
                     for (idx, image) in image_blocks.iter().enumerate() {
+                        if is_shutdown_requested() {
+                            break;
+                        }
                         match kmkc_actual_downloader(
                             KMKCDownloadNode {
                                 client: client.clone(),
@@ -476,12 +690,57 @@ pub(crate) async fn kmkc_download(
                             Ok(_) => {}
                             Err(e) => {
                                 console.error(&format!("    Failed to download image: {}", e));
+                                failed_pages += 1;
+                                dl_config.proxy.report_failure(&e);
                             }
                         }
                     }
-                }
+                    failed_pages
+                };
 
                 progress.finish_with_message("Downloaded");
+                if is_shutdown_requested() {
+                    // the next chapter stops the download
+                    queue.interrupted(chapter.id, console);
+                    continue;
+                }
+                if failed_pages > 0 {
+                    console.warn(&cformat!(
+                        "   Failed to download {} pages of chapter <m,s>{}</> (<s>{}</>)",
+                        failed_pages,
+                        chapter.title,
+                        chapter.id
+                    ));
+                    queue.failed(
+                        chapter.id,
+                        format!("{} pages failed to download", failed_pages),
+                        console,
+                    );
+                    continue;
+                }
+                if let Some(tolerance) = dl_config.trim {
+                    trim_downloaded_chapter(&image_dir, Implementations::Kmkc, tolerance, console)
+                        .await;
+                }
+                queue.done(chapter.id, console);
+                notify(
+                    NotifyEvent::new(EventKind::ChapterDownloaded, "KM")
+                        .with_title(&title_detail.title)
+                        .with_chapter(&chapter.title, chapter.id),
+                    console,
+                )
+                .await;
+                dl_config
+                    .hooks
+                    .run_chapter("KM", title_id, &title_dir, chapter.id, &image_dir, console)
+                    .await;
+            }
+
+            if !is_shutdown_requested() {
+                dl_config
+                    .hooks
+                    .run_title("KM", title_id, &title_dir, console)
+                    .await;
             }
 
             0
@@ -489,3 +748,60 @@ pub(crate) async fn kmkc_download(
         _ => 1,
     }
 }
+
+/// Fetch the title and every chapters of it as [`MangaDetailDump`].
+pub(crate) async fn kmkc_fetch_title_info(
+    title_id: i32,
+    client: &KMClient,
+) -> anyhow::Result<MangaDetailDump> {
+    let titles = client.get_titles(vec![title_id]).await?;
+    let title = titles
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("Unable to find title information"))?;
+
+    let mut chapters = vec![];
+    for episodes in title.episode_ids.clone().chunks(50) {
+        chapters.extend(client.get_episodes(episodes.to_vec()).await?);
+    }
+
+    Ok(create_chapters_info(title, chapters))
+}
+
+/// Compare the remote chapter list of a title with the local downloads.
+///
+/// With `download`, every available chapter will be downloaded without purchasing.
+pub(crate) async fn kmkc_sync(
+    title_id: i32,
+    download: bool,
+    output_dir: PathBuf,
+    client: &KMClient,
+    account: &Config,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    console.info(&cformat!(
+        "Fetching chapters for ID <m,s>{}</>...",
+        title_id
+    ));
+
+    let remote_info = match kmkc_fetch_title_info(title_id, client).await {
+        Ok(remote_info) => remote_info,
+        Err(e) => {
+            console.error(&format!("Failed to get title information: {}", e));
+            return 1;
+        }
+    };
+
+    let title_dir = get_output_directory(&output_dir, title_id, None, false);
+    let report = report_title_sync(&title_dir, &remote_info, console);
+
+    if download && report.has_downloadable() {
+        let dl_config = KMDownloadCliConfig {
+            no_input: true,
+            ..Default::default()
+        };
+
+        return kmkc_download(title_id, dl_config, output_dir, client, account, console).await;
+    }
+
+    0
+}
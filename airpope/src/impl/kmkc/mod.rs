@@ -3,6 +3,7 @@ use std::{ops::RangeInclusive, path::PathBuf};
 use clap::Subcommand;
 
 use super::parser::{parse_comma_number, CommaSeparatedNumber, WeeklyCodeCli};
+use super::tools::trim::DEFAULT_TRIM_TOLERANCE;
 
 use self::rankings::RankingType;
 
@@ -64,8 +65,11 @@ pub(crate) enum KMKCCommands {
         /// Title ID to use
         title_id: i32,
         /// Disable the auto purchase feature and only download free/purchased chapter(s).
-        #[arg(short = 'n', long)]
+        #[arg(short = 'n', long, overrides_with = "purchase")]
         no_purchase: bool,
+        /// Enable the auto purchase feature, overriding the settings
+        #[arg(long = "purchase", overrides_with = "no_purchase")]
+        purchase: bool,
         /// Specify the starting chapter ID to download
         #[arg(short = 's', long, default_value = None)]
         start_from: Option<i32>,
@@ -73,17 +77,38 @@ pub(crate) enum KMKCCommands {
         #[arg(short = 'e', long, default_value = None)]
         end_until: Option<i32>,
         /// Disable both title/premium ticket from being used to purchase chapters
-        #[arg(long)]
+        #[arg(long, overrides_with = "ticket")]
         no_ticket: bool,
+        /// Allow the use of title/premium ticket, overriding the settings
+        #[arg(long = "ticket", overrides_with = "no_ticket")]
+        ticket: bool,
         /// Disable the use of points to purchase chapters
-        #[arg(long)]
+        #[arg(long, overrides_with = "point")]
         no_point: bool,
+        /// Allow the use of points, overriding the settings
+        #[arg(long = "point", overrides_with = "no_point")]
+        point: bool,
         /// Output directory to use
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
         /// Enable parallel download
-        #[arg(short = 'p', long = "parallel")]
+        #[arg(short = 'p', long = "parallel", overrides_with = "no_parallel")]
         parallel: bool,
+        /// Disable parallel download, overriding the settings
+        #[arg(long = "no-parallel", overrides_with = "parallel")]
+        no_parallel: bool,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Get your account point balance
     Balance,
@@ -104,8 +129,23 @@ pub(crate) enum KMKCCommands {
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
         /// Enable parallel download
-        #[arg(short = 'x', long = "parallel")]
+        #[arg(short = 'x', long = "parallel", overrides_with = "no_parallel")]
         parallel: bool,
+        /// Disable parallel download, overriding the settings
+        #[arg(long = "no-parallel", overrides_with = "parallel")]
+        no_parallel: bool,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Get your account favorites list
     Favorites,
@@ -147,6 +187,17 @@ pub(crate) enum KMKCCommands {
         /// Query to search for
         query: String,
     },
+    /// Compare the chapters of a title with the local downloads
+    Sync {
+        /// Title ID to use
+        title_id: i32,
+        /// Download every available chapter without purchasing
+        #[arg(short = 'd', long = "download")]
+        download: bool,
+        /// Output directory to use
+        #[arg(short = 'o', long = "output", default_value = None)]
+        output: Option<PathBuf>,
+    },
     /// Get weekly releases
     Weekly {
         /// Day of the week to get releases for
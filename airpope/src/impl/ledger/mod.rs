@@ -0,0 +1,140 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::get_user_path, r#impl::notify::NotifyEvent};
+
+use super::Implementations;
+
+pub(crate) mod report;
+
+/// The ledger file name inside the config directory.
+pub(crate) const LEDGER_FILE: &str = "ledger.jsonl";
+
+/// A single purchase, stored as one JSON line in the ledger.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LedgerEntry {
+    pub(crate) timestamp: String,
+    pub(crate) source: Implementations,
+    pub(crate) account_id: String,
+    pub(crate) title_id: String,
+    pub(crate) title: String,
+    pub(crate) chapter_id: String,
+    pub(crate) chapter: String,
+    /// The currency used, ex: `free coin`, `paid point` or `ticket`
+    pub(crate) currency: String,
+    pub(crate) amount: u64,
+}
+
+impl LedgerEntry {
+    /// Create the entry of a single currency of a purchase event.
+    pub(crate) fn from_event(
+        source: Implementations,
+        account_id: &str,
+        title_id: impl ToString,
+        event: &NotifyEvent,
+        currency: &str,
+        amount: u64,
+    ) -> Self {
+        Self {
+            timestamp: event.timestamp.clone(),
+            source,
+            account_id: account_id.to_string(),
+            title_id: title_id.to_string(),
+            title: event.title.clone().unwrap_or_default(),
+            chapter_id: event.chapter_id.clone().unwrap_or_default(),
+            chapter: event.chapter.clone().unwrap_or_default(),
+            currency: currency.to_string(),
+            amount,
+        }
+    }
+
+    pub(crate) fn local_time(&self) -> Option<chrono::DateTime<chrono::Local>> {
+        chrono::DateTime::parse_from_rfc3339(&self.timestamp)
+            .ok()
+            .map(|dt| dt.with_timezone(&chrono::Local))
+    }
+}
+
+/// The append-only purchase ledger, stored as `ledger.jsonl` in the config directory.
+pub(crate) struct Ledger {
+    path: PathBuf,
+}
+
+impl Ledger {
+    pub(crate) fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub(crate) fn default_path() -> PathBuf {
+        get_user_path().join(LEDGER_FILE)
+    }
+
+    /// Append the entry to the end of the ledger.
+    pub(crate) fn append(&self, entry: &LedgerEntry) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Read every entry, a missing ledger means no purchases.
+    ///
+    /// Returns the entries and the number of lines that could not be parsed.
+    pub(crate) fn read(&self) -> anyhow::Result<(Vec<LedgerEntry>, usize)> {
+        if !self.path.exists() {
+            return Ok((vec![], 0));
+        }
+
+        let content = std::fs::read_to_string(&self.path)?;
+        let mut entries = vec![];
+        let mut invalid = 0;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str::<LedgerEntry>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => invalid += 1,
+            }
+        }
+
+        Ok((entries, invalid))
+    }
+}
+
+/// Record a purchase event into the ledger, one entry per currency it's paid with.
+///
+/// The unused currencies are skipped.
+/// A failure is only reported, the purchase itself already went through.
+pub(crate) fn record_purchase(
+    source: Implementations,
+    account_id: &str,
+    title_id: impl ToString,
+    event: &NotifyEvent,
+    costs: &[(String, u64)],
+    console: &crate::term::Terminal,
+) {
+    let ledger = Ledger::new(&Ledger::default_path());
+    let title_id = title_id.to_string();
+    for (currency, amount) in costs.iter().filter(|(_, amount)| *amount > 0) {
+        let entry =
+            LedgerEntry::from_event(source, account_id, &title_id, event, currency, *amount);
+        if let Err(err) = ledger.append(&entry) {
+            console.warn(&format!("   Failed to record the purchase: {}", err));
+            return;
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use clap::ValueEnum;
+use color_print::cformat;
+
+use crate::{cli::ExitCode, r#impl::Implementations};
+
+use super::{Ledger, LedgerEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum LedgerGroup {
+    /// Group by the purchase date, see `--period`
+    Period,
+    /// Group by title
+    Title,
+    /// Group by source currency
+    Currency,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum LedgerPeriod {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum LedgerExport {
+    Csv,
+    Json,
+}
+
+/// Which entries of the ledger to include.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LedgerFilter {
+    pub(crate) source: Option<Implementations>,
+    pub(crate) account_id: Option<String>,
+    pub(crate) title_id: Option<String>,
+    /// The first day to include, inclusive.
+    pub(crate) from: Option<chrono::NaiveDate>,
+    /// The last day to include, inclusive.
+    pub(crate) to: Option<chrono::NaiveDate>,
+}
+
+impl LedgerFilter {
+    fn accepts(&self, entry: &LedgerEntry) -> bool {
+        if self.source.is_some_and(|source| source != entry.source)
+            || self
+                .account_id
+                .as_ref()
+                .is_some_and(|id| id != &entry.account_id)
+            || self
+                .title_id
+                .as_ref()
+                .is_some_and(|id| id != &entry.title_id)
+        {
+            return false;
+        }
+
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+
+        match entry.local_time().map(|dt| dt.date_naive()) {
+            Some(date) => {
+                self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LedgerConfig {
+    pub(crate) filter: LedgerFilter,
+    pub(crate) group_by: LedgerGroup,
+    pub(crate) period: LedgerPeriod,
+    pub(crate) export: Option<LedgerExport>,
+    pub(crate) output: Option<PathBuf>,
+}
+
+/// The purchases of a single group.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct LedgerSummary {
+    purchases: usize,
+    /// The amount spent per source currency, ex: `MU free coin`
+    amounts: BTreeMap<String, u64>,
+}
+
+fn currency_key(entry: &LedgerEntry) -> String {
+    format!("{} {}", entry.source.source_name(), entry.currency)
+}
+
+fn group_key(entry: &LedgerEntry, group_by: LedgerGroup, period: LedgerPeriod) -> String {
+    match group_by {
+        LedgerGroup::Period => match entry.local_time() {
+            Some(dt) => match period {
+                LedgerPeriod::Day => dt.format("%Y-%m-%d").to_string(),
+                LedgerPeriod::Week => dt.format("%G-W%V").to_string(),
+                LedgerPeriod::Month => dt.format("%Y-%m").to_string(),
+                LedgerPeriod::Year => dt.format("%Y").to_string(),
+            },
+            None => "Unknown date".to_string(),
+        },
+        LedgerGroup::Title => format!(
+            "{} {} ({})",
+            entry.source.source_name(),
+            entry.title,
+            entry.title_id
+        ),
+        LedgerGroup::Currency => currency_key(entry),
+    }
+}
+
+/// A purchase paid with multiple currencies has one entry per currency, sharing the
+/// timestamp and the chapter.
+fn purchase_key(entry: &LedgerEntry) -> (&'static str, &str, &str) {
+    (
+        entry.source.source_name(),
+        &entry.timestamp,
+        &entry.chapter_id,
+    )
+}
+
+fn count_purchases(entries: &[LedgerEntry]) -> usize {
+    entries
+        .iter()
+        .map(purchase_key)
+        .collect::<BTreeSet<_>>()
+        .len()
+}
+
+fn summarize(
+    entries: &[LedgerEntry],
+    group_by: LedgerGroup,
+    period: LedgerPeriod,
+) -> BTreeMap<String, LedgerSummary> {
+    let mut summaries: BTreeMap<String, LedgerSummary> = BTreeMap::new();
+    let mut counted = BTreeSet::new();
+    for entry in entries {
+        let key = group_key(entry, group_by, period);
+        let is_new_purchase = counted.insert((key.clone(), purchase_key(entry)));
+        let summary = summaries.entry(key).or_default();
+        if is_new_purchase {
+            summary.purchases += 1;
+        }
+        *summary.amounts.entry(currency_key(entry)).or_default() += entry.amount;
+    }
+
+    summaries
+}
+
+fn format_amounts(amounts: &BTreeMap<String, u64>) -> String {
+    amounts
+        .iter()
+        .map(|(currency, amount)| format!("{} {}", amount, currency))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(entries: &[LedgerEntry]) -> String {
+    let mut content = String::from(
+        "timestamp,source,account_id,title_id,title,chapter_id,chapter,currency,amount\n",
+    );
+    for entry in entries {
+        let row = [
+            entry.timestamp.clone(),
+            entry.source.source_name().to_string(),
+            entry.account_id.clone(),
+            entry.title_id.clone(),
+            entry.title.clone(),
+            entry.chapter_id.clone(),
+            entry.chapter.clone(),
+            entry.currency.clone(),
+            entry.amount.to_string(),
+        ];
+        let row: Vec<String> = row.iter().map(|value| csv_escape(value)).collect();
+        content.push_str(&row.join(","));
+        content.push('\n');
+    }
+
+    content
+}
+
+fn export_entries(
+    entries: &[LedgerEntry],
+    export: LedgerExport,
+    output: Option<&PathBuf>,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let content = match export {
+        LedgerExport::Csv => to_csv(entries),
+        LedgerExport::Json => match serde_json::to_string_pretty(entries) {
+            Ok(content) => content,
+            Err(err) => {
+                console.error(&format!("Failed to serialize the ledger: {}", err));
+                return 1;
+            }
+        },
+    };
+
+    match output {
+        Some(output) => match std::fs::write(output, content) {
+            Ok(_) => {
+                console.info(&cformat!(
+                    "Exported <m,s>{}</> purchases to <s>{}</>",
+                    count_purchases(entries),
+                    output.display()
+                ));
+                0
+            }
+            Err(err) => {
+                console.error(&format!("Failed to write {}: {}", output.display(), err));
+                1
+            }
+        },
+        None => {
+            print!("{}", content);
+            if export == LedgerExport::Json {
+                println!();
+            }
+            0
+        }
+    }
+}
+
+pub(crate) fn ledger_report(config: LedgerConfig, console: &crate::term::Terminal) -> ExitCode {
+    let (entries, invalid) = match Ledger::new(&Ledger::default_path()).read() {
+        Ok(result) => result,
+        Err(err) => {
+            console.error(&format!("Failed to read the ledger: {}", err));
+            return 1;
+        }
+    };
+    // keep the standard output clean when exporting to it
+    let to_stdout = config.export.is_some() && config.output.is_none();
+    if invalid > 0 && !to_stdout {
+        console.warn(&format!("Skipped {} unreadable ledger lines", invalid));
+    }
+
+    let entries: Vec<LedgerEntry> = entries
+        .into_iter()
+        .filter(|entry| config.filter.accepts(entry))
+        .collect();
+
+    if let Some(export) = config.export {
+        return export_entries(&entries, export, config.output.as_ref(), console);
+    }
+
+    if entries.is_empty() {
+        console.warn("No purchases recorded!");
+        return 0;
+    }
+
+    let summaries = summarize(&entries, config.group_by, config.period);
+    console.info(&cformat!(
+        "Found <m,s>{}</> purchases:",
+        count_purchases(&entries)
+    ));
+    for (key, summary) in summaries.iter() {
+        console.info(&cformat!(
+            "  <m,s>{}</>: {} ({} purchases)",
+            key,
+            format_amounts(&summary.amounts),
+            summary.purchases
+        ));
+    }
+
+    let mut total: BTreeMap<String, u64> = BTreeMap::new();
+    for entry in entries.iter() {
+        *total.entry(currency_key(entry)).or_default() += entry.amount;
+    }
+    console.info(&cformat!("<s>Total</>: {}", format_amounts(&total)));
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: &str, source: Implementations, currency: &str, amount: u64) -> LedgerEntry {
+        LedgerEntry {
+            timestamp: timestamp.to_string(),
+            source,
+            account_id: "acc".to_string(),
+            title_id: "10".to_string(),
+            title: "Some, \"Title\"".to_string(),
+            chapter_id: "100".to_string(),
+            chapter: "Chapter 1".to_string(),
+            currency: currency.to_string(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_ledger_summary_and_csv() {
+        let entries = vec![
+            entry(
+                "2024-03-01T10:00:00+09:00",
+                Implementations::Musq,
+                "free coin",
+                30,
+            ),
+            // a single purchase paid with both free and paid coins
+            entry(
+                "2024-03-20T10:00:00+09:00",
+                Implementations::Musq,
+                "free coin",
+                10,
+            ),
+            entry(
+                "2024-03-20T10:00:00+09:00",
+                Implementations::Musq,
+                "paid coin",
+                40,
+            ),
+            entry(
+                "2024-03-21T10:00:00+09:00",
+                Implementations::Kmkc,
+                "ticket",
+                1,
+            ),
+        ];
+
+        assert_eq!(count_purchases(&entries), 3);
+
+        let summaries = summarize(&entries, LedgerGroup::Currency, LedgerPeriod::Month);
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries["MU free coin"].purchases, 2);
+        assert_eq!(summaries["MU free coin"].amounts["MU free coin"], 40);
+        assert_eq!(summaries["MU paid coin"].purchases, 1);
+
+        let by_month = summarize(&entries, LedgerGroup::Period, LedgerPeriod::Month);
+        assert_eq!(by_month["2024-03"].purchases, 3);
+        assert_eq!(
+            format_amounts(&by_month["2024-03"].amounts),
+            "1 KM ticket, 40 MU free coin, 40 MU paid coin"
+        );
+        assert_eq!(
+            format_amounts(&summaries["KM ticket"].amounts),
+            "1 KM ticket"
+        );
+
+        let filter = LedgerFilter {
+            source: Some(Implementations::Musq),
+            ..Default::default()
+        };
+        assert_eq!(entries.iter().filter(|e| filter.accepts(e)).count(), 3);
+
+        let csv = to_csv(&entries[..1]);
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(
+            row,
+            "2024-03-01T10:00:00+09:00,MU,acc,10,\"Some, \"\"Title\"\"\",100,Chapter 1,free coin,30"
+        );
+    }
+}
@@ -1,28 +1,66 @@
+pub(crate) mod accounts;
 pub(crate) mod amap;
+pub(crate) mod batch;
+pub(crate) mod budget;
 pub(crate) mod client;
 pub(super) mod common;
+pub(crate) mod config;
+pub(crate) mod hooks;
 pub(crate) mod kmkc;
+pub(crate) mod ledger;
 pub(crate) mod models;
 pub(crate) mod musq;
+pub(crate) mod notify;
 pub(super) mod parser;
+pub(crate) mod plan;
+pub(crate) mod queue;
 pub(crate) mod rbean;
+pub(crate) mod serve;
 pub(crate) mod sjv;
+pub(crate) mod sync;
 pub(crate) mod tools;
+pub(crate) mod watch;
 
 /// All available implementations
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
 pub enum Implementations {
     /// KM by KC
+    #[value(name = "km")]
+    #[serde(rename = "km")]
     Kmkc,
     /// MU! by SQ
+    #[value(name = "mu")]
+    #[serde(rename = "mu")]
     Musq,
     /// AM by AP
+    #[value(name = "am")]
+    #[serde(rename = "am")]
     Amap,
     /// SJ/M by V
+    #[value(name = "sj")]
+    #[serde(rename = "sj")]
     Sjv,
     /// 小豆 by KRKR
+    #[value(name = "rb")]
+    #[serde(rename = "rb")]
     Rbean,
 }
 
+impl Implementations {
+    /// The short source name, the same one used in `_info.json`.
+    pub fn source_name(&self) -> &'static str {
+        match self {
+            Implementations::Kmkc => "KM",
+            Implementations::Musq => "MU",
+            Implementations::Amap => "AM",
+            Implementations::Sjv => "SJ",
+            Implementations::Rbean => "RB",
+        }
+    }
+}
+
 const WINDOWS_RESERVED: [&str; 22] = [
     "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
     "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
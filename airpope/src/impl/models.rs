@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{collections::BTreeMap, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 use airpope_amap::models::{ComicEpisodeInfo, ComicEpisodeInfoNode};
@@ -60,7 +60,7 @@ pub struct ChapterDetailDump {
     /// The main chapter name.
     pub main_name: String,
     /// The timestamp of the chapter release date.
-    timestamp: Option<i64>,
+    pub(crate) timestamp: Option<i64>,
     /// The sub chapter name, if any.
     sub_name: Option<String>,
 }
@@ -72,6 +72,12 @@ pub struct MangaDetailDump {
     pub(crate) title_name: String,
     author_name: String,
     pub(crate) chapters: Vec<ChapterDetailDump>,
+    /// The source the manga is downloaded from, missing on older dumps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) source: Option<String>,
+    /// The title ID in the source, missing on older dumps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) title_id: Option<String>,
 }
 
 impl MangaDetailDump {
@@ -80,9 +86,23 @@ impl MangaDetailDump {
             title_name: title,
             author_name: author,
             chapters,
+            source: None,
+            title_id: None,
         }
     }
 
+    /// Set the source the manga is downloaded from.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    /// Set the title ID in the source, used to sync the title folder.
+    pub fn with_title_id(mut self, title_id: impl ToString) -> Self {
+        self.title_id = Some(title_id.to_string());
+        self
+    }
+
     /// Dump the info into `_info.json` format.
     ///
     /// # Arguments
@@ -198,6 +218,86 @@ pub struct MangaManualMergeDetail {
     pub(crate) chapters: Vec<MangaManualMergeChapterDetail>,
 }
 
+/// A single page moved by the merge process.
+///
+/// The paths are relative to the input folder.
+#[derive(Clone, Default, Deserialize, Serialize, Debug)]
+pub struct MangaMergeJournalPage {
+    pub(crate) source: String,
+    pub(crate) target: String,
+}
+
+/// A single merged chapter in the merge journal.
+#[derive(Clone, Default, Deserialize, Serialize, Debug)]
+pub struct MangaMergeJournalChapter {
+    pub(crate) name: String,
+    pub(crate) chapters: Vec<IdDump>,
+    pub(crate) pages: Vec<MangaMergeJournalPage>,
+}
+
+/// A single merge run in the merge journal.
+#[derive(Clone, Default, Deserialize, Serialize, Debug)]
+pub struct MangaMergeJournalRun {
+    /// The timestamp of when the merge is done.
+    pub(crate) timestamp: i64,
+    pub(crate) chapters: Vec<MangaMergeJournalChapter>,
+}
+
+/// The journal of all the merges done in a folder.
+///
+/// This is stored in `_merge_journal.json` and used to undo the merges.
+#[derive(Clone, Default, Deserialize, Serialize, Debug)]
+pub struct MangaMergeJournal {
+    pub(crate) title: String,
+    pub(crate) runs: Vec<MangaMergeJournalRun>,
+}
+
+/// How to handle chapters that goes backward in numbering
+/// (usually an extra/bonus chapter) when auto merging.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeExtraMode {
+    /// Put the chapter into its own `ex{base:03}.{extra}` folder.
+    #[default]
+    Extra,
+    /// Merge the chapter into the last known chapter.
+    Merge,
+    /// Do not merge the chapter at all.
+    Skip,
+}
+
+/// The ordering used before collecting chapters when auto merging.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeOrderBy {
+    /// Order by the chapter ID.
+    #[default]
+    Id,
+    /// Order by the chapter release timestamp, fallback to ID.
+    Timestamp,
+    /// Order by the chapter main name, fallback to ID.
+    Name,
+}
+
+/// A rules file used to automatically merge chapters without any prompt.
+///
+/// This is read from `_merge_rules.json` in the same folder as `_info.json`
+/// or from a custom path.
+#[derive(Clone, Default, Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MangaMergeRules {
+    /// Custom regexes to match the chapter title, tried in order before the default one.
+    ///
+    /// The regex should have a named group `base` and an optional named group `split`.
+    pub(crate) regexes: Vec<String>,
+    /// Explicit chapter ID to chapter number mapping.
+    pub(crate) overrides: BTreeMap<String, u64>,
+    /// How to handle extra chapters.
+    pub(crate) extra: MergeExtraMode,
+    /// How to order the chapters before merging.
+    pub(crate) order_by: MergeOrderBy,
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -233,4 +333,31 @@ mod tests {
         assert_eq!(chapter.timestamp, Some(1620000000));
         assert_eq!(chapter.sub_name, Some("Sub Chapter".to_string()));
     }
+
+    #[test]
+    fn test_deser_merge_rules() {
+        let json = r#"{
+            "regexes": ["Episode (?P<base>\\d+)"],
+            "overrides": {"1001": 12},
+            "extra": "skip",
+            "orderBy": "timestamp"
+        }"#;
+
+        let rules: super::MangaMergeRules = serde_json::from_str(json).unwrap();
+
+        assert_eq!(rules.regexes, vec![r"Episode (?P<base>\d+)".to_string()]);
+        assert_eq!(rules.overrides.get("1001"), Some(&12));
+        assert_eq!(rules.extra, super::MergeExtraMode::Skip);
+        assert_eq!(rules.order_by, super::MergeOrderBy::Timestamp);
+    }
+
+    #[test]
+    fn test_deser_merge_rules_default() {
+        let rules: super::MangaMergeRules = serde_json::from_str("{}").unwrap();
+
+        assert!(rules.regexes.is_empty());
+        assert!(rules.overrides.is_empty());
+        assert_eq!(rules.extra, super::MergeExtraMode::Extra);
+        assert_eq!(rules.order_by, super::MergeOrderBy::Id);
+    }
 }
@@ -8,6 +8,9 @@ use airpope_musq::{
 
 use crate::{
     linkify,
+    r#impl::notify::{notify, NotifyEvent},
+    r#impl::accounts::check::account_error_event,
+    r#impl::Implementations,
     term::{get_console, ConsoleChoice},
 };
 
@@ -170,6 +173,12 @@ pub(super) async fn common_purchase_select(
         }
         Err(e) => {
             console.error(&cformat!("Unable to connect to MU!: {}", e));
+            notify(
+                NotifyEvent::new(account_error_event(Implementations::Musq, &e), "MU")
+                    .with_message(&e),
+                console,
+            )
+            .await;
 
             (Err(e), None, None)
         }
@@ -4,16 +4,28 @@ use clap::ValueEnum;
 
 use color_print::cformat;
 use airpope_musq::{
-    proto::{ChapterV2, MangaDetailV2},
-    ImageQuality, MUClient,
+    proto::{ChapterV2, MangaDetailV2, UserPoint},
+    ConsumeCoin, ImageQuality, MUClient,
 };
 
 use crate::{
     cli::ExitCode,
+    r#impl::batch::manifest::BatchJob,
+    r#impl::budget::{BudgetGuard, PurchaseCost},
     r#impl::models::{ChapterDetailDump, MangaDetailDump},
+    r#impl::common::is_shutdown_requested,
+    r#impl::hooks::PostHooks,
+    r#impl::ledger::record_purchase,
+    r#impl::notify::{notify, EventKind, NotifyEvent},
+    r#impl::plan::{DownloadPlan, PlanAction},
+    r#impl::queue::QueueTracker,
+    r#impl::config::proxy::ProxyLease,
+    r#impl::sync::report_title_sync,
+    r#impl::tools::trim::trim_downloaded_chapter,
+    r#impl::Implementations,
 };
 
-use super::common::common_purchase_select;
+use super::{common::common_purchase_select, config::Config};
 
 #[derive(Debug, Clone, Default)]
 pub(crate) enum DownloadImageQuality {
@@ -78,6 +90,16 @@ pub(crate) struct MUDownloadCliConfig {
 
     pub(crate) no_paid_point: bool,
     pub(crate) no_xp_point: bool,
+    /// Trim uniform borders of the downloaded pages with the given tolerance.
+    pub(crate) trim: Option<u8>,
+    /// The commands to run after each chapter and the title are downloaded.
+    pub(crate) hooks: PostHooks,
+    /// How the download is recorded in the download queue.
+    pub(crate) queue: QueueTracker,
+    /// The proxy of the requests, to report its failures.
+    pub(crate) proxy: ProxyLease,
+    /// Only print the download plan without purchasing or downloading anything.
+    pub(crate) dry_run: bool,
 }
 
 fn check_downloaded_image_count(image_dir: &PathBuf) -> Option<usize> {
@@ -104,16 +126,18 @@ fn check_downloaded_image_count(image_dir: &PathBuf) -> Option<usize> {
     Some(count)
 }
 
-fn create_chapters_info(manga_detail: MangaDetailV2) -> MangaDetailDump {
+fn create_chapters_info(title_id: u64, manga_detail: MangaDetailV2) -> MangaDetailDump {
     let mut chapters: Vec<ChapterDetailDump> = vec![];
     for chapter in manga_detail.chapters {
         chapters.push(ChapterDetailDump::from(chapter));
     }
 
     MangaDetailDump::new(manga_detail.title, manga_detail.authors, chapters)
+        .with_source("MU")
+        .with_title_id(title_id)
 }
 
-fn get_output_directory(
+pub(crate) fn get_output_directory(
     output_dir: &Path,
     title_id: u64,
     chapter_id: Option<u64>,
@@ -133,11 +157,76 @@ fn get_output_directory(
     pathing
 }
 
+/// The coins spent by a purchase, per coin type.
+fn consume_costs(consume: &ConsumeCoin) -> Vec<(String, u64)> {
+    vec![
+        ("free coin".to_string(), consume.get_free()),
+        ("XP coin".to_string(), consume.get_event()),
+        ("paid coin".to_string(), consume.get_paid()),
+    ]
+}
+
+/// Simulate the coin purchases of the chapters for `--dry-run`.
+fn plan_download(
+    chapters: &[&ChapterV2],
+    coin_purse: &UserPoint,
+    client: &MUClient,
+    dl_config: &MUDownloadCliConfig,
+) -> DownloadPlan {
+    // interactive download would prompt for every purchase, assume it got confirmed
+    let would_purchase = dl_config.auto_purchase || !dl_config.no_input;
+    let mut coin_purse = coin_purse.clone();
+
+    let mut plan = DownloadPlan::default();
+    for chapter in chapters {
+        if chapter.is_free() {
+            plan.add(&chapter.title, chapter.id, PlanAction::Free);
+            continue;
+        }
+
+        let consume = client.calculate_coin(&coin_purse, chapter);
+        if !consume.is_possible() {
+            plan.add(
+                &chapter.title,
+                chapter.id,
+                PlanAction::Skip(format!(
+                    "need {} free coin, {} XP coin, and {} paid coin",
+                    consume.get_free(),
+                    consume.get_event(),
+                    consume.get_paid()
+                )),
+            );
+            continue;
+        }
+
+        if !would_purchase {
+            plan.add(
+                &chapter.title,
+                chapter.id,
+                PlanAction::Skip("purchase is disabled".to_string()),
+            );
+            continue;
+        }
+
+        coin_purse.free -= consume.get_free();
+        coin_purse.event -= consume.get_event();
+        coin_purse.paid -= consume.get_paid();
+        plan.add(
+            &chapter.title,
+            chapter.id,
+            PlanAction::Purchase(consume_costs(&consume)),
+        );
+    }
+
+    plan
+}
+
 pub(crate) async fn musq_download(
     title_id: u64,
     dl_config: MUDownloadCliConfig,
     output_dir: PathBuf,
     client: &MUClient,
+    account: &Config,
     console: &mut crate::term::Terminal,
 ) -> ExitCode {
     let (results, manga_detail, user_bal) = common_purchase_select(
@@ -176,12 +265,21 @@ pub(crate) async fn musq_download(
                             || dl_config.chapter_ids.contains(&(ch.id as usize))
                     }
                 })
+                .filter(|&ch| dl_config.queue.accepts(ch.id))
                 .collect();
 
             if results.is_empty() {
                 return 1;
             }
 
+            let mut budget = match BudgetGuard::load(Implementations::Musq, &account.id, title_id) {
+                Ok(budget) => budget,
+                Err(err) => {
+                    console.error(&format!("Failed to load the budgets: {}", err));
+                    return 1;
+                }
+            };
+
             let mut coin_purse = coin_purse.clone();
 
             if dl_config.no_paid_point {
@@ -191,6 +289,11 @@ pub(crate) async fn musq_download(
                 coin_purse.event = 0;
             }
 
+            if dl_config.dry_run {
+                plan_download(&results, &coin_purse, client, &dl_config).print(console);
+                return 0;
+            }
+
             console.info(&format!("Downloading {} chapters...", results.len()));
             let mut download_chapters = vec![];
             for chapter in results {
@@ -229,6 +332,14 @@ pub(crate) async fn musq_download(
                     should_purchase = console.confirm(Some(&prompt));
                 }
 
+                let cost = PurchaseCost::new(
+                    consume.get_free() + consume.get_event() + consume.get_paid(),
+                    consume.get_paid(),
+                );
+                if should_purchase && !budget.allow(&cost, console) {
+                    continue;
+                }
+
                 if should_purchase {
                     console.info(&cformat!(
                         "  Purchasing chapter <m,s>{}</> (<s>{}</>) with consumption <s>{:?}</>...",
@@ -262,6 +373,25 @@ pub(crate) async fn musq_download(
                                     chapter.id
                                 ));
                             } else {
+                                let event = NotifyEvent::new(EventKind::PurchaseMade, "MU")
+                                    .with_title(&manga_detail.title)
+                                    .with_chapter(&chapter.title, chapter.id)
+                                    .with_cost(
+                                        consume.get_free()
+                                            + consume.get_event()
+                                            + consume.get_paid(),
+                                        "coin",
+                                    );
+                                record_purchase(
+                                    Implementations::Musq,
+                                    &account.id,
+                                    title_id,
+                                    &event,
+                                    &consume_costs(&consume),
+                                    console,
+                                );
+                                notify(event, console).await;
+                                budget.record(&cost);
                                 download_chapters.push(chapter);
                                 coin_purse.free -= consume.get_free();
                                 coin_purse.event -= consume.get_event();
@@ -272,6 +402,8 @@ pub(crate) async fn musq_download(
                 }
             }
 
+            budget.report(console);
+
             if download_chapters.is_empty() {
                 console.warn("No chapters to be download after filtering, aborting");
                 return 1;
@@ -280,7 +412,7 @@ pub(crate) async fn musq_download(
             download_chapters.sort_by(|&a, &b| a.id.cmp(&b.id));
 
             let title_dir = get_output_directory(&output_dir, title_id, None, true);
-            let dump_info = create_chapters_info(manga_detail);
+            let dump_info = create_chapters_info(title_id, manga_detail);
 
             let title_dump_path = title_dir.join("_info.json");
             dump_info
@@ -288,12 +420,37 @@ pub(crate) async fn musq_download(
                 .expect("Failed to dump title info");
 
             let mut stored_blocks: Vec<airpope_musq::proto::PageBlock> = vec![];
+            let queue = dl_config.queue.start(
+                BatchJob {
+                    account: Some(account.id.clone()),
+                    quality: dl_config
+                        .quality
+                        .to_possible_value()
+                        .map(|value| value.get_name().to_string()),
+                    output: Some(output_dir.clone()),
+                    trim: dl_config.trim,
+                    ..BatchJob::new(Implementations::Musq, title_id)
+                },
+                &dump_info.title_name,
+                download_chapters
+                    .iter()
+                    .map(|ch| (ch.id.to_string(), ch.title.clone()))
+                    .collect(),
+                console,
+            );
+
             for chapter in download_chapters {
+                if is_shutdown_requested() {
+                    console.warn("Shutdown requested, skipping the remaining chapters");
+                    break;
+                }
+
                 console.info(&cformat!(
                     "  Downloading chapter <m,s>{}</> ({})...",
                     chapter.title,
                     chapter.id
                 ));
+                queue.in_progress(chapter.id, console);
 
                 let image_blocks = match stored_blocks.iter().find(|&b| b.id == chapter.id) {
                     Some(img_blocks) => img_blocks.images.clone(),
@@ -302,12 +459,22 @@ pub(crate) async fn musq_download(
                             .get_chapter_images(chapter.id, dl_config.quality.clone().into(), None)
                             .await;
                         if let Err(err) = ch_viewer {
+                            dl_config.proxy.report_failure(&err);
                             console.error(&format!("Failed to download chapter: {}", err));
+                            queue.failed(chapter.id, &err, console);
                             console.error(&cformat!(
                                 "   Skipping chapter <m,s>{}</> (<s>{}</>)",
                                 chapter.title,
                                 chapter.id
                             ));
+                            notify(
+                                NotifyEvent::new(EventKind::DownloadError, "MU")
+                                    .with_title(&dump_info.title_name)
+                                    .with_chapter(&chapter.title, chapter.id)
+                                    .with_message(err),
+                                console,
+                            )
+                            .await;
                             continue;
                         }
 
@@ -318,6 +485,7 @@ pub(crate) async fn musq_download(
                             chapter.title,
                             chapter.id
                         ));
+                            queue.failed(chapter.id, "empty image block", console);
                             continue;
                         }
 
@@ -336,6 +504,7 @@ pub(crate) async fn musq_download(
                                     chapter.title,
                                     chapter.id
                                 ));
+                                queue.failed(chapter.id, "missing chapter blocks", console);
                                 continue;
                             }
                         }
@@ -356,6 +525,7 @@ pub(crate) async fn musq_download(
                         chapter.title,
                         chapter.id
                     ));
+                    queue.failed(chapter.id, "no images", console);
                     continue;
                 }
 
@@ -367,6 +537,7 @@ pub(crate) async fn musq_download(
                             chapter.title,
                             chapter.id
                         ));
+                        queue.done(chapter.id, console);
                         continue;
                     }
                 }
@@ -375,8 +546,12 @@ pub(crate) async fn musq_download(
                 std::fs::create_dir_all(&ch_dir).unwrap();
 
                 // download images
+                let mut failed_pages = 0;
                 let total_image_count = image_blocks.len() as u64;
                 for image in image_blocks {
+                    if is_shutdown_requested() {
+                        break;
+                    }
                     let file_number: u64 = image.file_stem().parse().unwrap();
                     let img_fn = format!("p{:03}.{}", file_number, image.extension());
                     let img_dl_path = ch_dir.join(&img_fn);
@@ -399,6 +574,8 @@ pub(crate) async fn musq_download(
                         Ok(_) => {}
                         Err(err) => {
                             console.error(&format!("    Failed to download image: {}", err));
+                            failed_pages += 1;
+                            dl_config.proxy.report_failure(&err);
                             // silent delete the file
                             tokio::fs::remove_file(&img_dl_path)
                                 .await
@@ -407,6 +584,48 @@ pub(crate) async fn musq_download(
                     }
                 }
                 console.stop_progress(Some("Downloaded".to_string()));
+                if is_shutdown_requested() {
+                    // the next chapter stops the download
+                    queue.interrupted(chapter.id, console);
+                    continue;
+                }
+                if failed_pages > 0 {
+                    console.warn(&cformat!(
+                        "   Failed to download {} pages of chapter <m,s>{}</> (<s>{}</>)",
+                        failed_pages,
+                        chapter.title,
+                        chapter.id
+                    ));
+                    queue.failed(
+                        chapter.id,
+                        format!("{} pages failed to download", failed_pages),
+                        console,
+                    );
+                    continue;
+                }
+                if let Some(tolerance) = dl_config.trim {
+                    trim_downloaded_chapter(&ch_dir, Implementations::Musq, tolerance, console)
+                        .await;
+                }
+                queue.done(chapter.id, console);
+                notify(
+                    NotifyEvent::new(EventKind::ChapterDownloaded, "MU")
+                        .with_title(&dump_info.title_name)
+                        .with_chapter(&chapter.title, chapter.id),
+                    console,
+                )
+                .await;
+                dl_config
+                    .hooks
+                    .run_chapter("MU", title_id, &title_dir, chapter.id, &ch_dir, console)
+                    .await;
+            }
+
+            if !is_shutdown_requested() {
+                dl_config
+                    .hooks
+                    .run_title("MU", title_id, &title_dir, console)
+                    .await;
             }
 
             0
@@ -414,3 +633,52 @@ pub(crate) async fn musq_download(
         _ => 1,
     }
 }
+
+/// Fetch the title and every chapters of it as [`MangaDetailDump`].
+pub(crate) async fn musq_fetch_title_info(
+    title_id: u64,
+    client: &MUClient,
+) -> anyhow::Result<MangaDetailDump> {
+    let manga_detail = client.get_manga(title_id).await?;
+
+    Ok(create_chapters_info(title_id, manga_detail))
+}
+
+/// Compare the remote chapter list of a title with the local downloads.
+///
+/// With `download`, every available chapter will be downloaded without purchasing.
+pub(crate) async fn musq_sync(
+    title_id: u64,
+    download: bool,
+    output_dir: PathBuf,
+    client: &MUClient,
+    account: &Config,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    console.info(&cformat!(
+        "Fetching chapters for ID <m,s>{}</>...",
+        title_id
+    ));
+
+    let remote_info = match musq_fetch_title_info(title_id, client).await {
+        Ok(remote_info) => remote_info,
+        Err(e) => {
+            console.error(&format!("Failed to fetch manga: {}", e));
+            return 1;
+        }
+    };
+
+    let title_dir = get_output_directory(&output_dir, title_id, None, false);
+    let report = report_title_sync(&title_dir, &remote_info, console);
+
+    if download && report.has_downloadable() {
+        let dl_config = MUDownloadCliConfig {
+            no_input: true,
+            ..Default::default()
+        };
+
+        return musq_download(title_id, dl_config, output_dir, client, account, console).await;
+    }
+
+    0
+}
@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use clap::Subcommand;
 
 use super::parser::{parse_comma_number, CommaSeparatedNumber, WeeklyCodeCli};
+use super::tools::trim::DEFAULT_TRIM_TOLERANCE;
 
 pub(crate) mod accounts;
 pub(super) mod common;
@@ -33,8 +34,11 @@ pub(crate) enum MUSQCommands {
         /// Title ID to use
         title_id: u64,
         /// Disable the auto purchase feature and only download free/purchased chapter(s).
-        #[arg(short = 'n', long)]
+        #[arg(short = 'n', long, overrides_with = "purchase")]
         no_purchase: bool,
+        /// Enable the auto purchase feature, overriding the settings
+        #[arg(long = "purchase", overrides_with = "no_purchase")]
+        purchase: bool,
         /// Specify the starting chapter ID to download
         #[arg(short = 's', long, default_value = None)]
         start_from: Option<u64>,
@@ -42,17 +46,35 @@ pub(crate) enum MUSQCommands {
         #[arg(short = 'e', long, default_value = None)]
         end_until: Option<u64>,
         /// Disable the use of paid coins to purchase chapters
-        #[arg(long = "no-paid")]
+        #[arg(long = "no-paid", overrides_with = "paid_coins")]
         no_paid_coins: bool,
+        /// Allow the use of paid coins, overriding the settings
+        #[arg(long = "paid", overrides_with = "no_paid_coins")]
+        paid_coins: bool,
         /// Disable the use of XP/event coins to purchase chapters
-        #[arg(long = "no-xp")]
+        #[arg(long = "no-xp", overrides_with = "xp_coins")]
         no_xp_coins: bool,
-        /// Specify the image quality to download
-        #[arg(short = 'q', long = "quality", default_value = "high", value_enum)]
-        quality: crate::r#impl::musq::download::DownloadImageQuality,
+        /// Allow the use of XP/event coins, overriding the settings
+        #[arg(long = "xp", overrides_with = "no_xp_coins")]
+        xp_coins: bool,
+        /// Specify the image quality to download, default to high
+        #[arg(short = 'q', long = "quality", value_enum)]
+        quality: Option<crate::r#impl::musq::download::DownloadImageQuality>,
         /// Output directory to use
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Get your account point balance
     Balance,
@@ -69,12 +91,24 @@ pub(crate) enum MUSQCommands {
         /// Automatically purchase chapters if needed
         #[arg(short = 'p', long = "auto-purchase")]
         auto_purchase: bool,
-        /// Specify the image quality to download
-        #[arg(short = 'q', long = "quality", default_value = "high", value_enum)]
-        quality: crate::r#impl::musq::download::DownloadImageQuality,
+        /// Specify the image quality to download, default to high
+        #[arg(short = 'q', long = "quality", value_enum)]
+        quality: Option<crate::r#impl::musq::download::DownloadImageQuality>,
         /// Output directory to use
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Get your account favorites list
     Favorites,
@@ -110,6 +144,17 @@ pub(crate) enum MUSQCommands {
         /// Query to search for
         query: String,
     },
+    /// Compare the chapters of a title with the local downloads
+    Sync {
+        /// Title ID to use
+        title_id: u64,
+        /// Download every available chapter without purchasing
+        #[arg(short = 'd', long = "download")]
+        download: bool,
+        /// Output directory to use
+        #[arg(short = 'o', long = "output", default_value = None)]
+        output: Option<PathBuf>,
+    },
     /// Get weekly releases
     Weekly {
         /// Day of the week to get releases for
@@ -0,0 +1,273 @@
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use color_print::cformat;
+use serde::{Deserialize, Serialize};
+
+use crate::{cli::ExitCode, config::get_user_path};
+
+use super::dispatch::{send_to, EventKind, NotifyEvent};
+
+/// The notifiers file name inside the config directory.
+pub(crate) const NOTIFIERS_FILE: &str = "notifiers.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum NotifierKindCli {
+    /// POST the event as JSON to an URL
+    Webhook,
+    /// Run a local command with the event as AIRPOPE_* environment variables
+    Command,
+    /// Show a desktop notification
+    Desktop,
+}
+
+/// Where a notification is delivered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub(crate) enum NotifierKind {
+    Webhook { url: String },
+    Command { command: String },
+    Desktop,
+}
+
+impl std::fmt::Display for NotifierKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotifierKind::Webhook { url } => write!(f, "webhook: {}", url),
+            NotifierKind::Command { command } => write!(f, "command: {}", command),
+            NotifierKind::Desktop => write!(f, "desktop"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Notifier {
+    #[serde(flatten)]
+    pub(crate) kind: NotifierKind,
+    /// The events to fire on, empty means every event.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) events: Vec<EventKind>,
+}
+
+impl Notifier {
+    pub(crate) fn accepts(&self, event: EventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// The configured notifiers, stored as `notifiers.json` in the config directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Notifiers {
+    pub(crate) notifiers: Vec<Notifier>,
+}
+
+impl Notifiers {
+    pub(crate) fn default_path() -> PathBuf {
+        get_user_path().join(NOTIFIERS_FILE)
+    }
+
+    /// Load the notifiers, a missing file means no notifiers.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let notifiers: Notifiers = serde_json::from_str(&content)?;
+        Ok(notifiers)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+fn load_notifiers(console: &crate::term::Terminal) -> Option<Notifiers> {
+    match Notifiers::load(&Notifiers::default_path()) {
+        Ok(notifiers) => Some(notifiers),
+        Err(err) => {
+            console.error(&format!("Failed to read the notifiers: {}", err));
+            None
+        }
+    }
+}
+
+fn save_notifiers(notifiers: &Notifiers, console: &crate::term::Terminal) -> ExitCode {
+    match notifiers.save(&Notifiers::default_path()) {
+        Ok(_) => 0,
+        Err(err) => {
+            console.error(&format!("Failed to save the notifiers: {}", err));
+            1
+        }
+    }
+}
+
+fn format_events(events: &[EventKind]) -> String {
+    if events.is_empty() {
+        return "all events".to_string();
+    }
+
+    events
+        .iter()
+        .map(|event| event.to_name())
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+pub(crate) fn notify_add(
+    kind: NotifierKindCli,
+    target: Option<String>,
+    events: Vec<EventKind>,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let kind = match (kind, target) {
+        (NotifierKindCli::Webhook, Some(url)) => {
+            if reqwest::Url::parse(&url).is_err() {
+                console.error(&format!("Invalid webhook URL: {}", url));
+                return 1;
+            }
+            NotifierKind::Webhook { url }
+        }
+        (NotifierKindCli::Command, Some(command)) => NotifierKind::Command { command },
+        (NotifierKindCli::Desktop, None) => NotifierKind::Desktop,
+        (NotifierKindCli::Desktop, Some(_)) => {
+            console.error("Desktop notifier does not take any target");
+            return 1;
+        }
+        (_, None) => {
+            console.error("Webhook and command notifiers need a target URL or command");
+            return 1;
+        }
+    };
+
+    let mut notifiers = match load_notifiers(console) {
+        Some(notifiers) => notifiers,
+        None => return 1,
+    };
+
+    let mut unique_events = vec![];
+    for event in events {
+        if !unique_events.contains(&event) {
+            unique_events.push(event);
+        }
+    }
+    let events = unique_events;
+    let notifier = Notifier { kind, events };
+    console.info(&cformat!(
+        "Added notifier <m,s>{}</> ({})",
+        notifier.kind,
+        format_events(&notifier.events)
+    ));
+    notifiers.notifiers.push(notifier);
+
+    save_notifiers(&notifiers, console)
+}
+
+pub(crate) fn notify_remove(index: usize, console: &crate::term::Terminal) -> ExitCode {
+    let mut notifiers = match load_notifiers(console) {
+        Some(notifiers) => notifiers,
+        None => return 1,
+    };
+
+    if index == 0 || index > notifiers.notifiers.len() {
+        console.error(&format!("Notifier #{} does not exist", index));
+        return 1;
+    }
+
+    let removed = notifiers.notifiers.remove(index - 1);
+    let exit_code = save_notifiers(&notifiers, console);
+    if exit_code == 0 {
+        console.info(&cformat!("Removed notifier <m,s>{}</>", removed.kind));
+    }
+
+    exit_code
+}
+
+pub(crate) fn notify_list(console: &crate::term::Terminal) -> ExitCode {
+    let notifiers = match load_notifiers(console) {
+        Some(notifiers) => notifiers,
+        None => return 1,
+    };
+
+    if notifiers.notifiers.is_empty() {
+        console.warn("No notifiers configured!");
+        return 0;
+    }
+
+    console.info(&format!("Found {} notifiers:", notifiers.notifiers.len()));
+    for (i, notifier) in notifiers.notifiers.iter().enumerate() {
+        console.info(&cformat!(
+            "{:02}. <m,s>{}</> ({})",
+            i + 1,
+            notifier.kind,
+            format_events(&notifier.events)
+        ));
+    }
+
+    0
+}
+
+pub(crate) async fn notify_test(console: &crate::term::Terminal) -> ExitCode {
+    let notifiers = match load_notifiers(console) {
+        Some(notifiers) => notifiers,
+        None => return 1,
+    };
+
+    if notifiers.notifiers.is_empty() {
+        console.warn("No notifiers configured!");
+        return 1;
+    }
+
+    let event = NotifyEvent::new(EventKind::ChapterDownloaded, "TEST")
+        .with_title("airpope")
+        .with_chapter("Test notification", "0")
+        .with_message("This is a test notification from airpope");
+
+    let mut exit_code = 0;
+    for notifier in notifiers.notifiers.iter() {
+        match send_to(&notifier.kind, &event).await {
+            Ok(_) => console.info(&cformat!("Sent test event to <m,s>{}</>", notifier.kind)),
+            Err(err) => {
+                console.error(&format!("Failed to notify {}: {}", notifier.kind, err));
+                exit_code = 1;
+            }
+        }
+    }
+
+    exit_code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notifiers_deserialize() {
+        let content = r#"{
+            "notifiers": [
+                {"kind": "webhook", "url": "https://example.com/hook", "events": ["auth-failed"]},
+                {"kind": "command", "command": "echo $AIRPOPE_TITLE"},
+                {"kind": "desktop"}
+            ]
+        }"#;
+
+        let notifiers: Notifiers = serde_json::from_str(content).unwrap();
+        assert_eq!(notifiers.notifiers.len(), 3);
+        assert_eq!(
+            notifiers.notifiers[0].kind,
+            NotifierKind::Webhook {
+                url: "https://example.com/hook".to_string()
+            }
+        );
+        assert!(notifiers.notifiers[0].accepts(EventKind::AuthFailed));
+        assert!(!notifiers.notifiers[0].accepts(EventKind::PurchaseMade));
+        assert!(notifiers.notifiers[1].accepts(EventKind::PurchaseMade));
+        assert_eq!(notifiers.notifiers[2].kind, NotifierKind::Desktop);
+    }
+}
@@ -0,0 +1,308 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::r#impl::common::shell_command;
+
+use super::config::{NotifierKind, Notifiers, NOTIFIERS_FILE};
+
+lazy_static::lazy_static! {
+    // loaded once, a running daemon needs a restart to pick up changes
+    static ref NOTIFIERS: Notifiers = load_notifiers();
+}
+
+/// Load the notifiers, a malformed file is reported and disables the notifications.
+fn load_notifiers() -> Notifiers {
+    match Notifiers::load(&Notifiers::default_path()) {
+        Ok(notifiers) => notifiers,
+        Err(err) => {
+            crate::term::get_console(0)
+                .warn(&format!("Failed to read {}: {}", NOTIFIERS_FILE, err));
+            Notifiers::default()
+        }
+    }
+}
+
+/// How long a webhook or a command may take before it's abandoned.
+const NOTIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum EventKind {
+    /// A chapter finished downloading
+    ChapterDownloaded,
+    /// A chapter got purchased
+    PurchaseMade,
+    /// The account session is invalid or expired
+    AuthFailed,
+    /// A chapter failed to download
+    DownloadError,
+}
+
+impl EventKind {
+    pub(crate) fn to_name(self) -> &'static str {
+        match self {
+            EventKind::ChapterDownloaded => "chapter-downloaded",
+            EventKind::PurchaseMade => "purchase-made",
+            EventKind::AuthFailed => "auth-failed",
+            EventKind::DownloadError => "download-error",
+        }
+    }
+}
+
+/// An event sent to the notifiers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NotifyEvent {
+    pub(crate) event: EventKind,
+    /// The source name, ex: `KM`
+    pub(crate) source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) chapter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) chapter_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) cost: Option<u64>,
+    /// The currency of the cost, ex: `point`, `coin` or `ticket`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) message: Option<String>,
+    pub(crate) timestamp: String,
+}
+
+impl NotifyEvent {
+    pub(crate) fn new(event: EventKind, source: &str) -> Self {
+        Self {
+            event,
+            source: source.to_string(),
+            title: None,
+            chapter: None,
+            chapter_id: None,
+            cost: None,
+            currency: None,
+            message: None,
+            timestamp: chrono::Local::now().to_rfc3339(),
+        }
+    }
+
+    pub(crate) fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub(crate) fn with_chapter(mut self, chapter: &str, chapter_id: impl ToString) -> Self {
+        self.chapter = Some(chapter.to_string());
+        self.chapter_id = Some(chapter_id.to_string());
+        self
+    }
+
+    pub(crate) fn with_cost(mut self, cost: u64, currency: &str) -> Self {
+        self.cost = Some(cost);
+        self.currency = Some(currency.to_string());
+        self
+    }
+
+    pub(crate) fn with_message(mut self, message: impl ToString) -> Self {
+        self.message = Some(message.to_string());
+        self
+    }
+
+    /// A short one line summary, used for the desktop notification.
+    pub(crate) fn summary(&self) -> String {
+        let title = self.title.as_deref().unwrap_or("Unknown title");
+        let chapter = self.chapter.as_deref().unwrap_or("Unknown chapter");
+        match self.event {
+            EventKind::ChapterDownloaded => format!("Downloaded {} of {}", chapter, title),
+            EventKind::PurchaseMade => match (self.cost, &self.currency) {
+                (Some(cost), Some(currency)) => format!(
+                    "Purchased {} of {} for {} {}",
+                    chapter, title, cost, currency
+                ),
+                _ => format!("Purchased {} of {}", chapter, title),
+            },
+            EventKind::AuthFailed => "Account authentication failed".to_string(),
+            EventKind::DownloadError => match &self.chapter {
+                Some(chapter) => format!("Failed to download {} of {}", chapter, title),
+                None => format!("Failed to download {}", title),
+            },
+        }
+    }
+
+    /// The event as `AIRPOPE_*` environment variables.
+    pub(crate) fn to_env(&self) -> Vec<(&'static str, String)> {
+        let mut envs = vec![
+            ("AIRPOPE_EVENT", self.event.to_name().to_string()),
+            ("AIRPOPE_SOURCE", self.source.clone()),
+            ("AIRPOPE_SUMMARY", self.summary()),
+            ("AIRPOPE_TIMESTAMP", self.timestamp.clone()),
+            (
+                "AIRPOPE_EVENT_JSON",
+                serde_json::to_string(self).unwrap_or_default(),
+            ),
+        ];
+
+        let optionals = [
+            ("AIRPOPE_TITLE", self.title.clone()),
+            ("AIRPOPE_CHAPTER", self.chapter.clone()),
+            ("AIRPOPE_CHAPTER_ID", self.chapter_id.clone()),
+            ("AIRPOPE_COST", self.cost.map(|cost| cost.to_string())),
+            ("AIRPOPE_CURRENCY", self.currency.clone()),
+            ("AIRPOPE_MESSAGE", self.message.clone()),
+        ];
+        for (key, value) in optionals {
+            if let Some(value) = value {
+                envs.push((key, value));
+            }
+        }
+
+        envs
+    }
+}
+
+async fn send_webhook(url: &str, event: &NotifyEvent) -> anyhow::Result<()> {
+    let client = reqwest::Client::builder().timeout(NOTIFY_TIMEOUT).build()?;
+
+    client
+        .post(url)
+        .json(event)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn run_command(command: &str, event: &NotifyEvent) -> anyhow::Result<()> {
+    let mut cmd = shell_command(command);
+    cmd.envs(event.to_env())
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true);
+
+    let status = tokio::time::timeout(NOTIFY_TIMEOUT, cmd.status())
+        .await
+        .map_err(|_| anyhow::anyhow!("command timed out"))??;
+    if !status.success() {
+        anyhow::bail!("command exited with {}", status);
+    }
+
+    Ok(())
+}
+
+async fn show_desktop(event: &NotifyEvent) -> anyhow::Result<()> {
+    let heading = format!("airpope ({})", event.source);
+    let body = event.summary();
+
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        let mut cmd = tokio::process::Command::new("osascript");
+        cmd.arg("-e").arg(format!(
+            "display notification {:?} with title {:?}",
+            body, heading
+        ));
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let script = format!(
+            "[reflection.assembly]::loadwithpartialname('System.Windows.Forms') | Out-Null; \
+            $n = New-Object System.Windows.Forms.NotifyIcon; \
+            $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+            $n.ShowBalloonTip(5000, '{}', '{}', 'Info')",
+            heading.replace('\'', "''"),
+            body.replace('\'', "''")
+        );
+        let mut cmd = tokio::process::Command::new("powershell");
+        cmd.arg("-NoProfile").arg("-Command").arg(script);
+        cmd
+    };
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let mut cmd = {
+        let mut cmd = tokio::process::Command::new("notify-send");
+        cmd.arg("--app-name=airpope").arg(heading).arg(body);
+        cmd
+    };
+
+    let status = tokio::time::timeout(NOTIFY_TIMEOUT, cmd.status())
+        .await
+        .map_err(|_| anyhow::anyhow!("desktop notification timed out"))??;
+    if !status.success() {
+        anyhow::bail!("desktop notification exited with {}", status);
+    }
+
+    Ok(())
+}
+
+/// Send the event to a single notifier.
+pub(crate) async fn send_to(kind: &NotifierKind, event: &NotifyEvent) -> anyhow::Result<()> {
+    match kind {
+        NotifierKind::Webhook { url } => send_webhook(url, event).await,
+        NotifierKind::Command { command } => run_command(command, event).await,
+        NotifierKind::Desktop => show_desktop(event).await,
+    }
+}
+
+/// Fire the event to every configured notifier that accepts it.
+///
+/// When a webhook or a command fails, a desktop notification is shown instead
+/// so the event is not lost silently.
+pub(crate) async fn notify(event: NotifyEvent, console: &crate::term::Terminal) {
+    let notifiers: Vec<_> = NOTIFIERS
+        .notifiers
+        .iter()
+        .filter(|notifier| notifier.accepts(event.event))
+        .collect();
+    if notifiers.is_empty() {
+        return;
+    }
+
+    let mut desktop_shown = false;
+    let mut need_fallback = false;
+    for notifier in notifiers {
+        if let Err(err) = send_to(&notifier.kind, &event).await {
+            console.warn(&format!("Failed to notify {}: {}", notifier.kind, err));
+            if notifier.kind != NotifierKind::Desktop {
+                need_fallback = true;
+            }
+        } else if notifier.kind == NotifierKind::Desktop {
+            desktop_shown = true;
+        }
+    }
+
+    if need_fallback && !desktop_shown {
+        show_desktop(&event).await.unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_payload() {
+        let event = NotifyEvent::new(EventKind::PurchaseMade, "KM")
+            .with_title("Some Title")
+            .with_chapter("Chapter 1", 100)
+            .with_cost(30, "point");
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "purchase-made");
+        assert_eq!(json["chapterId"], "100");
+        assert_eq!(json["cost"], 30);
+        assert!(json.get("message").is_none());
+
+        let envs = event.to_env();
+        let get_env = |key: &str| {
+            envs.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(get_env("AIRPOPE_COST"), Some("30"));
+        assert_eq!(get_env("AIRPOPE_TITLE"), Some("Some Title"));
+        assert_eq!(get_env("AIRPOPE_MESSAGE"), None);
+        assert_eq!(
+            get_env("AIRPOPE_SUMMARY"),
+            Some("Purchased Chapter 1 of Some Title for 30 point")
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use clap::Subcommand;
+
+pub(crate) mod config;
+pub(crate) mod dispatch;
+
+pub(crate) use dispatch::{notify, EventKind, NotifyEvent};
+
+#[derive(Subcommand)]
+pub(crate) enum NotifyCommands {
+    /// Add a new notifier
+    Add {
+        /// The kind of notifier
+        #[arg(value_enum)]
+        kind: config::NotifierKindCli,
+        /// The webhook URL or the command to run
+        target: Option<String>,
+        /// Only fire on the given events, default to every event
+        #[arg(short = 'e', long = "event", value_enum)]
+        events: Vec<EventKind>,
+    },
+    /// Remove a notifier by its number in the list
+    Remove {
+        /// The notifier number, see `notify list`
+        index: usize,
+    },
+    /// List all the configured notifiers
+    List,
+    /// Send a test event to every configured notifier
+    Test,
+}
@@ -130,6 +130,15 @@ impl FromStr for NumberOrString {
     }
 }
 
+/// The value of a `--flag` and `--no-flag` pair, `None` when neither is given.
+pub(crate) fn flag_pair(enable: bool, disable: bool) -> Option<bool> {
+    match (enable, disable) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
 /// Value parser for comma separated numbers
 pub(super) fn parse_comma_number(s: &str) -> Result<CommaSeparatedNumber, String> {
     let mut numbers = Vec::new();
@@ -175,4 +184,11 @@ mod tests {
         let parsed = parse_comma_number("aaa,bbb");
         assert!(parsed.is_err());
     }
+
+    #[test]
+    fn test_flag_pair() {
+        assert_eq!(flag_pair(true, false), Some(true));
+        assert_eq!(flag_pair(false, true), Some(false));
+        assert_eq!(flag_pair(false, false), None);
+    }
 }
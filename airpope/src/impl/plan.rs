@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+
+use color_print::cformat;
+
+/// What a download would do with a chapter, used by `--dry-run`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PlanAction {
+    /// Already purchased or rented, downloaded as is.
+    Owned,
+    /// Free to read, downloaded as is.
+    Free,
+    /// Readable with the active subscription or premium account.
+    Subscription,
+    /// Claimed with a ticket, ex: `title ticket`
+    Ticket(String),
+    /// Purchased with the given amount of each currency, ex: `(free coin, 10)`
+    Purchase(Vec<(String, u64)>),
+    /// Not downloaded, with the reason.
+    Skip(String),
+}
+
+impl PlanAction {
+    /// Whether the chapter would be downloaded.
+    pub(crate) fn is_download(&self) -> bool {
+        !matches!(self, PlanAction::Skip(_))
+    }
+}
+
+/// A single chapter of the plan.
+#[derive(Debug, Clone)]
+struct PlanEntry {
+    title: String,
+    id: String,
+    action: PlanAction,
+}
+
+/// The chapters a download would process, without purchasing or downloading anything.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DownloadPlan {
+    entries: Vec<PlanEntry>,
+}
+
+impl DownloadPlan {
+    pub(crate) fn add(&mut self, title: impl ToString, id: impl ToString, action: PlanAction) {
+        self.entries.push(PlanEntry {
+            title: title.to_string(),
+            id: id.to_string(),
+            action,
+        });
+    }
+
+    /// The total amount of each currency that would be spent.
+    pub(crate) fn spending(&self) -> BTreeMap<String, u64> {
+        let mut spending: BTreeMap<String, u64> = BTreeMap::new();
+        for entry in self.entries.iter() {
+            match &entry.action {
+                PlanAction::Purchase(costs) => {
+                    for (currency, amount) in costs.iter().filter(|(_, amount)| *amount > 0) {
+                        *spending.entry(currency.clone()).or_default() += amount;
+                    }
+                }
+                PlanAction::Ticket(ticket) => {
+                    *spending.entry(ticket.clone()).or_default() += 1;
+                }
+                _ => {}
+            }
+        }
+
+        spending
+    }
+
+    fn count(&self, f: impl Fn(&PlanAction) -> bool) -> usize {
+        self.entries.iter().filter(|entry| f(&entry.action)).count()
+    }
+
+    pub(crate) fn print(&self, console: &crate::term::Terminal) {
+        console.info(&cformat!(
+            "<s>Dry run</>, nothing will be purchased or downloaded:"
+        ));
+        for entry in self.entries.iter() {
+            let action = match &entry.action {
+                PlanAction::Owned => cformat!("<g,s>owned</>"),
+                PlanAction::Free => cformat!("<g,s>free</>"),
+                PlanAction::Subscription => cformat!("<g,s>subscription</>"),
+                PlanAction::Ticket(ticket) => cformat!("<c,s>{}</>", ticket),
+                PlanAction::Purchase(costs) => {
+                    cformat!("<y,s>purchase</> ({})", format_costs(costs))
+                }
+                PlanAction::Skip(reason) => cformat!("<r,s>skip</> ({})", reason),
+            };
+            console.info(&cformat!(
+                "  <m,s>{}</> ({}): {}",
+                entry.title,
+                entry.id,
+                action
+            ));
+        }
+
+        let download = self.count(|action| action.is_download());
+        console.info(&cformat!(
+            "Would download <m,s>{}</> of {} chapters: {} owned, {} free, {} subscription, {} ticket, {} purchase",
+            download,
+            self.entries.len(),
+            self.count(|action| *action == PlanAction::Owned),
+            self.count(|action| *action == PlanAction::Free),
+            self.count(|action| *action == PlanAction::Subscription),
+            self.count(|action| matches!(action, PlanAction::Ticket(_))),
+            self.count(|action| matches!(action, PlanAction::Purchase(_)))
+        ));
+
+        let spending = self.spending();
+        if spending.is_empty() {
+            console.info("Would spend nothing");
+        } else {
+            let spending: Vec<(String, u64)> = spending.into_iter().collect();
+            console.info(&cformat!("Would spend <s>{}</>", format_costs(&spending)));
+        }
+    }
+}
+
+fn format_costs(costs: &[(String, u64)]) -> String {
+    let costs: Vec<String> = costs
+        .iter()
+        .filter(|(_, amount)| *amount > 0)
+        .map(|(currency, amount)| format!("{} {}", amount, currency))
+        .collect();
+
+    if costs.is_empty() {
+        "nothing".to_string()
+    } else {
+        costs.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_spending() {
+        let mut plan = DownloadPlan::default();
+        plan.add("Chapter 1", 1, PlanAction::Free);
+        plan.add(
+            "Chapter 2",
+            2,
+            PlanAction::Ticket("title ticket".to_string()),
+        );
+        plan.add(
+            "Chapter 3",
+            3,
+            PlanAction::Purchase(vec![
+                ("free coin".to_string(), 10),
+                ("paid coin".to_string(), 0),
+            ]),
+        );
+        plan.add(
+            "Chapter 4",
+            4,
+            PlanAction::Purchase(vec![("free coin".to_string(), 5)]),
+        );
+        plan.add(
+            "Chapter 5",
+            5,
+            PlanAction::Skip("not enough coin".to_string()),
+        );
+
+        let spending = plan.spending();
+        assert_eq!(spending.len(), 2);
+        assert_eq!(spending["free coin"], 15);
+        assert_eq!(spending["title ticket"], 1);
+        assert_eq!(plan.count(|action| action.is_download()), 4);
+        assert_eq!(
+            format_costs(&[("paid coin".to_string(), 0)]),
+            "nothing".to_string()
+        );
+    }
+}
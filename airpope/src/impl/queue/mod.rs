@@ -0,0 +1,26 @@
+use clap::Subcommand;
+
+pub(crate) mod runner;
+pub(crate) mod store;
+
+pub(crate) use store::QueueTracker;
+
+#[derive(Subcommand)]
+pub(crate) enum QueueCommands {
+    /// List the queued downloads and their chapters status
+    List {
+        /// Also list every chapter that is not downloaded yet
+        #[arg(short = 'c', long = "chapters")]
+        chapters: bool,
+    },
+    /// Continue every interrupted download where it stopped
+    Resume,
+    /// Download the failed chapters of every queued download again
+    RetryFailed,
+    /// Remove the finished downloads from the queue
+    Clear {
+        /// Remove every download, including the unfinished ones
+        #[arg(long = "all")]
+        all: bool,
+    },
+}
@@ -0,0 +1,212 @@
+use color_print::cformat;
+
+use crate::{
+    cli::ExitCode,
+    r#impl::{batch::runner::run_batch_job, config::proxy::ProxyRouter},
+};
+
+use super::{
+    store::{ChapterStatus, DownloadQueue, QueueJob},
+    QueueTracker,
+};
+
+fn load_queue(console: &crate::term::Terminal) -> Option<DownloadQueue> {
+    match DownloadQueue::load(&DownloadQueue::default_path()) {
+        Ok(queue) => Some(queue),
+        Err(err) => {
+            console.error(&format!("Failed to read the download queue: {}", err));
+            None
+        }
+    }
+}
+
+fn job_summary(job: &QueueJob) -> String {
+    let mut parts = vec![format!(
+        "{}/{} done",
+        job.count(ChapterStatus::Done),
+        job.chapters.len()
+    )];
+    let remaining = job.remaining().len();
+    if remaining > 0 {
+        parts.push(format!("{} remaining", remaining));
+    }
+    let failed = job.count(ChapterStatus::Failed);
+    if failed > 0 {
+        parts.push(format!("{} failed", failed));
+    }
+
+    parts.join(", ")
+}
+
+pub(crate) fn queue_list(chapters: bool, console: &crate::term::Terminal) -> ExitCode {
+    let queue = match load_queue(console) {
+        Some(queue) => queue,
+        None => return 1,
+    };
+
+    if queue.jobs.is_empty() {
+        console.warn("The download queue is empty!");
+        return 0;
+    }
+
+    console.info(&cformat!(
+        "Found <m,s>{}</> queued downloads:",
+        queue.jobs.len()
+    ));
+    for job in queue.jobs.iter() {
+        console.info(&cformat!(
+            "  <s>#{}</> <m,s>{}</> {} (<s>{}</>): {}",
+            job.id,
+            job.job.source.source_name(),
+            job.title,
+            job.job.title_id,
+            job_summary(job)
+        ));
+        if let Some(account) = &job.job.account {
+            console.info(&format!("    Account: {}", account));
+        }
+        console.info(&format!("    Updated: {}", job.updated_at));
+
+        for chapter in job.chapters.iter() {
+            match chapter.status {
+                ChapterStatus::Failed => console.info(&cformat!(
+                    "    - <r,s>Failed</> {} ({}): {}",
+                    chapter.title,
+                    chapter.id,
+                    chapter.error.as_deref().unwrap_or("unknown error")
+                )),
+                ChapterStatus::Pending | ChapterStatus::InProgress if chapters => console.info(
+                    &cformat!("    - <y,s>Pending</> {} ({})", chapter.title, chapter.id),
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    0
+}
+
+async fn run_queued_jobs(
+    job_ids: Vec<u64>,
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    let queue = match load_queue(console) {
+        Some(queue) => queue,
+        None => return 1,
+    };
+
+    let jobs: Vec<&QueueJob> = queue
+        .jobs
+        .iter()
+        .filter(|job| job_ids.contains(&job.id))
+        .collect();
+    if jobs.is_empty() {
+        console.info("Nothing to resume, every queued download is finished");
+        return 0;
+    }
+
+    let total = jobs.len();
+    let mut failed = 0;
+    for (i, job) in jobs.into_iter().enumerate() {
+        console.info(&cformat!(
+            "[{}/{}] Resuming <m,s>{}</> {} with <s>{}</> chapters...",
+            i + 1,
+            total,
+            job.job.source.source_name(),
+            job.title,
+            job.remaining().len()
+        ));
+
+        let queue_tracker = QueueTracker::resume(job);
+        if run_batch_job(&job.job, queue_tracker, proxies, console).await != 0 {
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        console.warn(&format!(
+            "{} of {} downloads did not finish successfully",
+            failed, total
+        ));
+        return 1;
+    }
+
+    console.info(&format!("Resumed {} downloads", total));
+    0
+}
+
+pub(crate) async fn queue_resume(
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    let queue = match load_queue(console) {
+        Some(queue) => queue,
+        None => return 1,
+    };
+
+    let job_ids = queue
+        .jobs
+        .iter()
+        .filter(|job| !job.remaining().is_empty())
+        .map(|job| job.id)
+        .collect();
+    run_queued_jobs(job_ids, proxies, console).await
+}
+
+pub(crate) async fn queue_retry_failed(
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    let result = DownloadQueue::update(|queue| {
+        let mut job_ids = vec![];
+        for job in queue.jobs.iter_mut() {
+            let mut has_failed = false;
+            for chapter in job.chapters.iter_mut() {
+                if chapter.status == ChapterStatus::Failed {
+                    chapter.status = ChapterStatus::Pending;
+                    chapter.error = None;
+                    has_failed = true;
+                }
+            }
+            if has_failed {
+                job_ids.push(job.id);
+            }
+        }
+        job_ids
+    });
+
+    match result {
+        Ok(job_ids) if job_ids.is_empty() => {
+            console.info("No failed chapters in the download queue");
+            0
+        }
+        Ok(job_ids) => run_queued_jobs(job_ids, proxies, console).await,
+        Err(err) => {
+            console.error(&format!("Failed to update the download queue: {}", err));
+            1
+        }
+    }
+}
+
+pub(crate) fn queue_clear(all: bool, console: &crate::term::Terminal) -> ExitCode {
+    let result = DownloadQueue::update(|queue| {
+        let before = queue.jobs.len();
+        queue.jobs.retain(|job| !all && !job.is_finished());
+        before - queue.jobs.len()
+    });
+
+    match result {
+        Ok(removed) => {
+            console.info(&cformat!(
+                "Removed <m,s>{}</> downloads from the queue",
+                removed
+            ));
+            0
+        }
+        Err(err) => {
+            console.error(&format!("Failed to update the download queue: {}", err));
+            1
+        }
+    }
+}
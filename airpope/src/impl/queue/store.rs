@@ -0,0 +1,385 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::get_user_path, r#impl::batch::manifest::BatchJob};
+
+/// The queue file name inside the config directory.
+pub(crate) const QUEUE_FILE: &str = "queue.json";
+
+/// Serialize the read-modify-write of the queue inside this process.
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ChapterStatus {
+    Pending,
+    InProgress,
+    Done,
+    Failed,
+}
+
+impl ChapterStatus {
+    /// Whether the chapter still need to be downloaded by `queue resume`.
+    pub(crate) fn is_remaining(self) -> bool {
+        matches!(self, ChapterStatus::Pending | ChapterStatus::InProgress)
+    }
+}
+
+/// A single chapter of a queued download.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QueueChapter {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) status: ChapterStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+/// A download of a single title, with the options needed to run it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QueueJob {
+    pub(crate) id: u64,
+    /// The title name, only used for display.
+    pub(crate) title: String,
+    pub(crate) created_at: String,
+    pub(crate) updated_at: String,
+    pub(crate) job: BatchJob,
+    pub(crate) chapters: Vec<QueueChapter>,
+}
+
+impl QueueJob {
+    pub(crate) fn count(&self, status: ChapterStatus) -> usize {
+        self.chapters
+            .iter()
+            .filter(|ch| ch.status == status)
+            .count()
+    }
+
+    pub(crate) fn remaining(&self) -> Vec<String> {
+        self.chapters
+            .iter()
+            .filter(|ch| ch.status.is_remaining())
+            .map(|ch| ch.id.clone())
+            .collect()
+    }
+
+    /// Every chapter is downloaded.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.chapters
+            .iter()
+            .all(|ch| ch.status == ChapterStatus::Done)
+    }
+
+    fn is_same_title(&self, job: &BatchJob) -> bool {
+        self.job.source == job.source
+            && self.job.title_id == job.title_id
+            && self.job.account == job.account
+    }
+}
+
+/// The download queue, stored as `queue.json` in the config directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadQueue {
+    /// The last job ID given, never reused even after a job is removed.
+    #[serde(default)]
+    last_id: u64,
+    pub(crate) jobs: Vec<QueueJob>,
+}
+
+impl DownloadQueue {
+    pub(crate) fn default_path() -> PathBuf {
+        get_user_path().join(QUEUE_FILE)
+    }
+
+    /// Load the queue, a missing file is an empty queue.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let queue: DownloadQueue = serde_json::from_str(&content)?;
+        Ok(queue)
+    }
+
+    /// Save the queue through a temporary file so a crash never leaves it half written.
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, content)?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Load, modify and save the default queue while holding the lock.
+    pub(crate) fn update<T>(f: impl FnOnce(&mut DownloadQueue) -> T) -> anyhow::Result<T> {
+        let _guard = QUEUE_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let path = Self::default_path();
+        let mut queue = Self::load(&path)?;
+        let result = f(&mut queue);
+        queue.save(&path)?;
+        Ok(result)
+    }
+
+    pub(crate) fn get_mut(&mut self, job_id: u64) -> Option<&mut QueueJob> {
+        self.jobs.iter_mut().find(|job| job.id == job_id)
+    }
+
+    /// Add a new job, finished jobs of the same title are replaced.
+    fn push(&mut self, job: BatchJob, title: &str, chapters: Vec<QueueChapter>) -> u64 {
+        self.jobs
+            .retain(|queued| !(queued.is_finished() && queued.is_same_title(&job)));
+
+        self.last_id = self
+            .jobs
+            .iter()
+            .map(|job| job.id)
+            .fold(self.last_id, u64::max)
+            + 1;
+        let id = self.last_id;
+        let now = chrono::Local::now().to_rfc3339();
+        self.jobs.push(QueueJob {
+            id,
+            title: title.to_string(),
+            created_at: now.clone(),
+            updated_at: now,
+            job,
+            chapters,
+        });
+        id
+    }
+
+    /// Mark the chapters of a resumed job as pending again.
+    ///
+    /// The remaining chapters that are no longer downloadable are marked as failed.
+    fn restart(&mut self, job_id: u64, chapter_ids: &[String]) {
+        if let Some(job) = self.get_mut(job_id) {
+            for chapter in job.chapters.iter_mut() {
+                if chapter_ids.contains(&chapter.id) {
+                    chapter.status = ChapterStatus::Pending;
+                    chapter.error = None;
+                } else if chapter.status.is_remaining() {
+                    chapter.status = ChapterStatus::Failed;
+                    chapter.error = Some("no longer available to download".to_string());
+                }
+            }
+            job.updated_at = chrono::Local::now().to_rfc3339();
+        }
+    }
+
+    fn set_status(
+        &mut self,
+        job_id: u64,
+        chapter_id: &str,
+        status: ChapterStatus,
+        error: Option<String>,
+    ) {
+        if let Some(job) = self.get_mut(job_id) {
+            if let Some(chapter) = job.chapters.iter_mut().find(|ch| ch.id == chapter_id) {
+                chapter.status = status;
+                chapter.error = error;
+            }
+            job.updated_at = chrono::Local::now().to_rfc3339();
+        }
+    }
+}
+
+/// The queued job being resumed.
+#[derive(Debug, Clone)]
+struct QueueResume {
+    job_id: u64,
+    chapter_ids: Vec<String>,
+}
+
+/// How a download is recorded in the queue, part of every download config.
+///
+/// By default a new job is added, see [`QueueTracker::resume`] to continue a queued job.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct QueueTracker {
+    resume: Option<QueueResume>,
+}
+
+impl QueueTracker {
+    pub(crate) fn resume(job: &QueueJob) -> Self {
+        Self {
+            resume: Some(QueueResume {
+                job_id: job.id,
+                chapter_ids: job.remaining(),
+            }),
+        }
+    }
+
+    /// Only the remaining chapters are accepted when resuming a job.
+    pub(crate) fn accepts(&self, chapter_id: impl ToString) -> bool {
+        match &self.resume {
+            Some(resume) => resume.chapter_ids.contains(&chapter_id.to_string()),
+            None => true,
+        }
+    }
+
+    /// Record the chapters that are about to be downloaded.
+    ///
+    /// `chapters` is a list of chapter ID and title. A failure only warns, the download continues.
+    pub(crate) fn start(
+        &self,
+        mut job: BatchJob,
+        title: &str,
+        chapters: Vec<(String, String)>,
+        console: &crate::term::Terminal,
+    ) -> QueueSession {
+        // store absolute path since `queue resume` can be called from anywhere
+        job.output = job.output.map(|output| {
+            if output.is_relative() {
+                std::env::current_dir()
+                    .map(|cwd| cwd.join(&output))
+                    .unwrap_or(output)
+            } else {
+                output
+            }
+        });
+
+        let result = DownloadQueue::update(|queue| match &self.resume {
+            Some(resume) => {
+                let chapter_ids: Vec<String> = chapters.into_iter().map(|(id, _)| id).collect();
+                queue.restart(resume.job_id, &chapter_ids);
+                resume.job_id
+            }
+            None => {
+                let chapters = chapters
+                    .into_iter()
+                    .map(|(id, title)| QueueChapter {
+                        id,
+                        title,
+                        status: ChapterStatus::Pending,
+                        error: None,
+                    })
+                    .collect();
+                queue.push(job, title, chapters)
+            }
+        });
+
+        match result {
+            Ok(job_id) => QueueSession {
+                job_id: Some(job_id),
+            },
+            Err(err) => {
+                console.warn(&format!("Failed to record the download queue: {}", err));
+                QueueSession { job_id: None }
+            }
+        }
+    }
+}
+
+/// The queued job of a running download.
+pub(crate) struct QueueSession {
+    job_id: Option<u64>,
+}
+
+impl QueueSession {
+    fn set(
+        &self,
+        chapter_id: impl ToString,
+        status: ChapterStatus,
+        error: Option<String>,
+        console: &crate::term::Terminal,
+    ) {
+        let job_id = match self.job_id {
+            Some(job_id) => job_id,
+            None => return,
+        };
+
+        let chapter_id = chapter_id.to_string();
+        if let Err(err) = DownloadQueue::update(|queue| {
+            queue.set_status(job_id, &chapter_id, status, error);
+        }) {
+            console.warn(&format!("   Failed to update the download queue: {}", err));
+        }
+    }
+
+    pub(crate) fn in_progress(&self, chapter_id: impl ToString, console: &crate::term::Terminal) {
+        self.set(chapter_id, ChapterStatus::InProgress, None, console);
+    }
+
+    pub(crate) fn done(&self, chapter_id: impl ToString, console: &crate::term::Terminal) {
+        self.set(chapter_id, ChapterStatus::Done, None, console);
+    }
+
+    /// Put the chapter back to pending since a shutdown interrupted it.
+    pub(crate) fn interrupted(&self, chapter_id: impl ToString, console: &crate::term::Terminal) {
+        self.set(chapter_id, ChapterStatus::Pending, None, console);
+    }
+
+    pub(crate) fn failed(
+        &self,
+        chapter_id: impl ToString,
+        error: impl ToString,
+        console: &crate::term::Terminal,
+    ) {
+        self.set(
+            chapter_id,
+            ChapterStatus::Failed,
+            Some(error.to_string()),
+            console,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#impl::Implementations;
+
+    fn chapter(id: &str, status: ChapterStatus) -> QueueChapter {
+        QueueChapter {
+            id: id.to_string(),
+            title: format!("Chapter {}", id),
+            status,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_queue_resume() {
+        let job = BatchJob::new(Implementations::Musq, "10");
+        let mut queue = DownloadQueue::default();
+        let first = queue.push(
+            job.clone(),
+            "Title",
+            vec![chapter("1", ChapterStatus::Done)],
+        );
+        let second = queue.push(
+            job.clone(),
+            "Title",
+            vec![
+                chapter("1", ChapterStatus::Done),
+                chapter("2", ChapterStatus::InProgress),
+                chapter("3", ChapterStatus::Pending),
+                chapter("4", ChapterStatus::Failed),
+            ],
+        );
+        // the finished job of the same title got replaced
+        assert_eq!(queue.jobs.len(), 1);
+        assert_eq!(second, first + 1);
+
+        let tracker = QueueTracker::resume(&queue.jobs[0]);
+        assert!(tracker.accepts(2) && tracker.accepts(3));
+        assert!(!tracker.accepts(1) && !tracker.accepts(4));
+
+        // chapter 3 is not available anymore
+        queue.restart(second, &["2".to_string()]);
+        let job = queue.get_mut(second).unwrap();
+        assert_eq!(job.remaining(), vec!["2".to_string()]);
+        assert_eq!(job.count(ChapterStatus::Failed), 2);
+    }
+}
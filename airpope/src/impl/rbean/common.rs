@@ -59,12 +59,15 @@ pub(super) fn do_print_search_information(
     }
 }
 
-pub(super) fn save_session_config(client: &RBClient, config: &Config) {
-    let mut config = config.clone();
-    config.access_token = client.get_token().to_string();
+/// Save the refreshed token and its expiry back into the config, if they changed.
+pub(crate) fn save_session_config(client: &RBClient, config: &Config) {
+    let mut new_config = config.clone();
+    new_config.access_token = client.get_config().token.clone();
     if let Some(expiry_at) = client.get_expiry_at() {
-        config.expiry = expiry_at;
+        new_config.expiry = expiry_at;
     }
 
-    save_config(config.into(), None);
+    if &new_config != config {
+        save_config(new_config.into(), None);
+    }
 }
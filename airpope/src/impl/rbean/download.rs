@@ -14,8 +14,19 @@ use airpope_rbean::{
 use crate::{
     cli::ExitCode,
     r#impl::{
+        batch::manifest::BatchJob,
         clean_filename,
         models::{ChapterDetailDump, MangaDetailDump},
+        common::is_shutdown_requested,
+        hooks::PostHooks,
+        notify::{notify, EventKind, NotifyEvent},
+        accounts::check::account_error_event,
+        plan::{DownloadPlan, PlanAction},
+        queue::QueueTracker,
+        config::proxy::ProxyLease,
+        sync::report_title_sync,
+        tools::trim::trim_downloaded_chapter,
+        Implementations,
     },
     term::{ConsoleChoice, Terminal},
 };
@@ -68,6 +79,16 @@ pub(crate) struct RBDownloadConfigCli {
 
     /// Parallel download
     pub(crate) parallel: bool,
+    /// Trim uniform borders of the downloaded pages with the given tolerance.
+    pub(crate) trim: Option<u8>,
+    /// The commands to run after each chapter and the title are downloaded.
+    pub(crate) hooks: PostHooks,
+    /// How the download is recorded in the download queue.
+    pub(crate) queue: QueueTracker,
+    /// The proxy of the requests, to report its failures.
+    pub(crate) proxy: ProxyLease,
+    /// Only print the download plan without purchasing or downloading anything.
+    pub(crate) dry_run: bool,
 }
 
 fn check_downloaded_image_count(image_dir: &PathBuf, extension: &str) -> Option<usize> {
@@ -107,9 +128,11 @@ fn create_chapters_info(title: &Manga, chapters: Vec<Chapter>) -> MangaDetailDum
         .collect::<Vec<String>>();
 
     MangaDetailDump::new(title.title.clone(), creators.join(", "), dumped_chapters)
+        .with_source("RB")
+        .with_title_id(&title.uuid)
 }
 
-fn get_output_directory(
+pub(crate) fn get_output_directory(
     output_dir: &Path,
     title_id: String,
     chapter_id: Option<String>,
@@ -228,17 +251,18 @@ async fn rbean_actual_downloader(
     }
 
     match node.client.stream_download(&download_url.url, writer).await {
-        Ok(_) => {}
+        Ok(_) => {
+            progress.inc(1);
+            Ok(())
+        }
         Err(err) => {
-            console.error(&format!("    Failed to download image: {}", err));
             // silent delete the file
-            tokio::fs::remove_file(&img_dl_path).await?;
+            tokio::fs::remove_file(&img_dl_path)
+                .await
+                .unwrap_or_default();
+            Err(err)
         }
     }
-
-    progress.inc(1);
-
-    Ok(())
 }
 
 pub(crate) async fn rbean_download(
@@ -257,6 +281,12 @@ pub(crate) async fn rbean_download(
     let acc_info = client.get_user().await;
     if let Err(e) = acc_info {
         console.error(&format!("Failed to fetch user information: {}", e));
+        notify(
+            NotifyEvent::new(account_error_event(Implementations::Rbean, &e), "RB")
+                .with_message(format!("{} ({})", e, account.email)),
+            console,
+        )
+        .await;
         return 1;
     }
 
@@ -310,10 +340,31 @@ pub(crate) async fn rbean_download(
         do_chapter_select(chapters, &result, &acc_info, console)
     };
 
-    let download_chapters: Vec<&Chapter> = selected_chapters
+    let selected_chapters: Vec<&Chapter> = selected_chapters
         .iter()
         .filter(|&ch| dl_config.chapter_ids.is_empty() || dl_config.chapter_ids.contains(&ch.uuid))
+        .filter(|&ch| dl_config.queue.accepts(&ch.uuid))
         .filter(|&ch| ch.published.is_some())
+        .collect();
+
+    if dl_config.dry_run {
+        let mut plan = DownloadPlan::default();
+        for chapter in selected_chapters.iter() {
+            let action = if chapter.free_published.is_some() {
+                PlanAction::Free
+            } else if acc_info.is_premium {
+                PlanAction::Subscription
+            } else {
+                PlanAction::Skip("need premium".to_string())
+            };
+            plan.add(chapter.formatted_title(), &chapter.uuid, action);
+        }
+        plan.print(console);
+        return 0;
+    }
+
+    let download_chapters: Vec<&Chapter> = selected_chapters
+        .into_iter()
         .filter(|&ch| {
             // Download chapter if it's free or user is premium
             ch.free_published.is_some() || acc_info.is_premium
@@ -333,12 +384,38 @@ pub(crate) async fn rbean_download(
         .dump(&title_dump_path)
         .expect("Failed to dump title info");
 
+    let queue = dl_config.queue.start(
+        BatchJob {
+            account: Some(account.id.clone()),
+            format: dl_config
+                .format
+                .to_possible_value()
+                .map(|value| value.get_name().to_string()),
+            output: Some(output_dir.clone()),
+            trim: dl_config.trim,
+            parallel: Some(dl_config.parallel),
+            ..BatchJob::new(Implementations::Rbean, &result.uuid)
+        },
+        &result.title,
+        download_chapters
+            .iter()
+            .map(|ch| (ch.uuid.clone(), ch.formatted_title()))
+            .collect(),
+        console,
+    );
+
     for chapter in download_chapters {
+        if is_shutdown_requested() {
+            console.warn("Shutdown requested, skipping the remaining chapters");
+            break;
+        }
+
         console.info(&cformat!(
             "  Downloading chapter <m,s>{}</> ({})...",
             chapter.formatted_title(),
             chapter.uuid
         ));
+        queue.in_progress(&chapter.uuid, console);
 
         let image_dir = get_output_directory(
             &output_dir,
@@ -355,11 +432,21 @@ pub(crate) async fn rbean_download(
         let view_req = client.get_chapter_viewer(&chapter.uuid).await;
 
         if let Err(e) = view_req {
+            dl_config.proxy.report_failure(&e);
             console.error(&cformat!(
                 "Failed to fetch viewer for <m,s>{}</>: {}",
                 chapter.formatted_title(),
                 e
             ));
+            queue.failed(&chapter.uuid, &e, console);
+            notify(
+                NotifyEvent::new(EventKind::DownloadError, "RB")
+                    .with_title(&result.title)
+                    .with_chapter(&chapter.formatted_title(), &chapter.uuid)
+                    .with_message(e),
+                console,
+            )
+            .await;
             continue;
         }
 
@@ -373,6 +460,7 @@ pub(crate) async fn rbean_download(
                     chapter.formatted_title(),
                     chapter.uuid
                 ));
+                queue.done(&chapter.uuid, console);
                 continue;
             }
         }
@@ -396,7 +484,7 @@ pub(crate) async fn rbean_download(
 
         let pages_data = view_req.data.pages.clone();
 
-        if dl_config.parallel {
+        let failed_pages = if dl_config.parallel {
             let tasks: Vec<_> = pages_data
                 .iter()
                 .enumerate()
@@ -408,6 +496,7 @@ pub(crate) async fn rbean_download(
                     let cnsl = console.clone();
                     let dl_config = dl_config.clone();
                     let progress = Arc::clone(&progress);
+                    let proxy = dl_config.proxy.clone();
                     tokio::spawn(async move {
                         match rbean_actual_downloader(
                             DownloadNode {
@@ -423,18 +512,28 @@ pub(crate) async fn rbean_download(
                         )
                         .await
                         {
-                            Ok(_) => {}
+                            Ok(_) => true,
                             Err(e) => {
                                 cnsl.error(&format!("Failed to download image: {}", e));
+                                proxy.report_failure(&e);
+                                false
                             }
                         }
                     })
                 })
                 .collect();
 
-            futures::future::join_all(tasks).await;
+            futures::future::join_all(tasks)
+                .await
+                .into_iter()
+                .filter(|task| !matches!(task, Ok(true)))
+                .count()
         } else {
+            let mut failed_pages = 0;
             for (idx, page) in pages_data.iter().enumerate() {
+                if is_shutdown_requested() {
+                    break;
+                }
                 let node = DownloadNode {
                     client: client.clone(),
                     page: page.clone(),
@@ -454,11 +553,113 @@ pub(crate) async fn rbean_download(
                     Ok(_) => {}
                     Err(e) => {
                         console.error(&format!("Failed to download image: {}", e));
+                        failed_pages += 1;
+                        dl_config.proxy.report_failure(&e);
                     }
                 }
             }
-        }
+            failed_pages
+        };
         progress.finish_with_message("Downloaded");
+        if is_shutdown_requested() {
+            // the next chapter stops the download
+            queue.interrupted(&chapter.uuid, console);
+            continue;
+        }
+        if failed_pages > 0 {
+            console.warn(&cformat!(
+                "   Failed to download {} pages of chapter <m,s>{}</> (<s>{}</>)",
+                failed_pages,
+                chapter.formatted_title(),
+                chapter.uuid
+            ));
+            queue.failed(
+                &chapter.uuid,
+                format!("{} pages failed to download", failed_pages),
+                console,
+            );
+            continue;
+        }
+        if let Some(tolerance) = dl_config.trim {
+            trim_downloaded_chapter(&image_dir, Implementations::Rbean, tolerance, console).await;
+        }
+        queue.done(&chapter.uuid, console);
+        notify(
+            NotifyEvent::new(EventKind::ChapterDownloaded, "RB")
+                .with_title(&result.title)
+                .with_chapter(&chapter.formatted_title(), &chapter.uuid),
+            console,
+        )
+        .await;
+        dl_config
+            .hooks
+            .run_chapter(
+                "RB",
+                &result.uuid,
+                &title_dir,
+                &chapter.uuid,
+                &image_dir,
+                console,
+            )
+            .await;
+    }
+
+    if !is_shutdown_requested() {
+        dl_config
+            .hooks
+            .run_title("RB", &result.uuid, &title_dir, console)
+            .await;
+    }
+
+    0
+}
+
+/// Fetch the title and every chapters of it as [`MangaDetailDump`].
+pub(crate) async fn rbean_fetch_title_info(
+    uuid: &str,
+    client: &mut RBClient,
+    account: &Config,
+) -> anyhow::Result<MangaDetailDump> {
+    let result = client.get_manga(uuid).await?;
+    save_session_config(client, account);
+
+    let chapter_meta = client.get_chapter_list(uuid).await?;
+    save_session_config(client, account);
+
+    Ok(create_chapters_info(&result, chapter_meta.chapters))
+}
+
+/// Compare the remote chapter list of a title with the local downloads.
+///
+/// With `download`, every available chapter will be downloaded.
+pub(crate) async fn rbean_sync(
+    uuid: &str,
+    download: bool,
+    output_dir: PathBuf,
+    client: &mut RBClient,
+    account: &Config,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    console.info(&cformat!("Fetching chapters for ID <m,s>{}</>...", uuid));
+
+    let remote_info = match rbean_fetch_title_info(uuid, client, account).await {
+        Ok(remote_info) => remote_info,
+        Err(e) => {
+            console.error(&format!("Failed to fetch manga: {}", e));
+            return 1;
+        }
+    };
+
+    let title_dir = get_output_directory(&output_dir, uuid.to_string(), None, false);
+    let report = report_title_sync(&title_dir, &remote_info, console);
+
+    if download && report.has_downloadable() {
+        let dl_config = RBDownloadConfigCli {
+            no_input: true,
+            ..Default::default()
+        };
+
+        return rbean_download(uuid, dl_config, output_dir, client, account, console).await;
     }
 
     0
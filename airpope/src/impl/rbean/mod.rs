@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use clap::Subcommand;
 
 use super::parser::{parse_comma_string, CommaSeparatedString};
+use super::tools::trim::DEFAULT_TRIM_TOLERANCE;
 
 pub(crate) mod accounts;
 pub(super) mod common;
@@ -36,12 +37,27 @@ pub(crate) enum RBeanCommands {
         /// Output directory to use
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
-        /// Format to use
-        #[arg(short = 'f', long = "format", default_value = "jpeg")]
-        format: crate::r#impl::rbean::download::CLIDownloadFormat,
+        /// Format to use, default to jpeg
+        #[arg(short = 'f', long = "format")]
+        format: Option<crate::r#impl::rbean::download::CLIDownloadFormat>,
         /// Enable parallel download
-        #[arg(short = 'p', long = "parallel")]
+        #[arg(short = 'p', long = "parallel", overrides_with = "no_parallel")]
         parallel: bool,
+        /// Disable parallel download, overriding the settings
+        #[arg(long = "no-parallel", overrides_with = "parallel")]
+        no_parallel: bool,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Download a chapters from a title
     Download {
@@ -53,12 +69,27 @@ pub(crate) enum RBeanCommands {
         /// Output directory to use
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
-        /// Format to use
-        #[arg(short = 'f', long = "format", default_value = "jpeg")]
-        format: crate::r#impl::rbean::download::CLIDownloadFormat,
+        /// Format to use, default to jpeg
+        #[arg(short = 'f', long = "format")]
+        format: Option<crate::r#impl::rbean::download::CLIDownloadFormat>,
         /// Enable parallel download
-        #[arg(short = 'p', long = "parallel")]
+        #[arg(short = 'p', long = "parallel", overrides_with = "no_parallel")]
         parallel: bool,
+        /// Disable parallel download, overriding the settings
+        #[arg(long = "no-parallel", overrides_with = "parallel")]
+        no_parallel: bool,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Get the home page of your account
     Homepage,
@@ -86,4 +117,15 @@ pub(crate) enum RBeanCommands {
         #[arg(short, long, value_enum, default_value = "alphabetical")]
         sort: Option<crate::r#impl::rbean::manga::CLISortOption>,
     },
+    /// Compare the chapters of a title with the local downloads
+    Sync {
+        /// UUID of the title
+        uuid: String,
+        /// Download every available chapter without purchasing
+        #[arg(short = 'd', long = "download")]
+        download: bool,
+        /// Output directory to use
+        #[arg(short = 'o', long = "output", default_value = None)]
+        output: Option<PathBuf>,
+    },
 }
@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::r#impl::{
+    config::proxy::ProxyRouter,
+    watch::{runner::run_watch_entry, watchlist::WatchEntry},
+    Implementations,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// A download enqueued through the API.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Job {
+    pub(crate) id: u64,
+    pub(crate) source: Implementations,
+    pub(crate) title_id: String,
+    pub(crate) account_id: String,
+    pub(crate) status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) exit_code: Option<u32>,
+    pub(crate) created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) finished_at: Option<String>,
+}
+
+/// Run the enqueued downloads one by one in the background.
+#[derive(Clone)]
+pub(crate) struct JobQueue {
+    jobs: Arc<Mutex<Vec<Job>>>,
+    sender: tokio::sync::mpsc::UnboundedSender<(u64, WatchEntry)>,
+}
+
+fn now() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+impl JobQueue {
+    pub(crate) fn start(proxies: ProxyRouter, console: crate::term::Terminal) -> Self {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let queue = Self {
+            jobs: Arc::new(Mutex::new(vec![])),
+            sender,
+        };
+
+        tokio::spawn(queue.clone().worker(receiver, proxies, console));
+        queue
+    }
+
+    async fn worker(
+        self,
+        mut receiver: tokio::sync::mpsc::UnboundedReceiver<(u64, WatchEntry)>,
+        proxies: ProxyRouter,
+        mut console: crate::term::Terminal,
+    ) {
+        while let Some((id, entry)) = receiver.recv().await {
+            self.update(id, |job| {
+                job.status = JobStatus::Running;
+                job.started_at = Some(now());
+            });
+
+            let exit_code = run_watch_entry(&entry, &proxies, &mut console).await;
+
+            self.update(id, |job| {
+                job.status = if exit_code == 0 {
+                    JobStatus::Completed
+                } else {
+                    JobStatus::Failed
+                };
+                job.exit_code = Some(exit_code);
+                job.finished_at = Some(now());
+            });
+        }
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Job)) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+            f(job);
+        }
+    }
+
+    pub(crate) fn enqueue(&self, entry: WatchEntry) -> anyhow::Result<Job> {
+        let job = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let job = Job {
+                id: jobs.last().map(|job| job.id + 1).unwrap_or(1),
+                source: entry.source,
+                title_id: entry.title_id.clone(),
+                account_id: entry.account_id.clone(),
+                status: JobStatus::Queued,
+                exit_code: None,
+                created_at: now(),
+                started_at: None,
+                finished_at: None,
+            };
+            jobs.push(job.clone());
+            job
+        };
+
+        self.sender
+            .send((job.id, entry))
+            .map_err(|_| anyhow::anyhow!("The download worker is not running"))?;
+        Ok(job)
+    }
+
+    pub(crate) fn list(&self) -> Vec<Job> {
+        self.jobs.lock().unwrap().clone()
+    }
+
+    pub(crate) fn get(&self, id: u64) -> Option<Job> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|job| job.id == id)
+            .cloned()
+    }
+}
@@ -0,0 +1,234 @@
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+
+use color_print::cformat;
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    body::{Bytes, Incoming},
+    header, Request, Response, StatusCode,
+};
+use rand::Rng;
+
+use crate::{
+    cli::ExitCode,
+    config::get_user_path,
+    r#impl::{
+        common::request_shutdown,
+        config::{
+            proxy::ProxyRouter,
+            vault::{disable_passphrase_prompt, unlock},
+        },
+    },
+};
+
+pub(crate) mod jobs;
+pub(crate) mod routes;
+
+/// The environment variable used for the API token when `--token` is not given.
+pub(crate) const SERVE_TOKEN_ENV: &str = "AIRPOPE_SERVE_TOKEN";
+/// The maximum accepted request body size.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ServeConfig {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) token: Option<String>,
+}
+
+pub(crate) struct ServerState {
+    token: String,
+    pub(crate) proxies: ProxyRouter,
+    pub(crate) jobs: jobs::JobQueue,
+}
+
+/// Compare the tokens without returning early on the first mismatch.
+fn is_same_token(expected: &str, given: &str) -> bool {
+    if expected.len() != given.len() {
+        return false;
+    }
+
+    expected
+        .bytes()
+        .zip(given.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+fn is_authorized(req: &Request<Incoming>, token: &str) -> bool {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|given| is_same_token(token, given.trim()))
+        .unwrap_or(false)
+}
+
+fn parse_query(uri: &hyper::Uri) -> HashMap<String, String> {
+    match uri.query() {
+        Some(query) => reqwest::Url::parse(&format!("http://localhost/?{}", query))
+            .map(|url| url.query_pairs().into_owned().collect())
+            .unwrap_or_default(),
+        None => HashMap::new(),
+    }
+}
+
+fn json_response(status: StatusCode, value: serde_json::Value) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(value.to_string())));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    json_response(status, serde_json::json!({ "error": message }))
+}
+
+async fn handle_request(
+    req: Request<Incoming>,
+    state: Arc<ServerState>,
+    console: crate::term::Terminal,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+
+    let response = if !is_authorized(&req, &state.token) {
+        json_error(StatusCode::UNAUTHORIZED, "Missing or invalid token")
+    } else {
+        let query = parse_query(req.uri());
+        match Limited::new(req.into_body(), MAX_BODY_SIZE).collect().await {
+            Ok(body) => {
+                let body = body.to_bytes();
+                match routes::route(&method, &path, &query, &body, &state).await {
+                    Ok(value) => json_response(StatusCode::OK, value),
+                    Err(err) => json_error(err.status, &err.message),
+                }
+            }
+            Err(err) => json_error(StatusCode::PAYLOAD_TOO_LARGE, &err.to_string()),
+        }
+    };
+
+    console.info(&format!(
+        "{} {} -> {}",
+        method,
+        path,
+        response.status().as_u16()
+    ));
+    Ok(response)
+}
+
+fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+pub(crate) async fn serve(
+    config: ServeConfig,
+    proxies: &ProxyRouter,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    // the passphrase can only be asked now, the requests never prompt for it
+    if let Err(err) = unlock(&get_user_path()) {
+        console.warn(&format!(
+            "Failed to unlock the accounts, the account endpoints stay locked: {}",
+            err
+        ));
+    }
+    disable_passphrase_prompt();
+
+    let listener = match tokio::net::TcpListener::bind((config.host.as_str(), config.port)).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            console.error(&format!(
+                "Unable to listen on {}:{}: {}",
+                config.host, config.port, err
+            ));
+            return 1;
+        }
+    };
+    let local_addr = listener.local_addr().ok();
+
+    let token = config
+        .token
+        .or_else(|| std::env::var(SERVE_TOKEN_ENV).ok())
+        .filter(|token| !token.is_empty());
+    let token = match token {
+        Some(token) => token,
+        None => {
+            let token = generate_token();
+            console.info(&cformat!("Generated API token: <m,s>{}</>", token));
+            token
+        }
+    };
+
+    if let Some(addr) = local_addr {
+        if !addr.ip().is_loopback() {
+            console.warn(&format!(
+                "Listening on {}, the API is reachable from other machines!",
+                addr.ip()
+            ));
+        }
+        console.info(&cformat!("Serving the API on <m,s>http://{}</>", addr));
+    }
+
+    let state = Arc::new(ServerState {
+        token,
+        proxies: proxies.clone(),
+        jobs: jobs::JobQueue::start(proxies.clone(), console.clone()),
+    });
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+
+        let stream = match accepted {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                console.warn(&format!("Failed to accept connection: {}", err));
+                continue;
+            }
+        };
+
+        let state = Arc::clone(&state);
+        let cnsl = console.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |req| {
+                handle_request(req, Arc::clone(&state), cnsl.clone())
+            });
+
+            hyper::server::conn::http1::Builder::new()
+                .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+                .await
+                .unwrap_or_default();
+        });
+    }
+
+    request_shutdown();
+    console.info("Server stopped");
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_and_query() {
+        assert!(is_same_token("abc123", "abc123"));
+        assert!(!is_same_token("abc123", "abc124"));
+        assert!(!is_same_token("abc123", "abc12"));
+
+        let uri: hyper::Uri = "/api/km/search?q=one%20piece&account=123".parse().unwrap();
+        let query = parse_query(&uri);
+        assert_eq!(query.get("q").map(|q| q.as_str()), Some("one piece"));
+        assert_eq!(query.get("account").map(|a| a.as_str()), Some("123"));
+        assert_eq!(generate_token().len(), 32);
+    }
+}
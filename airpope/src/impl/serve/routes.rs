@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use hyper::{body::Bytes, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    config::{get_all_config, get_user_path, ConfigImpl},
+    r#impl::{
+        amap::download::amap_fetch_title_info,
+        client::{
+            make_amap_client, make_kmkc_client, make_musq_client, make_rbean_client,
+            make_sjv_client, resolve_single_account, AccountLookupError,
+        },
+        config::{
+            settings::get_settings,
+            vault::{is_locked, PASSPHRASE_ENV},
+        },
+        kmkc::download::kmkc_fetch_title_info,
+        musq::download::musq_fetch_title_info,
+        parser::NumberOrString,
+        rbean::download::rbean_fetch_title_info,
+        sjv::{
+            common::{get_cached_store_data, search_manga_by_text},
+            download::sjv_fetch_title_info,
+        },
+        watch::watchlist::{PurchasePolicy, WatchEntry},
+        Implementations,
+    },
+};
+
+use super::ServerState;
+
+pub(crate) struct ApiError {
+    pub(crate) status: StatusCode,
+    pub(crate) message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl ToString) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.to_string(),
+        }
+    }
+
+    fn not_found(message: impl ToString) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: message.to_string(),
+        }
+    }
+
+    fn locked(message: impl ToString) -> Self {
+        Self {
+            status: StatusCode::LOCKED,
+            message: message.to_string(),
+        }
+    }
+
+    fn upstream(err: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::BAD_GATEWAY,
+            message: err.to_string(),
+        }
+    }
+}
+
+type ApiResult = Result<serde_json::Value, ApiError>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResult {
+    id: String,
+    title: String,
+}
+
+/// The body of `POST /api/downloads`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadRequest {
+    source: Implementations,
+    title_id: String,
+    #[serde(default)]
+    account: Option<String>,
+    /// A folder inside the download directory of the source.
+    #[serde(default)]
+    output: Option<std::path::PathBuf>,
+    #[serde(default)]
+    purchase: PurchasePolicy,
+    #[serde(default)]
+    trim: Option<u8>,
+}
+
+fn to_json<T: Serialize>(value: T) -> ApiResult {
+    serde_json::to_value(value).map_err(|e| ApiError {
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+        message: e.to_string(),
+    })
+}
+
+fn parse_source(source: &str) -> Result<Implementations, ApiError> {
+    Implementations::from_str(source, true)
+        .map_err(|_| ApiError::bad_request(format!("Unknown source: {}", source)))
+}
+
+/// The accounts can't be read while the vault is locked, since the server never prompts.
+fn ensure_unlocked() -> Result<(), ApiError> {
+    if is_locked(&get_user_path()) {
+        return Err(ApiError::locked(format!(
+            "The accounts are locked, restart the server with {} set",
+            PASSPHRASE_ENV
+        )));
+    }
+
+    Ok(())
+}
+
+/// Use the given account or alias, the default account, or the only account of the source.
+fn resolve_account(
+    source: Implementations,
+    account_id: Option<&String>,
+) -> Result<ConfigImpl, ApiError> {
+    ensure_unlocked()?;
+    let console = crate::term::get_console(0);
+    resolve_single_account(account_id.map(|id| id.as_str()), source, &console).map_err(|err| {
+        match err {
+            AccountLookupError::NotFound(_) => ApiError::not_found(err),
+            AccountLookupError::Ambiguous(_) => ApiError::bad_request(err),
+        }
+    })
+}
+
+fn list_accounts() -> ApiResult {
+    ensure_unlocked()?;
+    let accounts: Vec<serde_json::Value> = Implementations::value_variants()
+        .iter()
+        .flat_map(|source| {
+            get_all_config(source, None)
+                .into_iter()
+                .map(|config| json!({ "source": source, "id": config.get_id() }))
+                .collect::<Vec<serde_json::Value>>()
+        })
+        .collect();
+
+    Ok(json!(accounts))
+}
+
+async fn search(
+    source: Implementations,
+    query: &HashMap<String, String>,
+    state: &ServerState,
+) -> ApiResult {
+    let keyword = query
+        .get("q")
+        .ok_or_else(|| ApiError::bad_request("Missing the `q` parameter"))?;
+    let config = resolve_account(source, query.get("account"))?;
+    let proxy = state.proxies.for_account(source, config.get_id()).await;
+
+    let results: Vec<SearchResult> = match config {
+        ConfigImpl::Kmkc(config) => {
+            let client = make_kmkc_client(&config.into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let results = client
+                .search(keyword, Some(50))
+                .await
+                .map_err(ApiError::upstream)?;
+            results
+                .into_iter()
+                .map(|title| SearchResult {
+                    id: title.id.to_string(),
+                    title: title.title,
+                })
+                .collect()
+        }
+        ConfigImpl::Musq(config) => {
+            let client = make_musq_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let results = client.search(keyword).await.map_err(ApiError::upstream)?;
+            results
+                .titles
+                .into_iter()
+                .map(|title| SearchResult {
+                    id: title.id.to_string(),
+                    title: title.title,
+                })
+                .collect()
+        }
+        ConfigImpl::Amap(config) => {
+            let client = make_amap_client(&config.clone().into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let results = client
+                .search(keyword, None, None, None, None)
+                .await
+                .map_err(ApiError::upstream)?;
+            crate::r#impl::amap::common::save_session_config(&client, &config);
+            results
+                .comics
+                .into_iter()
+                .map(|comic| SearchResult {
+                    id: comic.info.id.to_string(),
+                    title: comic.info.title,
+                })
+                .collect()
+        }
+        ConfigImpl::Sjv(config) => {
+            let client = make_sjv_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let results = get_cached_store_data(&client)
+                .await
+                .map_err(ApiError::upstream)?;
+            search_manga_by_text(&results.series, keyword)
+                .into_iter()
+                .map(|title| SearchResult {
+                    id: title.id.to_string(),
+                    title: title.title.clone(),
+                })
+                .collect()
+        }
+        ConfigImpl::Rbean(config) => {
+            let client = make_rbean_client(&config);
+            let mut client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let results = client
+                .search(keyword, Some(0), Some(50), None)
+                .await
+                .map_err(ApiError::upstream)?;
+            crate::r#impl::rbean::common::save_session_config(&client, &config);
+            results
+                .results
+                .into_iter()
+                .map(|title| SearchResult {
+                    id: title.uuid,
+                    title: title.title,
+                })
+                .collect()
+        }
+    };
+
+    to_json(results)
+}
+
+async fn title_info(
+    source: Implementations,
+    title_id: &str,
+    query: &HashMap<String, String>,
+    state: &ServerState,
+) -> ApiResult {
+    let invalid_id = || ApiError::bad_request(format!("Invalid title ID: {}", title_id));
+    let config = resolve_account(source, query.get("account"))?;
+    let proxy = state.proxies.for_account(source, config.get_id()).await;
+
+    let info = match config {
+        ConfigImpl::Kmkc(config) => {
+            let title_id = title_id.parse::<i32>().map_err(|_| invalid_id())?;
+            let client = make_kmkc_client(&config.into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            kmkc_fetch_title_info(title_id, &client).await
+        }
+        ConfigImpl::Musq(config) => {
+            let title_id = title_id.parse::<u64>().map_err(|_| invalid_id())?;
+            let client = make_musq_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            musq_fetch_title_info(title_id, &client).await
+        }
+        ConfigImpl::Amap(config) => {
+            let title_id = title_id.parse::<u64>().map_err(|_| invalid_id())?;
+            let client = make_amap_client(&config.clone().into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            amap_fetch_title_info(title_id, &client, &config).await
+        }
+        ConfigImpl::Sjv(config) => {
+            let title_or_slug = match title_id.parse::<usize>() {
+                Ok(title_id) => NumberOrString::Number(title_id),
+                Err(_) => NumberOrString::Str(title_id.to_string()),
+            };
+            let client = make_sjv_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            sjv_fetch_title_info(&title_or_slug, &client)
+                .await
+                .map(|(_, info)| info)
+        }
+        ConfigImpl::Rbean(config) => {
+            let client = make_rbean_client(&config);
+            let mut client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            rbean_fetch_title_info(title_id, &mut client, &config).await
+        }
+    };
+
+    to_json(info.map_err(ApiError::upstream)?)
+}
+
+async fn balance(
+    source: Implementations,
+    query: &HashMap<String, String>,
+    state: &ServerState,
+) -> ApiResult {
+    let config = resolve_account(source, query.get("account"))?;
+    let proxy = state.proxies.for_account(source, config.get_id()).await;
+
+    match config {
+        ConfigImpl::Kmkc(config) => {
+            let client = make_kmkc_client(&config.into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let balance = client.get_user_point().await.map_err(ApiError::upstream)?;
+            to_json(balance)
+        }
+        ConfigImpl::Musq(config) => {
+            let client = make_musq_client(&config);
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let balance = client.get_user_point().await.map_err(ApiError::upstream)?;
+            Ok(json!({
+                "total": balance.sum(),
+                "paid": balance.paid,
+                "event": balance.event,
+                "free": balance.free,
+            }))
+        }
+        ConfigImpl::Amap(config) => {
+            let client = make_amap_client(&config.clone().into());
+            let client = match proxy {
+                Some(proxy) => client.with_proxy(proxy),
+                None => client,
+            };
+            let remainder = client.get_remainder().await.map_err(ApiError::upstream)?;
+            crate::r#impl::amap::common::save_session_config(&client, &config);
+            to_json(remainder.info)
+        }
+        ConfigImpl::Sjv(_) | ConfigImpl::Rbean(_) => Err(ApiError::bad_request(format!(
+            "{} does not have any balance",
+            source.source_name()
+        ))),
+    }
+}
+
+/// Check that the requested output stays inside the download directory.
+fn inner_output(output: &std::path::Path) -> Result<&std::path::Path, ApiError> {
+    let is_inner = output
+        .components()
+        .all(|part| matches!(part, std::path::Component::Normal(_)));
+    if output.as_os_str().is_empty() || !is_inner {
+        return Err(ApiError::bad_request(
+            "The output must be a relative folder inside the download directory",
+        ));
+    }
+
+    Ok(output)
+}
+
+fn enqueue_download(body: &Bytes, state: &ServerState) -> ApiResult {
+    let request: DownloadRequest = serde_json::from_slice(body)
+        .map_err(|e| ApiError::bad_request(format!("Invalid request body: {}", e)))?;
+
+    let output_dir = get_settings().resolve(request.source).output_dir(None);
+    let output_dir = match request.output {
+        Some(output) => output_dir.join(inner_output(&output)?),
+        None => output_dir,
+    };
+
+    let account = resolve_account(request.source, request.account.as_ref())?;
+    let entry = WatchEntry {
+        source: request.source,
+        account_id: account.get_id().to_string(),
+        title_id: request.title_id,
+        output: Some(output_dir),
+        purchase: request.purchase,
+        trim: request.trim,
+        schedule: None,
+    };
+
+    let job = state.jobs.enqueue(entry).map_err(|e| ApiError {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        message: e.to_string(),
+    })?;
+    to_json(job)
+}
+
+/// Dispatch a request to the matching endpoint.
+pub(crate) async fn route(
+    method: &Method,
+    path: &str,
+    query: &HashMap<String, String>,
+    body: &Bytes,
+    state: &ServerState,
+) -> ApiResult {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (method, segments.as_slice()) {
+        (&Method::GET, ["api", "accounts"]) => list_accounts(),
+        (&Method::GET, ["api", "jobs"]) => to_json(state.jobs.list()),
+        (&Method::GET, ["api", "jobs", job_id]) => {
+            let job_id = job_id
+                .parse::<u64>()
+                .map_err(|_| ApiError::bad_request("Invalid job ID"))?;
+            match state.jobs.get(job_id) {
+                Some(job) => to_json(job),
+                None => Err(ApiError::not_found(format!("Job {} not found", job_id))),
+            }
+        }
+        (&Method::POST, ["api", "downloads"]) => enqueue_download(body, state),
+        (&Method::GET, ["api", source, "search"]) => {
+            search(parse_source(source)?, query, state).await
+        }
+        (&Method::GET, ["api", source, "titles", title_id]) => {
+            title_info(parse_source(source)?, title_id, query, state).await
+        }
+        (&Method::GET, ["api", source, "balance"]) => {
+            balance(parse_source(source)?, query, state).await
+        }
+        _ => Err(ApiError::not_found("Unknown endpoint")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_inner_output() {
+        assert!(inner_output(Path::new("manga/one")).is_ok());
+        assert!(inner_output(Path::new("")).is_err());
+        assert!(inner_output(Path::new("/tmp/manga")).is_err());
+        assert!(inner_output(Path::new("manga/../../etc")).is_err());
+    }
+}
@@ -72,8 +72,8 @@ pub(crate) fn search_manga_by_text<'a>(
 const CACHE_EXPIRY: i64 = 12 * 60 * 60;
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub(super) struct WrappedStoreCache {
-    pub(super) series: Vec<MangaDetail>,
+pub(crate) struct WrappedStoreCache {
+    pub(crate) series: Vec<MangaDetail>,
     pub(super) chapters: Vec<MangaChapterDetail>,
     #[serde(rename = "_last_updated")]
     pub(super) last_updated: i64,
@@ -107,7 +107,7 @@ impl From<MangaStoreResponse> for WrappedStoreCache {
     }
 }
 
-pub(super) async fn get_cached_store_data(client: &SJClient) -> anyhow::Result<WrappedStoreCache> {
+pub(crate) async fn get_cached_store_data(client: &SJClient) -> anyhow::Result<WrappedStoreCache> {
     let term = get_console(0);
 
     let base_path = get_user_path();
@@ -10,13 +10,24 @@ use airpope_sjv::{
 use crate::{
     cli::ExitCode,
     r#impl::{
+        batch::manifest::BatchJob,
         models::{ChapterDetailDump, MangaDetailDump},
         parser::NumberOrString,
+        plan::{DownloadPlan, PlanAction},
+        common::is_shutdown_requested,
+        hooks::PostHooks,
+        notify::{notify, EventKind, NotifyEvent},
+        accounts::check::account_error_event,
+        queue::QueueTracker,
+        config::proxy::ProxyLease,
+        sync::report_title_sync,
+        tools::trim::trim_downloaded_chapter,
+        Implementations,
     },
     term::ConsoleChoice,
 };
 
-use super::common::get_cached_store_data;
+use super::{common::get_cached_store_data, config::Config};
 
 #[derive(Clone, Debug, Default)]
 pub(crate) struct SJDownloadCliConfig {
@@ -36,6 +47,16 @@ pub(crate) struct SJDownloadCliConfig {
     ///
     /// Used only when `no_input` is `true`.
     pub(crate) end_at: Option<u32>,
+    /// Trim uniform borders of the downloaded pages with the given tolerance.
+    pub(crate) trim: Option<u8>,
+    /// The commands to run after each chapter and the title are downloaded.
+    pub(crate) hooks: PostHooks,
+    /// How the download is recorded in the download queue.
+    pub(crate) queue: QueueTracker,
+    /// The proxy of the requests, to report its failures.
+    pub(crate) proxy: ProxyLease,
+    /// Only print the download plan without purchasing or downloading anything.
+    pub(crate) dry_run: bool,
 }
 
 fn check_downloaded_image_count(image_dir: &PathBuf, extension: &str) -> Option<usize> {
@@ -73,9 +94,11 @@ fn create_chapters_info(title: &MangaDetail, chapters: Vec<MangaChapterDetail>)
         title.author.clone().unwrap_or("Unknown Author".to_string()),
         dumped_chapters,
     )
+    .with_source("SJ")
+    .with_title_id(title.id)
 }
 
-fn get_output_directory(
+pub(crate) fn get_output_directory(
     output_dir: &Path,
     title_id: u32,
     chapter_id: Option<u32>,
@@ -201,17 +224,18 @@ async fn sjv_actual_downloader(
     }
 
     match node.client.stream_download(&download_url, writer).await {
-        Ok(_) => {}
+        Ok(_) => {
+            progress.inc(1);
+            Ok(())
+        }
         Err(err) => {
-            console.error(&format!("    Failed to download image: {}", err));
             // silent delete the file
-            tokio::fs::remove_file(&img_dl_path).await?;
+            tokio::fs::remove_file(&img_dl_path)
+                .await
+                .unwrap_or_default();
+            Err(err)
         }
     }
-
-    progress.inc(1);
-
-    Ok(())
 }
 
 pub(crate) async fn sjv_download(
@@ -219,6 +243,7 @@ pub(crate) async fn sjv_download(
     dl_config: SJDownloadCliConfig,
     output_dir: PathBuf,
     client: &SJClient,
+    account: &Config,
     console: &mut crate::term::Terminal,
 ) -> ExitCode {
     if let (Some(start), Some(end)) = (dl_config.start_from, dl_config.end_at) {
@@ -257,6 +282,11 @@ pub(crate) async fn sjv_download(
     let subs_resp = client.get_entitlements().await;
     if let Err(e) = subs_resp {
         console.error(&format!("Failed to fetch subscription info: {}", e));
+        notify(
+            NotifyEvent::new(account_error_event(Implementations::Sjv, &e), "SJ").with_message(&e),
+            console,
+        )
+        .await;
         return 1;
     }
 
@@ -325,6 +355,7 @@ pub(crate) async fn sjv_download(
                             || dl_config.chapter_ids.contains(&(ch.id as usize))
                     }
                 })
+                .filter(|&ch| dl_config.queue.accepts(ch.id))
                 .filter(|&ch| ch.is_available() || has_subs)
                 .filter(|&ch| {
                     // Hide future chapters because we're not time traveler
@@ -343,6 +374,21 @@ pub(crate) async fn sjv_download(
 
             download_chapters.sort_by(|&a, &b| a.id.cmp(&b.id));
 
+            if dl_config.dry_run {
+                let mut plan = DownloadPlan::default();
+                for chapter in download_chapters.iter() {
+                    // unavailable chapters are only kept when subscribed
+                    let action = if chapter.is_available() {
+                        PlanAction::Free
+                    } else {
+                        PlanAction::Subscription
+                    };
+                    plan.add(chapter.pretty_title(), chapter.id, action);
+                }
+                plan.print(console);
+                return 0;
+            }
+
             let title_dir = get_output_directory(&output_dir, title.id, None, true);
             let dump_info = create_chapters_info(title, chapters);
 
@@ -351,12 +397,34 @@ pub(crate) async fn sjv_download(
                 .dump(&title_dump_path)
                 .expect("Failed to dump title info");
 
+            let queue = dl_config.queue.start(
+                BatchJob {
+                    account: Some(account.id.clone()),
+                    output: Some(output_dir.clone()),
+                    trim: dl_config.trim,
+                    parallel: Some(dl_config.parallel),
+                    ..BatchJob::new(Implementations::Sjv, title.id)
+                },
+                &title.title,
+                download_chapters
+                    .iter()
+                    .map(|ch| (ch.id.to_string(), ch.pretty_title()))
+                    .collect(),
+                console,
+            );
+
             for chapter in download_chapters {
+                if is_shutdown_requested() {
+                    console.warn("Shutdown requested, skipping the remaining chapters");
+                    break;
+                }
+
                 console.info(&cformat!(
                     "  Downloading chapter <m,s>{}</> ({})...",
                     chapter.pretty_title(),
                     chapter.id
                 ));
+                queue.in_progress(chapter.id, console);
 
                 let image_dir =
                     get_output_directory(&output_dir, title.id, Some(chapter.id), false);
@@ -372,19 +440,39 @@ pub(crate) async fn sjv_download(
                             chapter.pretty_title(),
                             chapter.id
                         ));
+                        queue.done(chapter.id, console);
                         continue;
                     }
                 }
 
                 let view_req = client.verify_chapter(chapter.id).await;
                 if let Err(e) = view_req {
+                    dl_config.proxy.report_failure(&e);
                     console.error(&format!("Failed to verify chapter: {}", e));
+                    queue.failed(chapter.id, &e, console);
+                    notify(
+                        NotifyEvent::new(EventKind::DownloadError, "SJ")
+                            .with_title(&title.title)
+                            .with_chapter(&chapter.pretty_title(), chapter.id)
+                            .with_message(e),
+                        console,
+                    )
+                    .await;
                     continue;
                 }
 
                 let ch_metadata = client.get_chapter_metadata(chapter.id).await;
                 if let Err(e) = ch_metadata {
                     console.error(&format!("Failed to fetch chapter metadata: {}", e));
+                    queue.failed(chapter.id, &e, console);
+                    notify(
+                        NotifyEvent::new(EventKind::DownloadError, "SJ")
+                            .with_title(&title.title)
+                            .with_chapter(&chapter.pretty_title(), chapter.id)
+                            .with_message(e),
+                        console,
+                    )
+                    .await;
                     continue;
                 }
 
@@ -409,7 +497,7 @@ pub(crate) async fn sjv_download(
                 );
                 progress.set_message("Downloading");
 
-                if dl_config.parallel {
+                let failed_pages = if dl_config.parallel {
                     let tasks: Vec<_> = (0..total_image_count)
                         .map(|page| {
                             // wrap function in async block
@@ -417,6 +505,7 @@ pub(crate) async fn sjv_download(
                             let image_dir = image_dir.clone();
                             let cnsl = console.clone();
                             let progress = Arc::clone(&progress);
+                            let proxy = dl_config.proxy.clone();
                             let chapter_id = chapter.id;
                             tokio::spawn(async move {
                                 match sjv_actual_downloader(
@@ -432,21 +521,31 @@ pub(crate) async fn sjv_download(
                                 )
                                 .await
                                 {
-                                    Ok(_) => {}
+                                    Ok(_) => true,
                                     Err(e) => {
                                         cnsl.error(&format!(
                                             "    Failed to download chapter: {}",
                                             e
                                         ));
+                                        proxy.report_failure(&e);
+                                        false
                                     }
                                 }
                             })
                         })
                         .collect();
 
-                    futures::future::join_all(tasks).await;
+                    futures::future::join_all(tasks)
+                        .await
+                        .into_iter()
+                        .filter(|task| !matches!(task, Ok(true)))
+                        .count()
                 } else {
+                    let mut failed_pages = 0;
                     for page in 0..total_image_count {
+                        if is_shutdown_requested() {
+                            break;
+                        }
                         match sjv_actual_downloader(
                             DownloadNode {
                                 client: client.clone(),
@@ -463,11 +562,56 @@ pub(crate) async fn sjv_download(
                             Ok(_) => {}
                             Err(e) => {
                                 console.error(&format!("    Failed to download chapter: {}", e));
+                                failed_pages += 1;
+                                dl_config.proxy.report_failure(&e);
                             }
                         }
                     }
-                }
+                    failed_pages
+                };
                 progress.finish_with_message("Downloaded");
+                if is_shutdown_requested() {
+                    // the next chapter stops the download
+                    queue.interrupted(chapter.id, console);
+                    continue;
+                }
+                if failed_pages > 0 {
+                    console.warn(&cformat!(
+                        "   Failed to download {} pages of chapter <m,s>{}</> (<s>{}</>)",
+                        failed_pages,
+                        chapter.pretty_title(),
+                        chapter.id
+                    ));
+                    queue.failed(
+                        chapter.id,
+                        format!("{} pages failed to download", failed_pages),
+                        console,
+                    );
+                    continue;
+                }
+                if let Some(tolerance) = dl_config.trim {
+                    trim_downloaded_chapter(&image_dir, Implementations::Sjv, tolerance, console)
+                        .await;
+                }
+                queue.done(chapter.id, console);
+                notify(
+                    NotifyEvent::new(EventKind::ChapterDownloaded, "SJ")
+                        .with_title(&title.title)
+                        .with_chapter(&chapter.pretty_title(), chapter.id),
+                    console,
+                )
+                .await;
+                dl_config
+                    .hooks
+                    .run_chapter("SJ", title.id, &title_dir, chapter.id, &image_dir, console)
+                    .await;
+            }
+
+            if !is_shutdown_requested() {
+                dl_config
+                    .hooks
+                    .run_title("SJ", title.id, &title_dir, console)
+                    .await;
             }
 
             0
@@ -478,3 +622,79 @@ pub(crate) async fn sjv_download(
         }
     }
 }
+
+/// Fetch the title and every chapters of it as [`MangaDetailDump`], with the numeric title ID.
+pub(crate) async fn sjv_fetch_title_info(
+    title_or_slug: &NumberOrString,
+    client: &SJClient,
+) -> anyhow::Result<(u32, MangaDetailDump)> {
+    let results = get_cached_store_data(client).await?;
+
+    let title = results
+        .series
+        .iter()
+        .find(|x| {
+            if let NumberOrString::Number(n) = title_or_slug {
+                x.id == *n as u32
+            } else {
+                x.slug == title_or_slug.to_string()
+            }
+        })
+        .ok_or_else(|| anyhow::anyhow!("No match found"))?;
+
+    let chapters: Vec<MangaChapterDetail> = client
+        .get_chapters(title.id)
+        .await?
+        .into_iter()
+        .filter(|ch| ch.chapter.is_some())
+        .collect();
+
+    Ok((title.id, create_chapters_info(title, chapters)))
+}
+
+/// Compare the remote chapter list of a title with the local downloads.
+///
+/// With `download`, every available chapter will be downloaded.
+pub(crate) async fn sjv_sync(
+    title_or_slug: NumberOrString,
+    download: bool,
+    output_dir: PathBuf,
+    client: &SJClient,
+    account: &Config,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    console.info(&cformat!(
+        "Fetching chapters for <m,s>{}</>...",
+        title_or_slug
+    ));
+
+    let (title_id, remote_info) = match sjv_fetch_title_info(&title_or_slug, client).await {
+        Ok(result) => result,
+        Err(e) => {
+            console.error(&format!("Failed to fetch chapters: {}", e));
+            return 1;
+        }
+    };
+
+    let title_dir = get_output_directory(&output_dir, title_id, None, false);
+    let report = report_title_sync(&title_dir, &remote_info, console);
+
+    if download && report.has_downloadable() {
+        let dl_config = SJDownloadCliConfig {
+            no_input: true,
+            ..Default::default()
+        };
+
+        return sjv_download(
+            title_or_slug,
+            dl_config,
+            output_dir,
+            client,
+            account,
+            console,
+        )
+        .await;
+    }
+
+    0
+}
@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use clap::Subcommand;
 
 use super::parser::{parse_comma_number, CommaSeparatedNumber, NumberOrString};
+use super::tools::trim::DEFAULT_TRIM_TOLERANCE;
 
 pub(crate) mod accounts;
 pub(crate) mod common;
@@ -43,8 +44,23 @@ pub(crate) enum SJVCommands {
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
         /// Enable parallel download
-        #[arg(short = 'p', long = "parallel")]
+        #[arg(short = 'p', long = "parallel", overrides_with = "no_parallel")]
         parallel: bool,
+        /// Disable parallel download, overriding the settings
+        #[arg(long = "no-parallel", overrides_with = "parallel")]
+        no_parallel: bool,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Download a chapters from a title
     Download {
@@ -57,8 +73,23 @@ pub(crate) enum SJVCommands {
         #[arg(short = 'o', long = "output", default_value = None)]
         output: Option<PathBuf>,
         /// Enable parallel download
-        #[arg(short = 'p', long = "parallel")]
+        #[arg(short = 'p', long = "parallel", overrides_with = "no_parallel")]
         parallel: bool,
+        /// Disable parallel download, overriding the settings
+        #[arg(long = "no-parallel", overrides_with = "parallel")]
+        no_parallel: bool,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Command to run after each chapter is downloaded
+        #[arg(long = "post-chapter-hook", value_name = "COMMAND")]
+        post_chapter_hook: Option<String>,
+        /// Command to run after the title is downloaded
+        #[arg(long = "post-title-hook", value_name = "COMMAND")]
+        post_title_hook: Option<String>,
+        /// Only print the download plan without purchasing or downloading anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
     },
     /// Get a title information
     Info {
@@ -77,4 +108,15 @@ pub(crate) enum SJVCommands {
     },
     /// Get account subscription info
     Subscription,
+    /// Compare the chapters of a title with the local downloads
+    Sync {
+        /// Title ID or Slug to use
+        title_or_slug: NumberOrString,
+        /// Download every available chapter without purchasing
+        #[arg(short = 'd', long = "download")]
+        download: bool,
+        /// Output directory to use
+        #[arg(short = 'o', long = "output", default_value = None)]
+        output: Option<PathBuf>,
+    },
 }
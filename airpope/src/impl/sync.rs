@@ -0,0 +1,558 @@
+use std::path::{Path, PathBuf};
+
+use color_print::cformat;
+
+use crate::{
+    cli::ExitCode,
+    config::ConfigImpl,
+    r#impl::{
+        client::{
+            make_amap_client, make_kmkc_client, make_musq_client, make_rbean_client,
+            make_sjv_client, select_single_account,
+        },
+        config::proxy::ProxyRouter,
+        clean_filename,
+        models::{ChapterDetailDump, MangaDetailDump},
+        parser::NumberOrString,
+        tools::stats::find_title_dirs,
+        Implementations,
+    },
+};
+
+/// The difference between the remote chapter list and the local downloads.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SyncReport {
+    /// Chapters that are not in the local `_info.json` yet.
+    pub(crate) new: Vec<ChapterDetailDump>,
+    /// Chapters known locally but without any downloaded folder.
+    pub(crate) missing: Vec<ChapterDetailDump>,
+    /// Chapters that got renamed, as `(local, remote)`.
+    pub(crate) renamed: Vec<(ChapterDetailDump, ChapterDetailDump)>,
+    /// Chapters in the local `_info.json` that no longer exist on the remote.
+    pub(crate) removed: Vec<ChapterDetailDump>,
+}
+
+impl SyncReport {
+    pub(crate) fn is_up_to_date(&self) -> bool {
+        self.new.is_empty()
+            && self.missing.is_empty()
+            && self.renamed.is_empty()
+            && self.removed.is_empty()
+    }
+
+    /// Check if there is anything that can be downloaded.
+    pub(crate) fn has_downloadable(&self) -> bool {
+        !self.new.is_empty() || !self.missing.is_empty()
+    }
+}
+
+/// Read the local `_info.json` and the downloaded chapter folders of a title.
+pub(crate) fn read_local_title(title_dir: &Path) -> (Option<MangaDetailDump>, Vec<String>) {
+    let local_info: Option<MangaDetailDump> = std::fs::read_to_string(title_dir.join("_info.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+
+    let downloaded: Vec<String> = match std::fs::read_dir(title_dir) {
+        Ok(read_dirs) => read_dirs
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter(|entry| {
+                // empty folder is left behind when a download got aborted
+                std::fs::read_dir(entry.path())
+                    .map(|mut inner| inner.next().is_some())
+                    .unwrap_or(false)
+            })
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    (local_info, downloaded)
+}
+
+/// The folder name of a downloaded chapter, RB names them after the chapter title.
+pub(crate) fn chapter_folder_name(source: Option<&str>, chapter: &ChapterDetailDump) -> String {
+    match source {
+        Some("RB") => clean_filename(&chapter.main_name),
+        _ => chapter.id.to_string(),
+    }
+}
+
+/// The title ID of a title folder, from `_info.json` or from the folder name on older dumps.
+pub(crate) fn local_title_id(title_dir: &Path, local: &MangaDetailDump) -> String {
+    if let Some(title_id) = &local.title_id {
+        return title_id.clone();
+    }
+
+    let folder_name = title_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let prefix = match local.source.as_deref() {
+        Some("SJ") => "SJV_",
+        Some("RB") => "RB_",
+        _ => "",
+    };
+    folder_name
+        .strip_prefix(prefix)
+        .unwrap_or(&folder_name)
+        .to_string()
+}
+
+/// Compare the remote chapter list with the local `_info.json` and chapter folders.
+pub(crate) fn diff_chapters(
+    remote: &MangaDetailDump,
+    local: Option<&MangaDetailDump>,
+    downloaded: &[String],
+) -> SyncReport {
+    let mut report = SyncReport::default();
+    let local_chapters: &[ChapterDetailDump] = match local {
+        Some(local) => &local.chapters,
+        None => &[],
+    };
+
+    for chapter in remote.chapters.iter() {
+        let is_downloaded =
+            downloaded.contains(&chapter_folder_name(remote.source.as_deref(), chapter));
+
+        match local_chapters.iter().find(|local| local.id == chapter.id) {
+            Some(local_chapter) => {
+                if local_chapter.main_name != chapter.main_name {
+                    report
+                        .renamed
+                        .push((local_chapter.clone(), chapter.clone()));
+                }
+                if !is_downloaded {
+                    report.missing.push(chapter.clone());
+                }
+            }
+            None => {
+                if !is_downloaded {
+                    report.new.push(chapter.clone());
+                }
+            }
+        }
+    }
+
+    for chapter in local_chapters.iter() {
+        if !remote.chapters.iter().any(|remote| remote.id == chapter.id) {
+            report.removed.push(chapter.clone());
+        }
+    }
+
+    report
+}
+
+pub(crate) fn print_sync_report(title: &str, report: &SyncReport, console: &crate::term::Terminal) {
+    if report.is_up_to_date() {
+        console.info(&cformat!("<m,s>{}</> is up to date", title));
+        return;
+    }
+
+    console.info(&cformat!(
+        "<m,s>{}</>: <g,s>{}</> new, <y,s>{}</> missing, <c,s>{}</> renamed, <r,s>{}</> removed",
+        title,
+        report.new.len(),
+        report.missing.len(),
+        report.renamed.len(),
+        report.removed.len()
+    ));
+
+    for chapter in report.new.iter() {
+        console.info(&cformat!(
+            "  <g,s>+</> {} ({})",
+            chapter.main_name,
+            chapter.id.to_string()
+        ));
+    }
+    for chapter in report.missing.iter() {
+        console.info(&cformat!(
+            "  <y,s>?</> {} ({})",
+            chapter.main_name,
+            chapter.id.to_string()
+        ));
+    }
+    for (local, remote) in report.renamed.iter() {
+        console.info(&cformat!(
+            "  <c,s>~</> {} -> {} ({})",
+            local.main_name,
+            remote.main_name,
+            remote.id.to_string()
+        ));
+    }
+    for chapter in report.removed.iter() {
+        console.warn(&cformat!(
+            "  <r,s>-</> {} ({})",
+            chapter.main_name,
+            chapter.id.to_string()
+        ));
+    }
+}
+
+/// Compare the remote chapter list with the local title folder and print the report.
+pub(crate) fn report_title_sync(
+    title_dir: &Path,
+    remote: &MangaDetailDump,
+    console: &crate::term::Terminal,
+) -> SyncReport {
+    let (local, downloaded) = read_local_title(title_dir);
+    let report = diff_chapters(remote, local.as_ref(), &downloaded);
+    print_sync_report(&remote.title_name, &report, console);
+
+    report
+}
+
+/// A source name, with the title folders and the title IDs of that source.
+type SourceTitles = (String, Vec<(PathBuf, String)>);
+
+/// The titles of the download root grouped by source, with their title ID.
+fn find_sync_titles(
+    output_dir: &Path,
+    only_title: Option<&str>,
+    console: &crate::term::Terminal,
+) -> std::io::Result<Vec<SourceTitles>> {
+    let mut sources: Vec<SourceTitles> = vec![];
+    for (title_dir, _) in find_title_dirs(output_dir)? {
+        let local = read_local_title(&title_dir).0;
+        match local.as_ref().and_then(|info| info.source.clone()) {
+            Some(source) => {
+                let title_id = local_title_id(&title_dir, local.as_ref().unwrap());
+                if only_title.is_some_and(|only_title| only_title != title_id) {
+                    continue;
+                }
+
+                match sources.iter_mut().find(|(name, _)| name == &source) {
+                    Some((_, dirs)) => dirs.push((title_dir, title_id)),
+                    None => sources.push((source, vec![(title_dir, title_id)])),
+                }
+            }
+            None => {
+                console.warn(&format!(
+                    "Skipping {}, unknown source (download it again once to record it)",
+                    title_dir.display()
+                ));
+            }
+        }
+    }
+
+    Ok(sources)
+}
+
+/// Sync the titles in the download root, using the source recorded in `_info.json`.
+///
+/// Every title is synced unless filtered by source or by title ID.
+pub(crate) async fn sync_all(
+    output_dir: &Path,
+    only_source: Option<Implementations>,
+    only_title: Option<&str>,
+    download: bool,
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    if !output_dir.is_dir() {
+        console.error("The download folder does not exist.");
+        return 1;
+    }
+
+    let sources = match find_sync_titles(output_dir, only_title, console) {
+        Ok(sources) => sources,
+        Err(err) => {
+            console.error(&format!("Failed to read download folder: {}", err));
+            return 1;
+        }
+    };
+
+    if sources.is_empty() {
+        console.warn("No titles to sync found in the download folder.");
+        return 1;
+    }
+
+    let mut exit_code = 0;
+    let mut synced_any = false;
+    for (source, title_dirs) in sources {
+        let implementation = match source.as_str() {
+            "KM" => Implementations::Kmkc,
+            "MU" => Implementations::Musq,
+            "AM" => Implementations::Amap,
+            "SJ" => Implementations::Sjv,
+            "RB" => Implementations::Rbean,
+            _ => {
+                console.warn(&format!("Skipping unknown source {}", source));
+                continue;
+            }
+        };
+        if only_source.is_some_and(|only_source| only_source != implementation) {
+            continue;
+        }
+        synced_any = true;
+
+        console.info(&cformat!(
+            "Syncing <m,s>{}</> titles from <s>{}</>...",
+            title_dirs.len(),
+            source
+        ));
+        let config = match select_single_account(None, implementation, console) {
+            Some(config) => config,
+            None => {
+                console.warn(&format!("No account selected for {}, skipping", source));
+                exit_code = 1;
+                continue;
+            }
+        };
+
+        let proxy = proxies.for_account(implementation, config.get_id()).await;
+        for (title_dir, title_id) in title_dirs {
+            let root_dir = title_dir.parent().unwrap_or(output_dir).to_path_buf();
+
+            let result = match &config {
+                ConfigImpl::Kmkc(config) => {
+                    let client = make_kmkc_client(&config.clone().into());
+                    let client = match proxy.clone() {
+                        Some(proxy) => client.with_proxy(proxy),
+                        None => client,
+                    };
+                    match title_id.parse::<i32>() {
+                        Ok(title_id) => {
+                            super::kmkc::download::kmkc_sync(
+                                title_id, download, root_dir, &client, config, console,
+                            )
+                            .await
+                        }
+                        Err(_) => 1,
+                    }
+                }
+                ConfigImpl::Musq(config) => {
+                    let client = make_musq_client(config);
+                    let client = match proxy.clone() {
+                        Some(proxy) => client.with_proxy(proxy),
+                        None => client,
+                    };
+                    match title_id.parse::<u64>() {
+                        Ok(title_id) => {
+                            super::musq::download::musq_sync(
+                                title_id, download, root_dir, &client, config, console,
+                            )
+                            .await
+                        }
+                        Err(_) => 1,
+                    }
+                }
+                ConfigImpl::Amap(config) => {
+                    let client = make_amap_client(&config.clone().into());
+                    let client = match proxy.clone() {
+                        Some(proxy) => client.with_proxy(proxy),
+                        None => client,
+                    };
+                    match title_id.parse::<u64>() {
+                        Ok(title_id) => {
+                            super::amap::download::amap_sync(
+                                title_id, download, root_dir, &client, config, console,
+                            )
+                            .await
+                        }
+                        Err(_) => 1,
+                    }
+                }
+                ConfigImpl::Sjv(config) => {
+                    let client = make_sjv_client(config);
+                    let client = match proxy.clone() {
+                        Some(proxy) => client.with_proxy(proxy),
+                        None => client,
+                    };
+                    match title_id.parse::<u32>() {
+                        Ok(title_id) => {
+                            super::sjv::download::sjv_sync(
+                                NumberOrString::Number(title_id as usize),
+                                download,
+                                root_dir,
+                                &client,
+                                config,
+                                console,
+                            )
+                            .await
+                        }
+                        Err(_) => 1,
+                    }
+                }
+                ConfigImpl::Rbean(config) => {
+                    let client = make_rbean_client(config);
+                    let mut client = match proxy.clone() {
+                        Some(proxy) => client.with_proxy(proxy),
+                        None => client,
+                    };
+                    super::rbean::download::rbean_sync(
+                        &title_id,
+                        download,
+                        root_dir,
+                        &mut client,
+                        config,
+                        console,
+                    )
+                    .await
+                }
+            };
+
+            if result != 0 {
+                console.warn(&format!("Failed to sync {}", title_dir.display()));
+                exit_code = 1;
+            }
+        }
+    }
+
+    if !synced_any {
+        console.warn("No titles to sync found in the download folder.");
+        return 1;
+    }
+
+    exit_code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_chapter(id: u64, name: &str) -> ChapterDetailDump {
+        serde_json::from_str(&format!(r#"{{"id": {}, "mainName": "{}"}}"#, id, name)).unwrap()
+    }
+
+    #[test]
+    fn test_diff_chapters() {
+        let local = MangaDetailDump::new(
+            "Title".to_string(),
+            "Author".to_string(),
+            vec![
+                make_chapter(1, "Chapter 1"),
+                make_chapter(2, "Chapter 2"),
+                make_chapter(3, "Chapter 3"),
+                make_chapter(4, "Chapter 4"),
+            ],
+        );
+        let remote = MangaDetailDump::new(
+            "Title".to_string(),
+            "Author".to_string(),
+            vec![
+                make_chapter(1, "Chapter 1"),
+                make_chapter(2, "Chapter 2: Renamed"),
+                make_chapter(3, "Chapter 3"),
+                make_chapter(5, "Chapter 5"),
+            ],
+        );
+        let downloaded = vec!["1".to_string(), "2".to_string(), "4".to_string()];
+
+        let report = diff_chapters(&remote, Some(&local), &downloaded);
+
+        let ids = |chapters: &[ChapterDetailDump]| -> Vec<String> {
+            chapters.iter().map(|ch| ch.id.to_string()).collect()
+        };
+        assert_eq!(ids(&report.new), vec!["5"]);
+        assert_eq!(ids(&report.missing), vec!["3"]);
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].1.main_name, "Chapter 2: Renamed");
+        assert_eq!(ids(&report.removed), vec!["4"]);
+        assert!(report.has_downloadable());
+
+        let report = diff_chapters(&remote, None, &[]);
+        assert_eq!(report.new.len(), 4);
+        assert!(report.removed.is_empty());
+    }
+
+    #[test]
+    fn test_sync_download_layouts() {
+        use crate::r#impl::{amap, kmkc, musq, rbean, sjv};
+
+        let root = std::env::temp_dir().join(format!("airpope-sync-{}", std::process::id()));
+        let rb_chapter = |id: &str, name: &str| -> ChapterDetailDump {
+            serde_json::from_str(&format!(r#"{{"id": "{}", "mainName": "{}"}}"#, id, name)).unwrap()
+        };
+
+        // the title folder, the downloaded chapter folder and the dump, as the downloaders write them
+        let layouts = vec![
+            (
+                kmkc::download::get_output_directory(&root, 100, None, false),
+                kmkc::download::get_output_directory(&root, 100, Some(10), true),
+                MangaDetailDump::new("KM Title".to_string(), "A".to_string(), vec![])
+                    .with_source("KM")
+                    .with_title_id(100),
+            ),
+            (
+                musq::download::get_output_directory(&root, 200, None, false),
+                musq::download::get_output_directory(&root, 200, Some(10), true),
+                MangaDetailDump::new("MU Title".to_string(), "A".to_string(), vec![])
+                    .with_source("MU")
+                    .with_title_id(200),
+            ),
+            (
+                amap::download::get_output_directory(&root, 300, None, false),
+                amap::download::get_output_directory(&root, 300, Some(10), true),
+                MangaDetailDump::new("AM Title".to_string(), "A".to_string(), vec![])
+                    .with_source("AM")
+                    .with_title_id(300),
+            ),
+            (
+                sjv::download::get_output_directory(&root, 400, None, false),
+                sjv::download::get_output_directory(&root, 400, Some(10), true),
+                // older dump without the title ID
+                MangaDetailDump::new("SJ Title".to_string(), "A".to_string(), vec![])
+                    .with_source("SJ"),
+            ),
+            (
+                rbean::download::get_output_directory(&root, "rb-uuid".to_string(), None, false),
+                rbean::download::get_output_directory(
+                    &root,
+                    "rb-uuid".to_string(),
+                    Some("Chapter 1: Start".to_string()),
+                    true,
+                ),
+                MangaDetailDump::new("RB Title".to_string(), "A".to_string(), vec![])
+                    .with_source("RB"),
+            ),
+        ];
+        for (title_dir, chapter_dir, mut dump) in layouts {
+            std::fs::write(chapter_dir.join("0001.jpg"), vec![0u8; 10]).unwrap();
+            dump.chapters = match dump.source.as_deref() {
+                Some("RB") => vec![
+                    rb_chapter("ch-1", "Chapter 1: Start"),
+                    rb_chapter("ch-2", "Chapter 2"),
+                ],
+                _ => vec![make_chapter(10, "Chapter 1"), make_chapter(11, "Chapter 2")],
+            };
+            dump.dump(&title_dir.join("_info.json")).unwrap();
+        }
+
+        let console = crate::term::get_console(0);
+        let sources = find_sync_titles(&root, None, &console).unwrap();
+        let mut title_ids: Vec<(String, String)> = vec![];
+        for (source, titles) in sources.iter() {
+            for (title_dir, title_id) in titles {
+                title_ids.push((source.clone(), title_id.clone()));
+
+                let (local, downloaded) = read_local_title(title_dir);
+                let local = local.unwrap();
+                let report = diff_chapters(&local, Some(&local), &downloaded);
+                let missing: Vec<String> = report
+                    .missing
+                    .iter()
+                    .map(|ch| ch.main_name.clone())
+                    .collect();
+                assert_eq!(missing, vec!["Chapter 2"], "{} {}", source, title_id);
+            }
+        }
+        title_ids.sort();
+        assert_eq!(
+            title_ids,
+            vec![
+                ("AM".to_string(), "300".to_string()),
+                ("KM".to_string(), "100".to_string()),
+                ("MU".to_string(), "200".to_string()),
+                ("RB".to_string(), "rb-uuid".to_string()),
+                ("SJ".to_string(), "400".to_string()),
+            ]
+        );
+
+        let sources = find_sync_titles(&root, Some("rb-uuid"), &console).unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].0, "RB");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
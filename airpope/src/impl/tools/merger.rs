@@ -2,7 +2,8 @@ use crate::{
     cli::ExitCode,
     r#impl::models::{
         ChapterDetailDump, IdDump, MangaDetailDump, MangaManualMergeChapterDetail,
-        MangaManualMergeDetail,
+        MangaManualMergeDetail, MangaMergeJournal, MangaMergeJournalChapter, MangaMergeJournalPage,
+        MangaMergeJournalRun, MangaMergeRules, MergeExtraMode, MergeOrderBy,
     },
     term::ConsoleChoice,
 };
@@ -24,11 +25,21 @@ const MODERN_IMAGE_EXT: [&str; 5] = ["avif", "jxl", "webp", "heif", "heic"];
 #[derive(Clone, Debug, Default)]
 pub(crate) struct ToolsMergeConfig {
     pub(crate) skip_last: bool,
-    pub(crate) no_input: bool,
+    /// Collect the chapters automatically from their titles
+    /// instead of selecting them manually.
+    pub(crate) auto_merge: bool,
     /// Ignore _info_manual_merge.json file which
     /// contains all the chapter that are merged manually
     /// to filter out the chapters that are already merged.
     pub(crate) ignore_manual_info: bool,
+    /// Never prompt for anything, unknown chapter titles
+    /// will fail the merge instead.
+    pub(crate) no_input: bool,
+    /// Custom path to the merge rules file, default to
+    /// `_merge_rules.json` in the input folder.
+    pub(crate) rules_file: Option<PathBuf>,
+    /// Only print the merge plan without moving anything.
+    pub(crate) dry_run: bool,
 }
 
 /// Simulate a Regex match object.
@@ -147,43 +158,92 @@ fn inquire_chapter_number(
     Some(matching)
 }
 
+/// Try to match the chapter title with the custom regexes first, then the default one.
+///
+/// Returns the `base` and `split` part of the chapter number.
+fn match_chapter_title(
+    chapter: &ChapterDetailDump,
+    custom_regexes: &[regex::Regex],
+) -> Option<(String, String)> {
+    for regex in custom_regexes.iter().chain(std::iter::once(&*TITLE_REGEX)) {
+        if let Some(matching) = regex.captures(&chapter.main_name) {
+            let base = matching.name("base").map_or("", |m| m.as_str());
+            let split = matching.name("split").map_or("", |m| m.as_str());
+
+            if !base.is_empty() || !split.is_empty() {
+                return Some((base.to_string(), split.to_string()));
+            }
+        }
+    }
+
+    None
+}
+
+fn sort_chapters_dump(chapters_dump: &mut [ChapterDetailDump], order_by: MergeOrderBy) {
+    match order_by {
+        MergeOrderBy::Id => chapters_dump.sort_by(|a, b| a.id.cmp(&b.id)),
+        MergeOrderBy::Timestamp => chapters_dump.sort_by(|a, b| {
+            // chapters without timestamp are put last
+            let a_ts = a.timestamp.unwrap_or(i64::MAX);
+            let b_ts = b.timestamp.unwrap_or(i64::MAX);
+            a_ts.cmp(&b_ts).then_with(|| a.id.cmp(&b.id))
+        }),
+        MergeOrderBy::Name => chapters_dump
+            .sort_by(|a, b| a.main_name.cmp(&b.main_name).then_with(|| a.id.cmp(&b.id))),
+    }
+}
+
 pub fn auto_chapters_collector(
     mut chapters_dump: Vec<ChapterDetailDump>,
+    rules: &MangaMergeRules,
+    no_input: bool,
     console: &mut crate::term::Terminal,
 ) -> BTreeMap<String, Vec<ChapterDetailDump>> {
-    chapters_dump.sort_by(|a, b| a.id.cmp(&b.id));
+    sort_chapters_dump(&mut chapters_dump, rules.order_by);
 
     if chapters_dump.is_empty() {
         console.error("  Empty chapters collection, aborting...");
         return BTreeMap::new();
     }
 
+    let mut custom_regexes = vec![];
+    for pattern in rules.regexes.iter() {
+        match regex::Regex::new(pattern) {
+            Ok(regex) => custom_regexes.push(regex),
+            Err(err) => {
+                console.error(&format!(
+                    "  Invalid regex in merge rules ({}): {}",
+                    pattern, err
+                ));
+                return BTreeMap::new();
+            }
+        }
+    }
+
     let mut last_known_num = 0;
     let mut extra = 0;
     let mut chapters_mapping: BTreeMap<String, Vec<ChapterDetailDump>> = BTreeMap::new();
+    let mut unknown_chapters: Vec<ChapterDetailDump> = vec![];
     for chapter in chapters_dump {
-        let matching = TITLE_REGEX.captures(&chapter.main_name);
-
-        let (mut base, _) = match matching {
-            Some(matching) => {
-                let base = matching.name("base").map_or("", |m| m.as_str());
-                let split = matching.name("split").map_or("", |m| m.as_str());
-
-                match (base, split) {
-                    ("", "") => {
-                        let temp = inquire_chapter_number(&chapter, last_known_num, console);
-                        if let Some(temp) = temp {
-                            let base_t = temp.get("base").unwrap_or_default();
-                            let split_t = temp.get("split").unwrap_or_default();
-                            (base_t, split_t)
-                        } else {
-                            continue;
-                        }
-                    }
-                    _ => (base.to_string(), split.to_string()),
-                }
-            }
+        if let Some(&number) = rules.overrides.get(&chapter.id.to_string()) {
+            console.log(&format!(
+                "  Using override chapter {} for {}",
+                number, chapter.main_name
+            ));
+            last_known_num = last_known_num.max(number);
+            let name = format!("c{:03}", number);
+            chapters_mapping.entry(name).or_default().push(chapter);
+            continue;
+        }
+
+        let (mut base, _) = match match_chapter_title(&chapter, &custom_regexes) {
+            Some(matching) => matching,
             None => {
+                if no_input {
+                    unknown_chapters.push(chapter);
+                    continue;
+                }
+
                 let test_match = inquire_chapter_number(&chapter, last_known_num, console);
 
                 if let Some(test_match) = test_match {
@@ -202,7 +262,19 @@ pub fn auto_chapters_collector(
             base = last_known_num.to_string();
         }
 
-        let mut base = base.trim().parse::<u64>().unwrap();
+        // a custom regex can capture anything as the base
+        let mut base = match base.trim().parse::<u64>() {
+            Ok(base) => base,
+            Err(_) => {
+                console.warn(&format!(
+                    "  Invalid chapter number \"{}\" in: {}",
+                    base.trim(),
+                    chapter.main_name
+                ));
+                unknown_chapters.push(chapter);
+                continue;
+            }
+        };
         let mut use_extra = false;
         if last_known_num > base {
             console.warn(&format!(
@@ -214,17 +286,46 @@ pub fn auto_chapters_collector(
             last_known_num = base;
         }
 
-        if use_extra {
-            // name: ex{base:03}.{extra}
-            let name = format!("ex{:03}.{}", base, extra);
-            extra += 1;
-            chapters_mapping.entry(name).or_default().push(chapter);
-        } else {
-            // name: c{base:03}
-            let name = format!("c{:03}", base);
-            chapters_mapping.entry(name).or_default().push(chapter);
+        match (use_extra, rules.extra) {
+            (true, MergeExtraMode::Extra) => {
+                // name: ex{base:03}.{extra}
+                let name = format!("ex{:03}.{}", base, extra);
+                extra += 1;
+                chapters_mapping.entry(name).or_default().push(chapter);
+            }
+            (true, MergeExtraMode::Skip) => {
+                console.warn(&format!(
+                    "  Skipping extra chapter: {} ({})",
+                    chapter.main_name,
+                    chapter.id.to_string()
+                ));
+            }
+            _ => {
+                // name: c{base:03}
+                let name = format!("c{:03}", base);
+                chapters_mapping.entry(name).or_default().push(chapter);
+            }
         }
     }
+
+    if !unknown_chapters.is_empty() {
+        console.error(&format!(
+            "  Failed to parse {} chapter title(s):",
+            unknown_chapters.len()
+        ));
+        for chapter in unknown_chapters.iter() {
+            console.error(&cformat!(
+                "   - <m,s>{}</> (<s>{}</>)",
+                chapter.main_name,
+                chapter.id.to_string()
+            ));
+        }
+        console.error(
+            "  Add a custom regex or an override for the chapter ID in the merge rules file.",
+        );
+        return BTreeMap::new();
+    }
+
     chapters_mapping
 }
 
@@ -354,6 +455,10 @@ fn is_all_folder_exist(base_dir: PathBuf, chapters: &[ChapterDetailDump]) -> boo
 
 async fn get_last_page(target_dir: PathBuf) -> u64 {
     let mut last_page = 0;
+    if !target_dir.exists() {
+        return last_page;
+    }
+
     let mut read_dirs = tokio::fs::read_dir(target_dir).await.unwrap();
 
     loop {
@@ -425,6 +530,175 @@ async fn read_manual_info_json(input_folder: &Path) -> MangaManualMergeDetail {
     info_json
 }
 
+/// A single chapter merge plan, the pages paths are relative to the input folder.
+struct MergePlanChapter {
+    name: String,
+    chapters: Vec<ChapterDetailDump>,
+    pages: Vec<MangaMergeJournalPage>,
+    last_page: u64,
+}
+
+async fn collect_source_images(source_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut images = vec![];
+    let mut read_dirs = tokio::fs::read_dir(source_dir).await?;
+
+    while let Some(file) = read_dirs.next_entry().await? {
+        let path = file.path();
+        if path.is_file() && is_image(&path) {
+            images.push(path);
+        }
+    }
+
+    images.sort();
+    Ok(images)
+}
+
+fn relative_path_string(input_folder: &Path, path: &Path) -> String {
+    path.strip_prefix(input_folder)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+async fn make_merge_plan(
+    input_folder: &Path,
+    chapters_maps: &BTreeMap<String, Vec<ChapterDetailDump>>,
+    console: &crate::term::Terminal,
+) -> Vec<MergePlanChapter> {
+    let mut merge_plan = vec![];
+    for (name, chapters) in chapters_maps.iter() {
+        if !is_all_folder_exist(input_folder.to_path_buf(), chapters) {
+            console.warn(&format!(
+                "   Not all folders exist for {}, skipping...",
+                name
+            ));
+            continue;
+        }
+
+        let target_dir = input_folder.join(name);
+        let mut last_page = get_last_page(target_dir.clone()).await;
+        let mut pages = vec![];
+        for chapter in chapters {
+            let source_dir = input_folder.join(chapter.id.to_string());
+            let images = match collect_source_images(&source_dir).await {
+                Ok(images) => images,
+                Err(err) => {
+                    console.error(&format!("   Failed to read source directory: {}", err));
+                    continue;
+                }
+            };
+
+            for path in images {
+                let file_ext = path.extension().unwrap().to_str().unwrap();
+                let file_name = format!("p{:03}.{}", last_page, file_ext);
+
+                pages.push(MangaMergeJournalPage {
+                    source: relative_path_string(input_folder, &path),
+                    target: relative_path_string(input_folder, &target_dir.join(file_name)),
+                });
+                last_page += 1;
+            }
+        }
+
+        merge_plan.push(MergePlanChapter {
+            name: name.clone(),
+            chapters: chapters.clone(),
+            pages,
+            last_page,
+        });
+    }
+
+    merge_plan
+}
+
+fn print_merge_plan(
+    input_folder: &Path,
+    merge_plan: &[MergePlanChapter],
+    console: &crate::term::Terminal,
+) {
+    console.info(&cformat!(
+        "Merge plan for <m,s>{}</> (dry run, nothing will be moved):",
+        input_folder.display()
+    ));
+    for plan in merge_plan {
+        let sources = plan
+            .chapters
+            .iter()
+            .map(|ch| ch.id.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        console.info(&cformat!(
+            "  <m,s>{}</>: merged from {} ({} pages)",
+            plan.name,
+            sources,
+            plan.pages.len()
+        ));
+        for page in plan.pages.iter() {
+            console.info(&format!("    {} -> {}", page.source, page.target));
+        }
+    }
+}
+
+/// Read the merge journal, a missing journal is empty.
+///
+/// An unreadable journal is an error, since writing over it loses the record of the past merges.
+async fn read_merge_journal(input_folder: &Path) -> Result<MangaMergeJournal, String> {
+    let journal_json = input_folder.join("_merge_journal.json");
+
+    if !journal_json.exists() {
+        return Ok(MangaMergeJournal::default());
+    }
+
+    let content = tokio::fs::read_to_string(journal_json)
+        .await
+        .map_err(|err| format!("Failed to read _merge_journal.json file: {}", err))?;
+
+    serde_json::from_str(&content)
+        .map_err(|err| format!("Failed to parse _merge_journal.json file: {}", err))
+}
+
+async fn write_merge_journal(
+    input_folder: &Path,
+    journal: &MangaMergeJournal,
+) -> anyhow::Result<()> {
+    let journal_json = input_folder.join("_merge_journal.json");
+    let content = serde_json::to_string_pretty(journal)?;
+    tokio::fs::write(journal_json, content).await?;
+
+    Ok(())
+}
+
+async fn read_merge_rules(
+    input_folder: &Path,
+    rules_file: Option<&Path>,
+) -> Result<MangaMergeRules, String> {
+    let rules_json = match rules_file {
+        Some(rules_file) => {
+            if !rules_file.exists() {
+                return Err(format!(
+                    "The merge rules file is not found: {}",
+                    rules_file.display()
+                ));
+            }
+            rules_file.to_path_buf()
+        }
+        None => {
+            let rules_json = input_folder.join("_merge_rules.json");
+            if !rules_json.exists() {
+                return Ok(MangaMergeRules::default());
+            }
+            rules_json
+        }
+    };
+
+    let rules_json = tokio::fs::read_to_string(rules_json)
+        .await
+        .map_err(|err| format!("Failed to read merge rules file: {}", err))?;
+
+    serde_json::from_str(&rules_json)
+        .map_err(|err| format!("Failed to parse merge rules file: {}", err))
+}
+
 pub(crate) async fn tools_split_merge(
     input_folder: &Path,
     config: ToolsMergeConfig,
@@ -459,10 +733,33 @@ pub(crate) async fn tools_split_merge(
         info_json.chapters.len()
     ));
 
+    // read before merging anything, so a broken journal is never written over
+    let mut journal = match read_merge_journal(input_folder).await {
+        Ok(journal) => journal,
+        Err(err) => {
+            console.error(&err);
+            console.error("Fix or move the journal aside before merging again.");
+            return 1;
+        }
+    };
+
     let mut manual_info_merge = read_manual_info_json(input_folder).await;
 
-    let mut chapters_maps = if config.no_input {
-        auto_chapters_collector(info_json.chapters.clone(), console)
+    let mut chapters_maps = if config.auto_merge {
+        let merge_rules = match read_merge_rules(input_folder, config.rules_file.as_deref()).await {
+            Ok(merge_rules) => merge_rules,
+            Err(err) => {
+                console.error(&err);
+                return 1;
+            }
+        };
+
+        auto_chapters_collector(
+            info_json.chapters.clone(),
+            &merge_rules,
+            config.no_input,
+            console,
+        )
     } else {
         let mut current_chapters = info_json.chapters.clone();
         if !config.ignore_manual_info {
@@ -501,7 +798,7 @@ pub(crate) async fn tools_split_merge(
         }
     }
 
-    if config.no_input {
+    if config.auto_merge && !config.no_input && !config.dry_run {
         let total_values = chapters_maps
             .values()
             .map(|chapters| chapters.len())
@@ -523,19 +820,22 @@ pub(crate) async fn tools_split_merge(
         chapters_maps.remove(&last_key).unwrap();
     }
 
-    console.info("Starting merge...");
-    for (name, chapters) in chapters_maps.iter() {
-        console.info(&format!("  Merging {}...", name));
+    let merge_plan = make_merge_plan(input_folder, &chapters_maps, console).await;
 
-        if !is_all_folder_exist(input_folder.to_path_buf(), chapters) {
-            console.warn(&format!(
-                "   Not all folders exist for {}, skipping...",
-                name
-            ));
-            continue;
-        }
+    if config.dry_run {
+        print_merge_plan(input_folder, &merge_plan, console);
+        return 0;
+    }
 
-        let target_dir = input_folder.join(name);
+    console.info("Starting merge...");
+    let mut journal_run = MangaMergeJournalRun {
+        timestamp: chrono::Utc::now().timestamp(),
+        chapters: vec![],
+    };
+    for plan in merge_plan {
+        console.info(&format!("  Merging {}...", plan.name));
+
+        let target_dir = input_folder.join(&plan.name);
         // create
         match tokio::fs::create_dir_all(&target_dir).await {
             Ok(_) => {}
@@ -550,75 +850,46 @@ pub(crate) async fn tools_split_merge(
             }
         }
 
-        let mut last_page = get_last_page(target_dir.clone()).await;
-        let mut write_to_json = false;
-        for chapter in chapters {
-            let source_dir = input_folder.join(&chapter.id.to_string());
-            if !source_dir.exists() {
-                console.warn(&format!(
-                    "   Source directory for chapter {} does not exist, skipping...",
-                    chapter.id.to_string()
-                ));
-                continue;
-            }
-
-            // iterate through the source directory
-            let mut read_dirs = match tokio::fs::read_dir(source_dir).await {
-                Ok(read_dirs) => read_dirs,
-                Err(err) => {
-                    console.error(&format!("   Failed to read source directory: {}", err));
-                    continue;
+        let mut journal_chapter = MangaMergeJournalChapter {
+            name: plan.name.clone(),
+            chapters: plan.chapters.iter().map(|ch| ch.id.clone()).collect(),
+            pages: vec![],
+        };
+        for page in plan.pages.iter() {
+            // move the file from "source" to target_dir / p{last_page}.{ext}
+            match tokio::fs::rename(
+                input_folder.join(&page.source),
+                input_folder.join(&page.target),
+            )
+            .await
+            {
+                Ok(_) => {
+                    journal_chapter.pages.push(page.clone());
                 }
-            };
-
-            loop {
-                let file = match read_dirs.next_entry().await {
-                    Ok(file) => file,
-                    Err(err) => {
-                        console.error(&format!("   Failed to read source directory: {}", err));
-                        break;
-                    }
-                };
-
-                let file = match file {
-                    Some(file) => file,
-                    None => break,
-                };
-
-                let path = &file.path();
-                if path.is_file() && is_image(path) {
-                    // move the file from "file" to target_dir / p{last_page}.{ext}
-                    let file_name = format!("p{:03}", last_page);
-                    let file_ext = path.extension().unwrap().to_str().unwrap();
-                    let file_name = format!("{}.{}", file_name, file_ext);
-
-                    let target_path = target_dir.join(file_name);
-                    match tokio::fs::rename(path, target_path).await {
-                        Ok(_) => {
-                            write_to_json = true;
-                        }
-                        Err(err) => {
-                            console.error(&format!(
-                                "   Failed to move {}: {}",
-                                path.display(),
-                                err,
-                            ));
-                        }
-                    }
-                    last_page += 1;
+                Err(err) => {
+                    console.error(&format!("   Failed to move {}: {}", page.source, err,));
                 }
             }
         }
 
-        console.info(&format!("   Merged {} with {} pages", name, last_page));
+        console.info(&format!(
+            "   Merged {} with {} pages",
+            plan.name, plan.last_page
+        ));
+
+        let write_to_json = !journal_chapter.pages.is_empty();
+        if write_to_json {
+            journal_run.chapters.push(journal_chapter);
+        }
 
-        if !config.no_input && write_to_json {
+        if !config.auto_merge && write_to_json {
             // manual mode, update the manual info
             manual_info_merge
                 .chapters
                 .push(MangaManualMergeChapterDetail {
-                    name: name.clone(),
-                    chapters: chapters
+                    name: plan.name.clone(),
+                    chapters: plan
+                        .chapters
                         .iter()
                         .map(|ch| ch.id.clone())
                         .collect::<Vec<IdDump>>(),
@@ -626,9 +897,18 @@ pub(crate) async fn tools_split_merge(
         }
     }
 
+    if !journal_run.chapters.is_empty() {
+        journal.title = info_json.title_name.clone();
+        journal.runs.push(journal_run);
+
+        if let Err(err) = write_merge_journal(input_folder, &journal).await {
+            console.error(&format!("Failed to write _merge_journal.json: {}", err));
+        }
+    }
+
     manual_info_merge.title = info_json.title_name;
 
-    if !config.no_input {
+    if !config.auto_merge {
         // write the manual info
         let manual_json_content = serde_json::to_string_pretty(&manual_info_merge).unwrap();
         let manual_json_path = input_folder.join("_info_manual_merge.json");
@@ -642,3 +922,106 @@ pub(crate) async fn tools_split_merge(
 
     0
 }
+
+pub(crate) async fn tools_split_unmerge(
+    input_folder: &Path,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    let journal_json = input_folder.join("_merge_journal.json");
+    console.info(&format!(
+        "Reading _merge_journal.json file: {}",
+        journal_json.display()
+    ));
+
+    if !journal_json.exists() {
+        console.error("The _merge_journal.json file is not found in the input folder.");
+        return 1;
+    }
+
+    let journal = match read_merge_journal(input_folder).await {
+        Ok(journal) => journal,
+        Err(err) => {
+            console.error(&err);
+            return 1;
+        }
+    };
+    if journal.runs.is_empty() {
+        console.warn("No merge recorded in the journal, nothing to unmerge.");
+        return 1;
+    }
+
+    let mut manual_info_merge = read_manual_info_json(input_folder).await;
+    let mut failed_pages = 0;
+    // undo in reverse order so the latest merge is restored first
+    for run in journal.runs.iter().rev() {
+        for chapter in run.chapters.iter().rev() {
+            console.info(&format!("  Unmerging {}...", chapter.name));
+
+            for page in chapter.pages.iter().rev() {
+                let source_path = input_folder.join(&page.source);
+                let target_path = input_folder.join(&page.target);
+
+                if !target_path.exists() {
+                    console.warn(&format!(
+                        "   Merged page {} does not exist, skipping...",
+                        page.target
+                    ));
+                    continue;
+                }
+
+                if source_path.exists() {
+                    console.error(&format!(
+                        "   Original page {} already exists, skipping...",
+                        page.source
+                    ));
+                    failed_pages += 1;
+                    continue;
+                }
+
+                if let Some(parent) = source_path.parent() {
+                    if let Err(err) = tokio::fs::create_dir_all(parent).await {
+                        console.error(&format!("   Failed to create source directory: {}", err));
+                        failed_pages += 1;
+                        continue;
+                    }
+                }
+
+                if let Err(err) = tokio::fs::rename(&target_path, &source_path).await {
+                    console.error(&format!("   Failed to move {}: {}", page.target, err));
+                    failed_pages += 1;
+                }
+            }
+
+            // only remove the merged folder if it's empty
+            tokio::fs::remove_dir(input_folder.join(&chapter.name))
+                .await
+                .unwrap_or_default();
+            manual_info_merge
+                .chapters
+                .retain(|merged| merged.name != chapter.name);
+        }
+    }
+
+    if failed_pages > 0 {
+        console.error(&format!(
+            "Failed to restore {} pages, keeping the journal file.",
+            failed_pages
+        ));
+        return 1;
+    }
+
+    if let Err(err) = tokio::fs::remove_file(&journal_json).await {
+        console.error(&format!("Failed to remove _merge_journal.json: {}", err));
+    }
+
+    let manual_json_path = input_folder.join("_info_manual_merge.json");
+    if manual_json_path.exists() {
+        let manual_json_content = serde_json::to_string_pretty(&manual_info_merge).unwrap();
+        if let Err(err) = tokio::fs::write(manual_json_path, manual_json_content).await {
+            console.error(&format!("Failed to write _info_manual_merge.json: {}", err));
+        }
+    }
+
+    console.info("Restored the original split chapters layout.");
+    0
+}
@@ -3,6 +3,8 @@ use std::path::PathBuf;
 use clap::Subcommand;
 
 pub(crate) mod merger;
+pub(crate) mod stats;
+pub(crate) mod trim;
 
 #[derive(Subcommand)]
 pub(crate) enum ToolsCommands {
@@ -16,6 +18,15 @@ pub(crate) enum ToolsCommands {
         /// Skip the last chapter merge, useful since the last chapter might not have full split chapters yet.
         #[arg(short, long)]
         skip_last: bool,
+        /// Path to the merge rules file, default to _merge_rules.json in the input directory
+        #[arg(short = 'r', long = "rules", default_value = None)]
+        rules_file: Option<PathBuf>,
+        /// Do not prompt for anything, fail if any chapter title can't be parsed
+        #[arg(short = 'n', long = "no-input")]
+        no_input: bool,
+        /// Only print the merge plan without moving anything
+        #[arg(short = 'd', long = "dry-run")]
+        dry_run: bool,
     },
     /// Merge multiple folders of split chapters into one folder
     ///
@@ -26,5 +37,41 @@ pub(crate) enum ToolsCommands {
         /// Ignore the _info_manual_merge.json file which will filter out the chapters to merge
         #[arg(short = 's', long = "ignore-manual")]
         ignore_manual_merge: bool,
+        /// Only print the merge plan without moving anything
+        #[arg(short = 'd', long = "dry-run")]
+        dry_run: bool,
+    },
+    /// Restore the split chapters layout from the merge journal
+    ///
+    /// The merge journal (_merge_journal.json) is written automatically on every merge.
+    Unmerge {
+        /// Input directory to use that contains the _merge_journal.json file
+        input_folder: PathBuf,
+    },
+    /// Trim uniform white/black borders from downloaded pages
+    ///
+    /// Only JPEG and PNG images are supported, the images are overwritten in place.
+    Trim {
+        /// Input directory of a single chapter or a title with multiple chapters
+        input_folder: PathBuf,
+        /// Maximum color difference per channel to be considered as a border
+        #[arg(short = 't', long = "tolerance", default_value_t = trim::DEFAULT_TRIM_TOLERANCE)]
+        tolerance: u8,
+        /// Pair facing pages with the same size and keep them at the same height
+        #[arg(short = 's', long = "spread")]
+        spread: bool,
+        /// The pages before the first facing pages, ex: 1 to skip a single cover page
+        #[arg(long = "spread-offset", default_value_t = 0, requires = "spread")]
+        spread_offset: u32,
+    },
+    /// Summarize the downloaded titles per source and per title
+    ///
+    /// The chapter timestamps are taken from the _info.json file of each title.
+    Stats {
+        /// The download root, a single title folder, or a folder grouped per source
+        input_folder: PathBuf,
+        /// Output the stats as JSON instead of a table
+        #[arg(short = 'j', long = "json")]
+        json: bool,
     },
 }
@@ -0,0 +1,461 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use color_print::cformat;
+use serde::Serialize;
+
+use crate::{
+    cli::ExitCode,
+    r#impl::{common::unix_timestamp_to_string, models::MangaDetailDump},
+};
+
+/// Image extensions that are counted as a page.
+const PAGE_IMAGE_EXT: [&str; 6] = ["jpg", "jpeg", "png", "webp", "avif", "gif"];
+/// Source name used when we can't figure out where a title came from.
+const UNKNOWN_SOURCE: &str = "Unknown";
+
+/// Statistics of a single downloaded title.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TitleStats {
+    pub(crate) title: String,
+    pub(crate) source: String,
+    pub(crate) path: PathBuf,
+    pub(crate) chapters: usize,
+    pub(crate) pages: usize,
+    pub(crate) bytes: u64,
+    /// Bytes on disk grouped by the image format.
+    pub(crate) formats: BTreeMap<String, u64>,
+    pub(crate) average_page_size: u64,
+    pub(crate) oldest_chapter: Option<i64>,
+    pub(crate) newest_chapter: Option<i64>,
+}
+
+/// Statistics of every title from a single source.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SourceStats {
+    pub(crate) source: String,
+    pub(crate) titles: usize,
+    pub(crate) chapters: usize,
+    pub(crate) pages: usize,
+    pub(crate) bytes: u64,
+    pub(crate) formats: BTreeMap<String, u64>,
+    pub(crate) average_page_size: u64,
+    pub(crate) oldest_chapter: Option<i64>,
+    pub(crate) newest_chapter: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LibraryStats {
+    pub(crate) sources: Vec<SourceStats>,
+    pub(crate) titles: Vec<TitleStats>,
+}
+
+fn page_format(path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+
+    if !PAGE_IMAGE_EXT.contains(&ext.as_str()) {
+        return None;
+    }
+
+    match ext.as_str() {
+        "jpeg" => Some("jpg".to_string()),
+        _ => Some(ext),
+    }
+}
+
+fn merge_timestamp(
+    current: Option<i64>,
+    other: Option<i64>,
+    pick: fn(i64, i64) -> i64,
+) -> Option<i64> {
+    match (current, other) {
+        (Some(current), Some(other)) => Some(pick(current, other)),
+        (current, other) => current.or(other),
+    }
+}
+
+/// Find every title folder (folder with `_info.json`) in the download root.
+///
+/// The download root can either contains the title folders directly or
+/// be grouped per source first (e.g. `DOWNLOADS/KM/<title>`), in which case
+/// the folder name is used as the source when `_info.json` does not have it.
+pub(crate) fn find_title_dirs(root: &Path) -> std::io::Result<Vec<(PathBuf, Option<String>)>> {
+    if root.join("_info.json").exists() {
+        return Ok(vec![(root.to_path_buf(), None)]);
+    }
+
+    let mut title_dirs = vec![];
+    for entry in std::fs::read_dir(root)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        if path.join("_info.json").exists() {
+            title_dirs.push((path, None));
+            continue;
+        }
+
+        let group_name = entry.file_name().to_string_lossy().to_string();
+        for sub_entry in std::fs::read_dir(&path)?.flatten() {
+            let sub_path = sub_entry.path();
+            if sub_path.is_dir() && sub_path.join("_info.json").exists() {
+                title_dirs.push((sub_path, Some(group_name.clone())));
+            }
+        }
+    }
+
+    title_dirs.sort();
+    Ok(title_dirs)
+}
+
+/// Collect the statistics of a single title folder.
+pub(crate) fn collect_title_stats(
+    title_dir: &Path,
+    source_hint: Option<String>,
+    console: &crate::term::Terminal,
+) -> std::io::Result<TitleStats> {
+    let dir_name = title_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let manga_info: Option<MangaDetailDump> = std::fs::read_to_string(title_dir.join("_info.json"))
+        .ok()
+        .and_then(|content| match serde_json::from_str(&content) {
+            Ok(info) => Some(info),
+            Err(err) => {
+                console.warn(&format!(
+                    "Failed to parse _info.json in {}: {}",
+                    title_dir.display(),
+                    err
+                ));
+                None
+            }
+        });
+
+    let mut stats = TitleStats {
+        title: dir_name,
+        source: UNKNOWN_SOURCE.to_string(),
+        path: title_dir.to_path_buf(),
+        ..Default::default()
+    };
+
+    let mut chapter_names = vec![];
+    for entry in std::fs::read_dir(title_dir)?.flatten() {
+        let chapter_dir = entry.path();
+        if !chapter_dir.is_dir() {
+            continue;
+        }
+
+        let mut chapter_pages = 0;
+        for page in std::fs::read_dir(&chapter_dir)?.flatten() {
+            let page_path = page.path();
+            let format = match page_format(&page_path) {
+                Some(format) => format,
+                None => continue,
+            };
+            let size = page.metadata().map(|meta| meta.len()).unwrap_or(0);
+
+            chapter_pages += 1;
+            stats.bytes += size;
+            *stats.formats.entry(format).or_default() += size;
+        }
+
+        if chapter_pages > 0 {
+            stats.chapters += 1;
+            stats.pages += chapter_pages;
+            chapter_names.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+
+    if stats.pages > 0 {
+        stats.average_page_size = stats.bytes / stats.pages as u64;
+    }
+
+    if let Some(manga_info) = manga_info {
+        stats.title = manga_info.title_name.clone();
+        if let Some(source) = &manga_info.source {
+            stats.source = source.clone();
+        } else if let Some(source_hint) = source_hint {
+            stats.source = source_hint;
+        }
+
+        // prefer the timestamps of the downloaded chapters, merged chapters
+        // no longer use the chapter ID as the folder name so fallback to everything.
+        let downloaded: Vec<i64> = manga_info
+            .chapters
+            .iter()
+            .filter(|chapter| chapter_names.contains(&chapter.id.to_string()))
+            .filter_map(|chapter| chapter.timestamp)
+            .collect();
+        let timestamps = if downloaded.is_empty() {
+            manga_info
+                .chapters
+                .iter()
+                .filter_map(|chapter| chapter.timestamp)
+                .collect()
+        } else {
+            downloaded
+        };
+
+        stats.oldest_chapter = timestamps.iter().min().copied();
+        stats.newest_chapter = timestamps.iter().max().copied();
+    } else if let Some(source_hint) = source_hint {
+        stats.source = source_hint;
+    }
+
+    Ok(stats)
+}
+
+/// Group the title statistics per source.
+pub(crate) fn summarize_sources(titles: &[TitleStats]) -> Vec<SourceStats> {
+    let mut sources: BTreeMap<String, SourceStats> = BTreeMap::new();
+
+    for title in titles {
+        let source = sources
+            .entry(title.source.clone())
+            .or_insert_with(|| SourceStats {
+                source: title.source.clone(),
+                ..Default::default()
+            });
+
+        source.titles += 1;
+        source.chapters += title.chapters;
+        source.pages += title.pages;
+        source.bytes += title.bytes;
+        for (format, bytes) in title.formats.iter() {
+            *source.formats.entry(format.clone()).or_default() += bytes;
+        }
+        source.oldest_chapter =
+            merge_timestamp(source.oldest_chapter, title.oldest_chapter, i64::min);
+        source.newest_chapter =
+            merge_timestamp(source.newest_chapter, title.newest_chapter, i64::max);
+    }
+
+    sources
+        .into_values()
+        .map(|mut source| {
+            if source.pages > 0 {
+                source.average_page_size = source.bytes / source.pages as u64;
+            }
+            source
+        })
+        .collect()
+}
+
+/// Format bytes into a human readable size.
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.2} {}", size, UNITS[unit])
+    }
+}
+
+fn format_formats(formats: &BTreeMap<String, u64>) -> String {
+    formats
+        .iter()
+        .map(|(format, bytes)| format!("{} {}", format, format_bytes(*bytes)))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn format_timestamp(timestamp: Option<i64>) -> String {
+    timestamp
+        .and_then(unix_timestamp_to_string)
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn truncate_title(title: &str, width: usize) -> String {
+    if title.chars().count() <= width {
+        return title.to_string();
+    }
+
+    let truncated: String = title.chars().take(width - 1).collect();
+    format!("{}…", truncated)
+}
+
+fn print_stats_table(stats: &LibraryStats) {
+    const TITLE_WIDTH: usize = 40;
+
+    println!(
+        "{:<8} {:<TITLE_WIDTH$} {:>8} {:>8} {:>12} {:>12} {:<10} {:<10}  Formats",
+        "Source", "Title", "Chapters", "Pages", "Size", "Avg. Page", "Oldest", "Newest"
+    );
+    for title in stats.titles.iter() {
+        println!(
+            "{:<8} {:<TITLE_WIDTH$} {:>8} {:>8} {:>12} {:>12} {:<10} {:<10}  {}",
+            title.source,
+            truncate_title(&title.title, TITLE_WIDTH),
+            title.chapters,
+            title.pages,
+            format_bytes(title.bytes),
+            format_bytes(title.average_page_size),
+            format_timestamp(title.oldest_chapter),
+            format_timestamp(title.newest_chapter),
+            format_formats(&title.formats)
+        );
+    }
+
+    println!();
+    println!(
+        "{:<8} {:>8} {:>8} {:>8} {:>12} {:>12} {:<10} {:<10}  Formats",
+        "Source", "Titles", "Chapters", "Pages", "Size", "Avg. Page", "Oldest", "Newest"
+    );
+    for source in stats.sources.iter() {
+        println!(
+            "{:<8} {:>8} {:>8} {:>8} {:>12} {:>12} {:<10} {:<10}  {}",
+            source.source,
+            source.titles,
+            source.chapters,
+            source.pages,
+            format_bytes(source.bytes),
+            format_bytes(source.average_page_size),
+            format_timestamp(source.oldest_chapter),
+            format_timestamp(source.newest_chapter),
+            format_formats(&source.formats)
+        );
+    }
+}
+
+pub(crate) async fn tools_stats(
+    input_folder: &Path,
+    json: bool,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    if !input_folder.is_dir() {
+        console.error("The input folder does not exist.");
+        return 1;
+    }
+
+    let title_dirs = match find_title_dirs(input_folder) {
+        Ok(title_dirs) => title_dirs,
+        Err(err) => {
+            console.error(&format!("Failed to read input folder: {}", err));
+            return 1;
+        }
+    };
+
+    if title_dirs.is_empty() {
+        console.warn("No downloaded titles found in the input folder.");
+        return 1;
+    }
+
+    if !json {
+        console.info(&cformat!(
+            "Collecting stats for <m,s>{}</> titles...",
+            title_dirs.len()
+        ));
+    }
+
+    let mut titles = vec![];
+    for (title_dir, source_hint) in title_dirs {
+        match collect_title_stats(&title_dir, source_hint, console) {
+            Ok(stats) => titles.push(stats),
+            Err(err) => {
+                console.error(&format!("Failed to read {}: {}", title_dir.display(), err));
+            }
+        }
+    }
+
+    // biggest titles first in each source, those are the one worth transcoding/archiving
+    titles.sort_by(|a, b| a.source.cmp(&b.source).then(b.bytes.cmp(&a.bytes)));
+
+    let stats = LibraryStats {
+        sources: summarize_sources(&titles),
+        titles,
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&stats) {
+            Ok(content) => println!("{}", content),
+            Err(err) => {
+                console.error(&format!("Failed to serialize stats: {}", err));
+                return 1;
+            }
+        }
+    } else {
+        print_stats_table(&stats);
+    }
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.00 GiB");
+    }
+
+    #[test]
+    fn test_collect_title_and_source_stats() {
+        let temp_dir = std::env::temp_dir().join(format!("airpope-stats-{}", std::process::id()));
+        let title_dir = temp_dir.join("KM").join("1");
+        std::fs::create_dir_all(title_dir.join("10")).unwrap();
+        std::fs::create_dir_all(title_dir.join("11")).unwrap();
+        std::fs::create_dir_all(title_dir.join("empty")).unwrap();
+
+        std::fs::write(title_dir.join("10").join("p001.jpg"), vec![0u8; 100]).unwrap();
+        std::fs::write(title_dir.join("10").join("p002.jpeg"), vec![0u8; 300]).unwrap();
+        std::fs::write(title_dir.join("11").join("p001.png"), vec![0u8; 200]).unwrap();
+        std::fs::write(title_dir.join("11").join("notes.txt"), vec![0u8; 50]).unwrap();
+
+        let info_json = r#"{
+            "titleName": "Test Title",
+            "authorName": "Test Author",
+            "chapters": [
+                {"id": 10, "mainName": "Chapter 1", "timestamp": 1000},
+                {"id": 11, "mainName": "Chapter 2", "timestamp": 3000},
+                {"id": 12, "mainName": "Chapter 3", "timestamp": 5000}
+            ]
+        }"#;
+        std::fs::write(title_dir.join("_info.json"), info_json).unwrap();
+
+        let title_dirs = find_title_dirs(&temp_dir).unwrap();
+        assert_eq!(title_dirs.len(), 1);
+
+        let console = crate::term::get_console(0);
+        let (title_dir, source_hint) = title_dirs[0].clone();
+        let stats = collect_title_stats(&title_dir, source_hint, &console).unwrap();
+
+        assert_eq!(stats.title, "Test Title");
+        assert_eq!(stats.source, "KM");
+        assert_eq!(stats.chapters, 2);
+        assert_eq!(stats.pages, 3);
+        assert_eq!(stats.bytes, 600);
+        assert_eq!(stats.average_page_size, 200);
+        assert_eq!(stats.formats.get("jpg"), Some(&400));
+        assert_eq!(stats.formats.get("png"), Some(&200));
+        // chapter 12 is not downloaded
+        assert_eq!(stats.oldest_chapter, Some(1000));
+        assert_eq!(stats.newest_chapter, Some(3000));
+
+        let sources = summarize_sources(&[stats.clone(), stats]);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].titles, 2);
+        assert_eq!(sources[0].bytes, 1200);
+        assert_eq!(sources[0].average_page_size, 200);
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,486 @@
+use std::path::{Path, PathBuf};
+
+use color_print::cformat;
+use image::{GenericImageView, ImageFormat, Rgb, RgbImage};
+
+use crate::{
+    cli::ExitCode,
+    r#impl::{config::settings::get_settings, Implementations},
+};
+
+/// The default per-channel tolerance used to detect a uniform border.
+pub(crate) const DEFAULT_TRIM_TOLERANCE: u8 = 16;
+
+/// Image format that we can decode and encode back.
+const TRIMMABLE_IMAGE_EXT: [&str; 3] = ["jpg", "jpeg", "png"];
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ToolsTrimConfig {
+    /// Maximum per-channel difference for a pixel to be considered part of the border.
+    pub(crate) tolerance: u8,
+    /// Pair facing pages with the same size and keep them at the same height.
+    pub(crate) spread: bool,
+    /// The pages before the first facing pages, like a single cover page.
+    pub(crate) spread_offset: u32,
+}
+
+impl Default for ToolsTrimConfig {
+    fn default() -> Self {
+        Self {
+            tolerance: DEFAULT_TRIM_TOLERANCE,
+            spread: false,
+            spread_offset: 0,
+        }
+    }
+}
+
+/// The area of an image to keep after trimming.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct TrimBox {
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+struct TrimPage {
+    path: PathBuf,
+    dimensions: (u32, u32),
+    trim_box: Option<TrimBox>,
+}
+
+fn is_similar(pixel: &Rgb<u8>, reference: &Rgb<u8>, tolerance: u8) -> bool {
+    pixel
+        .0
+        .iter()
+        .zip(reference.0.iter())
+        .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+}
+
+fn is_uniform_row(
+    image: &RgbImage,
+    y: u32,
+    x_range: std::ops::Range<u32>,
+    reference: &Rgb<u8>,
+    tolerance: u8,
+) -> bool {
+    x_range
+        .into_iter()
+        .all(|x| is_similar(image.get_pixel(x, y), reference, tolerance))
+}
+
+fn is_uniform_column(
+    image: &RgbImage,
+    x: u32,
+    y_range: std::ops::Range<u32>,
+    reference: &Rgb<u8>,
+    tolerance: u8,
+) -> bool {
+    y_range
+        .into_iter()
+        .all(|y| is_similar(image.get_pixel(x, y), reference, tolerance))
+}
+
+/// Detect uniform borders on each side of the image.
+///
+/// Returns `None` if there is nothing to trim or the whole image is uniform.
+pub(crate) fn detect_borders(image: &RgbImage, tolerance: u8) -> Option<TrimBox> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    // top and left use the top-left corner, bottom and right use the bottom-right corner
+    let start_ref = *image.get_pixel(0, 0);
+    let end_ref = *image.get_pixel(width - 1, height - 1);
+
+    let mut top = 0;
+    while top < height && is_uniform_row(image, top, 0..width, &start_ref, tolerance) {
+        top += 1;
+    }
+
+    if top == height {
+        // blank page, keep as is
+        return None;
+    }
+
+    let mut bottom = height;
+    while bottom > top && is_uniform_row(image, bottom - 1, 0..width, &end_ref, tolerance) {
+        bottom -= 1;
+    }
+
+    let mut left = 0;
+    while left < width && is_uniform_column(image, left, top..bottom, &start_ref, tolerance) {
+        left += 1;
+    }
+
+    let mut right = width;
+    while right > left && is_uniform_column(image, right - 1, top..bottom, &end_ref, tolerance) {
+        right -= 1;
+    }
+
+    if top == 0 && left == 0 && bottom == height && right == width {
+        return None;
+    }
+
+    Some(TrimBox {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+/// Make both facing pages use the same vertical crop so they keep the same height.
+fn unify_spread(
+    left: Option<TrimBox>,
+    right: Option<TrimBox>,
+    dimensions: (u32, u32),
+) -> (Option<TrimBox>, Option<TrimBox>) {
+    if left.is_none() && right.is_none() {
+        return (None, None);
+    }
+
+    let full_box = TrimBox {
+        x: 0,
+        y: 0,
+        width: dimensions.0,
+        height: dimensions.1,
+    };
+    let left = left.unwrap_or(full_box);
+    let right = right.unwrap_or(full_box);
+
+    let top = left.y.min(right.y);
+    let bottom = (left.y + left.height).max(right.y + right.height);
+
+    let left = TrimBox {
+        y: top,
+        height: bottom - top,
+        ..left
+    };
+    let right = TrimBox {
+        y: top,
+        height: bottom - top,
+        ..right
+    };
+
+    let trimmed = |trim_box: TrimBox| {
+        if trim_box == full_box {
+            None
+        } else {
+            Some(trim_box)
+        }
+    };
+
+    (trimmed(left), trimmed(right))
+}
+
+/// Pair the facing pages with the same size, starting after the `offset` first pages.
+fn pair_spreads(pages: &mut [TrimPage], offset: usize) {
+    let mut idx = offset;
+    while idx + 1 < pages.len() {
+        if pages[idx].dimensions != pages[idx + 1].dimensions {
+            idx += 1;
+            continue;
+        }
+
+        let (left, right) = unify_spread(
+            pages[idx].trim_box,
+            pages[idx + 1].trim_box,
+            pages[idx].dimensions,
+        );
+        pages[idx].trim_box = left;
+        pages[idx + 1].trim_box = right;
+        idx += 2;
+    }
+}
+
+fn is_trimmable(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => TRIMMABLE_IMAGE_EXT.contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+fn save_trimmed_image(path: &Path, trim_box: &TrimBox) -> anyhow::Result<()> {
+    let image = image::open(path)?;
+    let cropped = image.view(trim_box.x, trim_box.y, trim_box.width, trim_box.height);
+    let cropped = image::DynamicImage::from(cropped.to_image());
+
+    match ImageFormat::from_path(path)? {
+        ImageFormat::Jpeg => {
+            let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(writer, 95);
+            cropped.to_rgb8().write_with_encoder(encoder)?;
+        }
+        format => {
+            cropped.save_with_format(path, format)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Trim all the pages in a single chapter directory.
+///
+/// Returns the amount of pages that got trimmed.
+pub(crate) fn trim_chapter_dir(
+    chapter_dir: &Path,
+    config: &ToolsTrimConfig,
+    console: &crate::term::Terminal,
+) -> anyhow::Result<usize> {
+    let mut image_paths: Vec<PathBuf> = std::fs::read_dir(chapter_dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_trimmable(path))
+        .collect();
+    image_paths.sort();
+
+    // first pass, only detect the borders so we don't keep every page in memory
+    let mut pages: Vec<TrimPage> = vec![];
+    for path in image_paths {
+        let image = match image::open(&path) {
+            Ok(image) => image,
+            Err(err) => {
+                console.warn(&format!("   Failed to read {}: {}", path.display(), err));
+                continue;
+            }
+        };
+
+        let dimensions = image.dimensions();
+        let trim_box = detect_borders(&image.to_rgb8(), config.tolerance);
+        pages.push(TrimPage {
+            path,
+            dimensions,
+            trim_box,
+        });
+    }
+
+    if config.spread {
+        pair_spreads(&mut pages, config.spread_offset as usize);
+    }
+
+    let mut trimmed = 0;
+    for page in pages.iter() {
+        if let Some(trim_box) = &page.trim_box {
+            if console.is_debug() {
+                console.log(&cformat!(
+                    "   Trimming <s>{}</> to {}x{} at ({}, {})",
+                    page.path.display(),
+                    trim_box.width,
+                    trim_box.height,
+                    trim_box.x,
+                    trim_box.y
+                ));
+            }
+
+            match save_trimmed_image(&page.path, trim_box) {
+                Ok(_) => trimmed += 1,
+                Err(err) => {
+                    console.error(&format!(
+                        "   Failed to trim {}: {}",
+                        page.path.display(),
+                        err
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(trimmed)
+}
+
+/// Trim the pages of a chapter directory on the blocking threads, since decoding and
+/// encoding the images would stall the async runtime.
+async fn trim_chapter_dir_blocking(
+    chapter_dir: &Path,
+    config: ToolsTrimConfig,
+    console: &crate::term::Terminal,
+) -> anyhow::Result<usize> {
+    let chapter_dir = chapter_dir.to_path_buf();
+    let console = console.clone();
+    tokio::task::spawn_blocking(move || trim_chapter_dir(&chapter_dir, &config, &console)).await?
+}
+
+/// Trim the pages of a freshly downloaded chapter, used as a download post-processor.
+///
+/// The facing pages are paired when the `trimSpread` setting of the source is enabled.
+pub(crate) async fn trim_downloaded_chapter(
+    chapter_dir: &Path,
+    source: Implementations,
+    tolerance: u8,
+    console: &crate::term::Terminal,
+) {
+    let settings = get_settings().resolve(source);
+    let config = ToolsTrimConfig {
+        tolerance,
+        spread: settings.trim_spread.unwrap_or_default(),
+        spread_offset: settings.trim_spread_offset.unwrap_or_default(),
+    };
+
+    match trim_chapter_dir_blocking(chapter_dir, config, console).await {
+        Ok(0) => {}
+        Ok(trimmed) => console.info(&format!("   Trimmed borders on {} pages", trimmed)),
+        Err(err) => console.error(&format!("   Failed to trim pages: {}", err)),
+    }
+}
+
+pub(crate) async fn tools_trim(
+    input_folder: &Path,
+    config: ToolsTrimConfig,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    if !input_folder.is_dir() {
+        console.error("The input folder does not exist.");
+        return 1;
+    }
+
+    // the input folder can be a single chapter or a title folder with chapters
+    let mut chapter_dirs: Vec<PathBuf> = match std::fs::read_dir(input_folder) {
+        Ok(read_dirs) => read_dirs
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(err) => {
+            console.error(&format!("Failed to read input folder: {}", err));
+            return 1;
+        }
+    };
+    chapter_dirs.sort();
+    chapter_dirs.insert(0, input_folder.to_path_buf());
+
+    console.info(&cformat!(
+        "Trimming borders with tolerance <m,s>{}</>...",
+        config.tolerance
+    ));
+
+    let mut total_trimmed = 0;
+    for chapter_dir in chapter_dirs {
+        match trim_chapter_dir_blocking(&chapter_dir, config, console).await {
+            Ok(0) => {}
+            Ok(trimmed) => {
+                console.info(&format!(
+                    "  Trimmed {} pages in {}",
+                    trimmed,
+                    chapter_dir.display()
+                ));
+                total_trimmed += trimmed;
+            }
+            Err(err) => {
+                console.error(&format!(
+                    "  Failed to trim {}: {}",
+                    chapter_dir.display(),
+                    err
+                ));
+            }
+        }
+    }
+
+    console.info(&format!("Trimmed {} pages in total", total_trimmed));
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_bordered_image(border: u32, border_color: [u8; 3]) -> RgbImage {
+        RgbImage::from_fn(100, 80, |x, y| {
+            if x < border || y < border || x >= 100 - border || y >= 80 - border {
+                Rgb(border_color)
+            } else {
+                Rgb([(x * 2) as u8, (y * 3) as u8, 128])
+            }
+        })
+    }
+
+    #[test]
+    fn test_detect_white_borders() {
+        let image = make_bordered_image(10, [255, 255, 255]);
+        let trim_box = detect_borders(&image, DEFAULT_TRIM_TOLERANCE);
+
+        assert_eq!(
+            trim_box,
+            Some(TrimBox {
+                x: 10,
+                y: 10,
+                width: 80,
+                height: 60
+            })
+        );
+    }
+
+    #[test]
+    fn test_detect_black_borders_with_tolerance() {
+        let mut image = make_bordered_image(5, [0, 0, 0]);
+        // add some noise into the border
+        image.put_pixel(2, 2, Rgb([10, 8, 12]));
+
+        let trim_box = detect_borders(&image, DEFAULT_TRIM_TOLERANCE);
+        assert_eq!(trim_box.map(|b| (b.x, b.y)), Some((5, 5)));
+
+        // the noise stops the border detection early
+        let trim_box = detect_borders(&image, 0);
+        assert_eq!(trim_box.map(|b| (b.x, b.y)), Some((2, 2)));
+    }
+
+    #[test]
+    fn test_detect_no_borders() {
+        let image = make_bordered_image(0, [255, 255, 255]);
+        assert_eq!(detect_borders(&image, DEFAULT_TRIM_TOLERANCE), None);
+
+        let blank = RgbImage::from_pixel(20, 20, Rgb([255, 255, 255]));
+        assert_eq!(detect_borders(&blank, DEFAULT_TRIM_TOLERANCE), None);
+    }
+
+    #[test]
+    fn test_unify_spread() {
+        let left = TrimBox {
+            x: 10,
+            y: 5,
+            width: 80,
+            height: 60,
+        };
+        let right = TrimBox {
+            x: 0,
+            y: 12,
+            width: 90,
+            height: 60,
+        };
+
+        let (left, right) = unify_spread(Some(left), Some(right), (100, 80));
+        let (left, right) = (left.unwrap(), right.unwrap());
+
+        assert_eq!(left.y, right.y);
+        assert_eq!(left.height, right.height);
+        assert_eq!(left.y, 5);
+        assert_eq!(left.height, 67);
+        assert_eq!(left.x, 10);
+        assert_eq!(right.width, 90);
+    }
+
+    #[test]
+    fn test_pair_spreads_after_cover() {
+        let page = |y: u32| TrimPage {
+            path: PathBuf::new(),
+            dimensions: (100, 80),
+            trim_box: Some(TrimBox {
+                x: 0,
+                y,
+                width: 100,
+                height: 60,
+            }),
+        };
+
+        // cover, then two facing pages of the same size as the cover
+        let mut pages = vec![page(1), page(5), page(10)];
+        pair_spreads(&mut pages, 1);
+
+        assert_eq!(pages[0].trim_box.unwrap().y, 1);
+        assert_eq!(pages[1].trim_box.unwrap().y, 5);
+        assert_eq!(pages[2].trim_box.unwrap().y, 5);
+        assert_eq!(pages[2].trim_box.unwrap().height, 65);
+    }
+}
@@ -0,0 +1,610 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, TimeZone, Weekday};
+use color_print::cformat;
+use rand::Rng;
+
+use crate::{
+    cli::ExitCode,
+    config::{get_user_path, ConfigImpl},
+    r#impl::{
+        client::{make_kmkc_client, make_musq_client, resolve_single_account},
+        common::{is_shutdown_requested, request_shutdown},
+        config::proxy::ProxyRouter,
+        Implementations,
+    },
+};
+
+use super::{
+    runner::run_watch_entry,
+    watchlist::{WatchEntry, Watchlist},
+};
+
+/// The lock file name inside the config directory.
+pub(crate) const DAEMON_LOCK_FILE: &str = "daemon.lock";
+/// How often the watchlist is reloaded while waiting.
+const RELOAD_INTERVAL_SECS: i64 = 60;
+
+#[derive(Debug, Clone)]
+pub(crate) struct DaemonConfig {
+    /// Check interval in minutes for titles without any schedule.
+    pub(crate) interval: u64,
+    /// Maximum random delay in seconds before each check.
+    pub(crate) jitter: u64,
+    /// Check every title right away on startup.
+    pub(crate) run_now: bool,
+}
+
+/// Convert the standard cron weekday numbers (0-7, Sunday is 0 or 7) into names
+/// since the `cron` crate counts from 1 starting on Sunday.
+fn normalize_weekdays(field: &str) -> String {
+    const NAMES: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+
+    let re = regex::Regex::new(r"(^|[,\-])([0-7])\b").unwrap();
+    re.replace_all(field, |caps: &regex::Captures| {
+        let index: usize = caps[2].parse().unwrap();
+        format!("{}{}", &caps[1], NAMES[index])
+    })
+    .to_string()
+}
+
+/// Parse a cron expression, the standard 5 fields (without seconds) format is supported.
+pub(crate) fn parse_schedule(expr: &str) -> anyhow::Result<cron::Schedule> {
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let normalized = if fields.len() == 5 {
+        format!(
+            "0 {} {} {} {} {}",
+            fields[0],
+            fields[1],
+            fields[2],
+            fields[3],
+            normalize_weekdays(fields[4])
+        )
+    } else {
+        expr.to_string()
+    };
+
+    cron::Schedule::from_str(&normalized).map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// When a watched title should be checked.
+#[derive(Debug, Clone)]
+pub(crate) enum EntrySchedule {
+    /// User provided cron expression, in local time.
+    Cron(Box<cron::Schedule>),
+    /// Weekly release day, checked at the start of the day in JST.
+    Weekly(Weekday),
+    /// Fixed interval for sources without release schedules.
+    Interval(chrono::Duration),
+}
+
+impl EntrySchedule {
+    pub(crate) fn next_after(&self, after: DateTime<Local>) -> DateTime<Local> {
+        match self {
+            EntrySchedule::Cron(schedule) => schedule
+                .after(&after)
+                .next()
+                .unwrap_or(after + chrono::Duration::days(1)),
+            EntrySchedule::Weekly(weekday) => {
+                let jst = FixedOffset::east_opt(9 * 3600).unwrap();
+                let after_jst = after.with_timezone(&jst);
+                let mut date = after_jst.date_naive();
+
+                loop {
+                    if date.weekday() == *weekday {
+                        let start = jst
+                            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                            .unwrap();
+                        if start > after_jst {
+                            return start.with_timezone(&Local);
+                        }
+                    }
+                    date = date.succ_opt().unwrap();
+                }
+            }
+            EntrySchedule::Interval(interval) => after + *interval,
+        }
+    }
+}
+
+/// A lock file with the daemon PID, so only one daemon runs at a time.
+pub(crate) struct DaemonLock {
+    path: PathBuf,
+}
+
+fn is_process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let pid = match libc::pid_t::try_from(pid) {
+            Ok(pid) => pid,
+            Err(_) => return false,
+        };
+
+        // signal 0 only checks the process, EPERM means it runs as another user
+        let result = unsafe { libc::kill(pid, 0) };
+        result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::{
+            Foundation::{CloseHandle, GetLastError, ERROR_ACCESS_DENIED, STILL_ACTIVE},
+            System::Threading::{
+                GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+            },
+        };
+
+        let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if handle == 0 {
+            return unsafe { GetLastError() } == ERROR_ACCESS_DENIED;
+        }
+
+        let mut exit_code = 0;
+        let queried = unsafe { GetExitCodeProcess(handle, &mut exit_code) } != 0;
+        unsafe { CloseHandle(handle) };
+        !queried || exit_code == STILL_ACTIVE as u32
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        // no way to check, assume it's still running
+        let _ = pid;
+        true
+    }
+}
+
+impl DaemonLock {
+    pub(crate) fn acquire(path: &Path) -> anyhow::Result<Self> {
+        if let Ok(content) = std::fs::read_to_string(path) {
+            match content.trim().parse::<u32>() {
+                Ok(pid) if pid != std::process::id() && is_process_alive(pid) => {
+                    anyhow::bail!(
+                        "Another daemon is already running (PID {}), remove {} if it is not",
+                        pid,
+                        path.display()
+                    );
+                }
+                _ => {
+                    // stale lock
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        std::io::Write::write_all(&mut file, std::process::id().to_string().as_bytes())?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for DaemonLock {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).unwrap_or_default();
+    }
+}
+
+/// Listen for Ctrl+C, and SIGTERM on unix.
+///
+/// The listeners are registered once, creating them again would see the
+/// signal that was already delivered.
+struct ShutdownSignal {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl ShutdownSignal {
+    fn new() -> std::io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            Ok(Self {
+                interrupt: signal(SignalKind::interrupt())?,
+                terminate: signal(SignalKind::terminate())?,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(Self {})
+        }
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.interrupt.recv() => {}
+                _ = self.terminate.recv() => {}
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            tokio::signal::ctrl_c().await.unwrap_or_default();
+        }
+    }
+}
+
+fn spawn_shutdown_listener(
+    lock_path: PathBuf,
+    console: crate::term::Terminal,
+) -> std::io::Result<()> {
+    let mut signal = ShutdownSignal::new()?;
+    tokio::spawn(async move {
+        signal.recv().await;
+        console.warn("Shutting down after the in-flight pages are finished, press Ctrl+C again to force quit...");
+        request_shutdown();
+
+        signal.recv().await;
+        console.warn("Forced shutdown!");
+        std::fs::remove_file(&lock_path).unwrap_or_default();
+        std::process::exit(130);
+    });
+
+    Ok(())
+}
+
+/// Sleep for the given duration, or until a shutdown is requested.
+async fn sleep_or_shutdown(duration: std::time::Duration) {
+    let wait_shutdown = async {
+        while !is_shutdown_requested() {
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {}
+        _ = wait_shutdown => {}
+    }
+}
+
+fn weekday_from_index(index: i32) -> Option<Weekday> {
+    match index {
+        1 => Some(Weekday::Mon),
+        2 => Some(Weekday::Tue),
+        3 => Some(Weekday::Wed),
+        4 => Some(Weekday::Thu),
+        5 => Some(Weekday::Fri),
+        6 => Some(Weekday::Sat),
+        7 => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Fetch the weekly release day of the watched KM and MU titles.
+/// The account of the first title of the source following the weekly schedule.
+fn weekly_account(
+    entries: &[WatchEntry],
+    source: Implementations,
+    console: &crate::term::Terminal,
+) -> Option<ConfigImpl> {
+    let entry = entries
+        .iter()
+        .find(|e| e.source == source && e.schedule.is_none())?;
+
+    match resolve_single_account(Some(&entry.account_id), source, console) {
+        Ok(config) => Some(config),
+        Err(err) => {
+            console.warn(&format!(
+                "Failed to fetch {} weekly schedule: {}",
+                source.source_name(),
+                err
+            ));
+            None
+        }
+    }
+}
+
+async fn fetch_weekly_schedule(
+    entries: &[WatchEntry],
+    proxies: &ProxyRouter,
+    console: &crate::term::Terminal,
+) -> HashMap<(Implementations, String), Weekday> {
+    let mut weekly = HashMap::new();
+
+    if let Some(ConfigImpl::Kmkc(config)) = weekly_account(entries, Implementations::Kmkc, console)
+    {
+        let proxy = proxies
+            .for_account(Implementations::Kmkc, config.get_id())
+            .await;
+        let client = make_kmkc_client(&config.into());
+        let client = match proxy {
+            Some(proxy) => client.with_proxy(proxy),
+            None => client,
+        };
+
+        match client.get_weekly().await {
+            Ok(results) => {
+                for content in results.contents {
+                    if let Some(weekday) = weekday_from_index(content.weekday) {
+                        for title_id in content.titles {
+                            weekly.insert((Implementations::Kmkc, title_id.to_string()), weekday);
+                        }
+                    }
+                }
+            }
+            Err(e) => console.warn(&format!("Failed to fetch KM weekly schedule: {}", e)),
+        }
+    }
+
+    if let Some(ConfigImpl::Musq(config)) = weekly_account(entries, Implementations::Musq, console)
+    {
+        let client = make_musq_client(&config);
+        let client = match proxies.for_account(Implementations::Musq, &config.id).await {
+            Some(proxy) => client.with_proxy(proxy),
+            None => client,
+        };
+
+        let weekdays = [
+            (airpope_musq::WeeklyCode::Monday, Weekday::Mon),
+            (airpope_musq::WeeklyCode::Tuesday, Weekday::Tue),
+            (airpope_musq::WeeklyCode::Wednesday, Weekday::Wed),
+            (airpope_musq::WeeklyCode::Thursday, Weekday::Thu),
+            (airpope_musq::WeeklyCode::Friday, Weekday::Fri),
+            (airpope_musq::WeeklyCode::Saturday, Weekday::Sat),
+            (airpope_musq::WeeklyCode::Sunday, Weekday::Sun),
+        ];
+        for (code, weekday) in weekdays {
+            match client.get_weekly_titles(code).await {
+                Ok(results) => {
+                    for title in results.titles {
+                        weekly.insert((Implementations::Musq, title.id.to_string()), weekday);
+                    }
+                }
+                Err(e) => {
+                    console.warn(&format!("Failed to fetch MU weekly schedule: {}", e));
+                    break;
+                }
+            }
+        }
+    }
+
+    weekly
+}
+
+fn entry_schedule(
+    entry: &WatchEntry,
+    weekly: &HashMap<(Implementations, String), Weekday>,
+    interval: chrono::Duration,
+) -> anyhow::Result<EntrySchedule> {
+    if let Some(expr) = &entry.schedule {
+        return Ok(EntrySchedule::Cron(Box::new(parse_schedule(expr)?)));
+    }
+
+    match weekly.get(&(entry.source, entry.title_id.clone())) {
+        Some(weekday) => Ok(EntrySchedule::Weekly(*weekday)),
+        None => Ok(EntrySchedule::Interval(interval)),
+    }
+}
+
+fn format_time(time: &DateTime<Local>) -> String {
+    time.format("%Y-%m-%d %H:%M").to_string()
+}
+
+pub(crate) async fn daemon_run(
+    config: DaemonConfig,
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    let lock_path = get_user_path().join(DAEMON_LOCK_FILE);
+    let _lock = match DaemonLock::acquire(&lock_path) {
+        Ok(lock) => lock,
+        Err(err) => {
+            console.error(&format!("Unable to start the daemon: {}", err));
+            return 1;
+        }
+    };
+
+    if let Err(err) = spawn_shutdown_listener(lock_path, console.clone()) {
+        console.error(&format!("Unable to listen for shutdown signals: {}", err));
+        return 1;
+    }
+    console.info(&cformat!(
+        "Daemon started, checking titles every <m,s>{}</> minutes (up to <s>{}</>s jitter)",
+        config.interval,
+        config.jitter
+    ));
+
+    let interval = chrono::Duration::minutes(config.interval as i64);
+    let mut entries: Vec<WatchEntry> = vec![];
+    let mut next_runs: HashMap<(Implementations, String), DateTime<Local>> = HashMap::new();
+    let mut weekly: HashMap<(Implementations, String), Weekday> = HashMap::new();
+    let mut weekly_fetched_at: Option<DateTime<Local>> = None;
+
+    while !is_shutdown_requested() {
+        // reload every loop so watchlist edits apply without a restart
+        match Watchlist::load(&Watchlist::default_path()) {
+            Ok(watchlist) => entries = watchlist.entries,
+            Err(err) => console.warn(&format!("Failed to reload the watchlist: {}", err)),
+        }
+
+        let now = Local::now();
+        let weekly_stale = weekly_fetched_at
+            .map(|fetched| now - fetched > chrono::Duration::days(1))
+            .unwrap_or(true);
+        if weekly_stale && !entries.is_empty() {
+            weekly = fetch_weekly_schedule(&entries, proxies, console).await;
+            weekly_fetched_at = Some(now);
+        }
+
+        next_runs.retain(|(source, title_id), _| {
+            entries.iter().any(|e| e.is_same_title(*source, title_id))
+        });
+
+        let mut due = vec![];
+        for entry in entries.iter() {
+            let schedule = match entry_schedule(entry, &weekly, interval) {
+                Ok(schedule) => schedule,
+                Err(err) => {
+                    console.warn(&format!(
+                        "Invalid schedule for {} {}: {}",
+                        entry.source.source_name(),
+                        entry.title_id,
+                        err
+                    ));
+                    continue;
+                }
+            };
+
+            let key = (entry.source, entry.title_id.clone());
+            let next_run = *next_runs.entry(key.clone()).or_insert_with(|| {
+                let next_run = if config.run_now {
+                    now
+                } else {
+                    schedule.next_after(now)
+                };
+                console.info(&cformat!(
+                    "<m,s>{}</> title <s>{}</> next check at {}",
+                    entry.source.source_name(),
+                    entry.title_id,
+                    format_time(&next_run)
+                ));
+                next_run
+            });
+
+            if next_run <= now {
+                due.push((entry.clone(), schedule));
+            }
+        }
+
+        if due.is_empty() {
+            let next_wake = next_runs
+                .values()
+                .min()
+                .copied()
+                .unwrap_or(now + chrono::Duration::seconds(RELOAD_INTERVAL_SECS));
+            let wait = (next_wake - now)
+                .min(chrono::Duration::seconds(RELOAD_INTERVAL_SECS))
+                .to_std()
+                .unwrap_or(std::time::Duration::from_secs(1));
+            sleep_or_shutdown(wait).await;
+            continue;
+        }
+
+        for (entry, schedule) in due {
+            if is_shutdown_requested() {
+                break;
+            }
+
+            if config.jitter > 0 {
+                let delay = rand::thread_rng().gen_range(0..=config.jitter);
+                console.info(&format!(
+                    "Waiting {}s before checking {} {}...",
+                    delay,
+                    entry.source.source_name(),
+                    entry.title_id
+                ));
+                sleep_or_shutdown(std::time::Duration::from_secs(delay)).await;
+                if is_shutdown_requested() {
+                    break;
+                }
+            }
+
+            console.info(&cformat!(
+                "Checking <m,s>{}</> title <s>{}</>...",
+                entry.source.source_name(),
+                entry.title_id
+            ));
+            if run_watch_entry(&entry, proxies, console).await != 0 {
+                console.warn(&format!(
+                    "Failed to process {} {}",
+                    entry.source.source_name(),
+                    entry.title_id
+                ));
+            }
+
+            let next_run = schedule.next_after(Local::now());
+            console.info(&cformat!(
+                "<m,s>{}</> title <s>{}</> next check at {}",
+                entry.source.source_name(),
+                entry.title_id,
+                format_time(&next_run)
+            ));
+            next_runs.insert((entry.source, entry.title_id.clone()), next_run);
+        }
+    }
+
+    console.info("Daemon stopped");
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_standard_cron() {
+        let schedule = parse_schedule("30 9 * * 1-5").unwrap();
+        let after = Local.with_ymd_and_hms(2024, 5, 4, 12, 0, 0).unwrap(); // Saturday
+        let next = schedule.after(&after).next().unwrap();
+
+        assert_eq!(next.weekday(), Weekday::Mon);
+        assert_eq!(next.format("%H:%M").to_string(), "09:30");
+
+        assert_eq!(normalize_weekdays("0,3"), "SUN,WED");
+        assert_eq!(normalize_weekdays("*/2"), "*/2");
+        assert!(parse_schedule("not a cron").is_err());
+    }
+
+    #[test]
+    fn test_weekly_next_after() {
+        let jst = FixedOffset::east_opt(9 * 3600).unwrap();
+        // Wednesday noon in JST
+        let after = jst
+            .with_ymd_and_hms(2024, 5, 8, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Local);
+
+        let next = EntrySchedule::Weekly(Weekday::Wed).next_after(after);
+        let next = next.with_timezone(&jst);
+        assert_eq!(
+            next.format("%Y-%m-%d %H:%M").to_string(),
+            "2024-05-15 00:00"
+        );
+
+        let next = EntrySchedule::Weekly(Weekday::Thu).next_after(after);
+        let next = next.with_timezone(&jst);
+        assert_eq!(
+            next.format("%Y-%m-%d %H:%M").to_string(),
+            "2024-05-09 00:00"
+        );
+    }
+
+    #[test]
+    fn test_daemon_lock() {
+        let lock_path =
+            std::env::temp_dir().join(format!("airpope-daemon-{}.lock", std::process::id()));
+
+        let lock = DaemonLock::acquire(&lock_path).unwrap();
+        assert!(lock_path.exists());
+        drop(lock);
+        assert!(!lock_path.exists());
+
+        // stale lock from a dead process
+        std::fs::write(&lock_path, "4294967295").unwrap();
+        let lock = DaemonLock::acquire(&lock_path);
+        assert!(lock.is_ok());
+        drop(lock);
+
+        // init is always running
+        #[cfg(unix)]
+        {
+            std::fs::write(&lock_path, "1").unwrap();
+            assert!(DaemonLock::acquire(&lock_path).is_err());
+        }
+        std::fs::remove_file(&lock_path).unwrap_or_default();
+    }
+}
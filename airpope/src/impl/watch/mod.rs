@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use clap::Subcommand;
+
+use super::Implementations;
+use super::tools::trim::DEFAULT_TRIM_TOLERANCE;
+
+pub(crate) mod daemon;
+pub(crate) mod runner;
+pub(crate) mod watchlist;
+
+#[derive(Subcommand)]
+pub(crate) enum WatchCommands {
+    /// Add a title to the watchlist
+    Add {
+        /// The source of the title
+        #[arg(value_enum)]
+        source: Implementations,
+        /// Title ID (or UUID/slug) to watch
+        title_id: String,
+        /// Account ID to use, will prompt if there is more than one account
+        #[arg(short = 'a', long = "account", default_value = None)]
+        account_id: Option<String>,
+        /// Output directory to use, default to the current directory DOWNLOADS folder
+        #[arg(short = 'o', long = "output", default_value = None)]
+        output: Option<PathBuf>,
+        /// How chapters should be purchased when downloading
+        #[arg(short = 'p', long = "purchase", value_enum, default_value = "never")]
+        purchase: watchlist::PurchasePolicy,
+        /// Trim uniform borders from the downloaded pages with the given tolerance
+        #[arg(long = "trim", num_args = 0..=1, default_missing_value = DEFAULT_TRIM_TOLERANCE.to_string(), value_name = "TOLERANCE")]
+        trim: Option<u8>,
+        /// Cron expression for the daemon to check this title (ex: "0 9 * * MON")
+        ///
+        /// Default to the weekly release day for KM and MU, or the daemon interval otherwise.
+        #[arg(short = 's', long = "schedule", default_value = None)]
+        schedule: Option<String>,
+    },
+    /// Remove a title from the watchlist
+    Remove {
+        /// The source of the title
+        #[arg(value_enum)]
+        source: Implementations,
+        /// Title ID (or UUID/slug) to remove
+        title_id: String,
+    },
+    /// List all the titles in the watchlist
+    List,
+    /// Download new chapters for every title in the watchlist
+    Run,
+}
@@ -0,0 +1,72 @@
+use color_print::cformat;
+
+use crate::{
+    cli::ExitCode,
+    r#impl::{
+        batch::{manifest::BatchJob, runner::run_batch_job},
+        config::proxy::ProxyRouter,
+        queue::QueueTracker,
+    },
+};
+
+use super::watchlist::{WatchEntry, Watchlist};
+
+/// Process a single watched title through the autodownload path of its source.
+pub(crate) async fn run_watch_entry(
+    entry: &WatchEntry,
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    run_batch_job(
+        &BatchJob::from(entry),
+        QueueTracker::default(),
+        proxies,
+        console,
+    )
+    .await
+}
+
+pub(crate) async fn watch_run(
+    proxies: &ProxyRouter,
+    console: &mut crate::term::Terminal,
+) -> ExitCode {
+    let watchlist = match Watchlist::load(&Watchlist::default_path()) {
+        Ok(watchlist) => watchlist,
+        Err(err) => {
+            console.error(&format!("Failed to read the watchlist: {}", err));
+            return 1;
+        }
+    };
+
+    if watchlist.entries.is_empty() {
+        console.warn("The watchlist is empty!");
+        return 0;
+    }
+
+    let total = watchlist.entries.len();
+    let mut failed = 0;
+    for (i, entry) in watchlist.entries.iter().enumerate() {
+        console.info(&cformat!(
+            "[{}/{}] Processing <m,s>{}</> title <s>{}</>...",
+            i + 1,
+            total,
+            entry.source.source_name(),
+            entry.title_id
+        ));
+
+        if run_watch_entry(entry, proxies, console).await != 0 {
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        console.warn(&format!(
+            "{} of {} titles did not finish successfully",
+            failed, total
+        ));
+        return 1;
+    }
+
+    console.info(&format!("Processed {} titles", total));
+    0
+}
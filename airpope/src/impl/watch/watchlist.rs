@@ -0,0 +1,318 @@
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use color_print::cformat;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::ExitCode,
+    config::get_user_path,
+    r#impl::{client::select_single_account, Implementations},
+};
+
+/// The watchlist file name inside the config directory.
+pub(crate) const WATCHLIST_FILE: &str = "watchlist.json";
+
+/// How chapters should be purchased when downloading a watched title.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PurchasePolicy {
+    /// Only download free or already purchased chapters
+    #[default]
+    Never,
+    /// Purchase chapters with free currency only (tickets, free or event points)
+    Free,
+    /// Purchase chapters with any currency
+    All,
+}
+
+impl PurchasePolicy {
+    pub(crate) fn to_name(self) -> &'static str {
+        match self {
+            PurchasePolicy::Never => "never",
+            PurchasePolicy::Free => "free",
+            PurchasePolicy::All => "all",
+        }
+    }
+}
+
+/// A single watched title.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WatchEntry {
+    pub(crate) source: Implementations,
+    pub(crate) account_id: String,
+    /// The title ID, UUID or slug depending on the source.
+    pub(crate) title_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output: Option<PathBuf>,
+    #[serde(default)]
+    pub(crate) purchase: PurchasePolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) trim: Option<u8>,
+    /// Cron expression used by the daemon to check this title.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) schedule: Option<String>,
+}
+
+impl WatchEntry {
+    pub(crate) fn is_same_title(&self, source: Implementations, title_id: &str) -> bool {
+        self.source == source && self.title_id == title_id
+    }
+}
+
+/// The list of watched titles, stored as `watchlist.json` in the config directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Watchlist {
+    pub(crate) entries: Vec<WatchEntry>,
+}
+
+impl Watchlist {
+    pub(crate) fn default_path() -> PathBuf {
+        get_user_path().join(WATCHLIST_FILE)
+    }
+
+    /// Load the watchlist, a missing file is an empty watchlist.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let watchlist: Watchlist = serde_json::from_str(&content)?;
+        Ok(watchlist)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Add or replace the entry for the same source and title.
+    ///
+    /// Returns `true` if an existing entry got replaced.
+    pub(crate) fn upsert(&mut self, entry: WatchEntry) -> bool {
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.is_same_title(entry.source, &entry.title_id))
+        {
+            Some(existing) => {
+                *existing = entry;
+                true
+            }
+            None => {
+                self.entries.push(entry);
+                false
+            }
+        }
+    }
+
+    /// Remove the entry for the source and title, returns `true` if anything got removed.
+    pub(crate) fn remove(&mut self, source: Implementations, title_id: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|e| !e.is_same_title(source, title_id));
+        self.entries.len() != before
+    }
+}
+
+fn load_watchlist(console: &crate::term::Terminal) -> Option<Watchlist> {
+    match Watchlist::load(&Watchlist::default_path()) {
+        Ok(watchlist) => Some(watchlist),
+        Err(err) => {
+            console.error(&format!("Failed to read the watchlist: {}", err));
+            None
+        }
+    }
+}
+
+fn save_watchlist(watchlist: &Watchlist, console: &crate::term::Terminal) -> ExitCode {
+    match watchlist.save(&Watchlist::default_path()) {
+        Ok(_) => 0,
+        Err(err) => {
+            console.error(&format!("Failed to save the watchlist: {}", err));
+            1
+        }
+    }
+}
+
+/// Add a title to the watchlist, an empty account ID will prompt for the account.
+pub(crate) fn watch_add(mut entry: WatchEntry, console: &crate::term::Terminal) -> ExitCode {
+    if let Some(schedule) = &entry.schedule {
+        if let Err(err) = super::daemon::parse_schedule(schedule) {
+            console.error(&format!("Invalid schedule {}: {}", schedule, err));
+            return 1;
+        }
+    }
+
+    let mut watchlist = match load_watchlist(console) {
+        Some(watchlist) => watchlist,
+        None => return 1,
+    };
+
+    // resolve the account now so `watch run` never need to prompt
+    let account_id = Some(entry.account_id.as_str()).filter(|id| !id.is_empty());
+    let account = match select_single_account(account_id, entry.source, console) {
+        Some(account) => account,
+        None => {
+            console.warn("Aborted!");
+            return 1;
+        }
+    };
+    entry.account_id = account.get_id().to_string();
+
+    // store absolute path since `watch run` can be called from anywhere
+    entry.output = entry.output.map(|output| {
+        if output.is_relative() {
+            std::env::current_dir()
+                .map(|cwd| cwd.join(&output))
+                .unwrap_or(output)
+        } else {
+            output
+        }
+    });
+
+    let source = entry.source;
+    let title_id = entry.title_id.clone();
+    let replaced = watchlist.upsert(entry);
+    let exit_code = save_watchlist(&watchlist, console);
+    if exit_code == 0 {
+        let action = if replaced { "Updated" } else { "Added" };
+        console.info(&cformat!(
+            "{} <m,s>{}</> title <s>{}</> in the watchlist",
+            action,
+            source.source_name(),
+            title_id
+        ));
+    }
+
+    exit_code
+}
+
+pub(crate) fn watch_remove(
+    source: Implementations,
+    title_id: String,
+    console: &crate::term::Terminal,
+) -> ExitCode {
+    let mut watchlist = match load_watchlist(console) {
+        Some(watchlist) => watchlist,
+        None => return 1,
+    };
+
+    if !watchlist.remove(source, &title_id) {
+        console.warn(&cformat!(
+            "<m,s>{}</> title <s>{}</> is not in the watchlist",
+            source.source_name(),
+            title_id
+        ));
+        return 1;
+    }
+
+    let exit_code = save_watchlist(&watchlist, console);
+    if exit_code == 0 {
+        console.info(&cformat!(
+            "Removed <m,s>{}</> title <s>{}</> from the watchlist",
+            source.source_name(),
+            title_id
+        ));
+    }
+
+    exit_code
+}
+
+pub(crate) fn watch_list(console: &crate::term::Terminal) -> ExitCode {
+    let watchlist = match load_watchlist(console) {
+        Some(watchlist) => watchlist,
+        None => return 1,
+    };
+
+    if watchlist.entries.is_empty() {
+        console.warn("The watchlist is empty!");
+        return 0;
+    }
+
+    console.info(&format!(
+        "Found {} titles in the watchlist:",
+        watchlist.entries.len()
+    ));
+    for (i, entry) in watchlist.entries.iter().enumerate() {
+        let mut extra = format!("purchase: {}", entry.purchase.to_name());
+        if let Some(trim) = entry.trim {
+            extra.push_str(&format!(", trim: {}", trim));
+        }
+        if let Some(schedule) = &entry.schedule {
+            extra.push_str(&format!(", schedule: {}", schedule));
+        }
+
+        console.info(&cformat!(
+            "{:02}. <m,s>{}</> <s>{}</> — {} ({})",
+            i + 1,
+            entry.source.source_name(),
+            entry.title_id,
+            entry.account_id,
+            extra
+        ));
+        if let Some(output) = &entry.output {
+            console.info(&format!("    Output: {}", output.display()));
+        }
+    }
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_entry(source: Implementations, title_id: &str) -> WatchEntry {
+        WatchEntry {
+            source,
+            account_id: "account".to_string(),
+            title_id: title_id.to_string(),
+            output: None,
+            purchase: PurchasePolicy::Never,
+            trim: None,
+            schedule: None,
+        }
+    }
+
+    #[test]
+    fn test_watchlist_upsert_remove() {
+        let mut watchlist = Watchlist::default();
+
+        assert!(!watchlist.upsert(make_entry(Implementations::Kmkc, "100")));
+        assert!(!watchlist.upsert(make_entry(Implementations::Musq, "100")));
+
+        let mut replacement = make_entry(Implementations::Kmkc, "100");
+        replacement.purchase = PurchasePolicy::Free;
+        assert!(watchlist.upsert(replacement));
+        assert_eq!(watchlist.entries.len(), 2);
+        assert_eq!(watchlist.entries[0].purchase, PurchasePolicy::Free);
+
+        assert!(watchlist.remove(Implementations::Musq, "100"));
+        assert!(!watchlist.remove(Implementations::Musq, "100"));
+        assert_eq!(watchlist.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_watchlist_deserialize() {
+        let content = r#"{
+            "entries": [
+                {"source": "rb", "accountId": "abc", "titleId": "some-uuid", "purchase": "all"},
+                {"source": "km", "accountId": "def", "titleId": "123", "trim": 10}
+            ]
+        }"#;
+
+        let watchlist: Watchlist = serde_json::from_str(content).unwrap();
+        assert_eq!(watchlist.entries.len(), 2);
+        assert_eq!(watchlist.entries[0].source, Implementations::Rbean);
+        assert_eq!(watchlist.entries[0].purchase, PurchasePolicy::All);
+        assert_eq!(watchlist.entries[1].purchase, PurchasePolicy::Never);
+        assert_eq!(watchlist.entries[1].trim, Some(10));
+    }
+}
@@ -52,15 +52,28 @@ use std::path::PathBuf;
 
 use clap::Parser;
 use cli::ToshoCommands;
+use r#impl::accounts::aliases::get_aliases;
+use r#impl::accounts::AccountsCommands;
 use r#impl::amap::download::AMDownloadCliConfig;
 use r#impl::amap::AMAPCommands;
+use r#impl::batch::BatchCommands;
+use r#impl::budget::BudgetCommands;
 use r#impl::client::select_single_account;
-use r#impl::parser::WeeklyCodeCli;
+use r#impl::config::{
+    proxy::ProxyRouter,
+    settings::{get_settings, try_get_settings},
+};
+use r#impl::config::ConfigCommands;
+use r#impl::hooks::PostHooks;
+use r#impl::parser::{flag_pair, WeeklyCodeCli};
+use r#impl::queue::QueueCommands;
 use r#impl::rbean::download::RBDownloadConfigCli;
 use r#impl::rbean::RBeanCommands;
 use r#impl::sjv::download::SJDownloadCliConfig;
 use r#impl::sjv::SJVCommands;
 use r#impl::tools::ToolsCommands;
+use r#impl::notify::NotifyCommands;
+use r#impl::watch::WatchCommands;
 use r#impl::Implementations;
 use r#impl::{kmkc::download::KMDownloadCliConfig, musq::download::MUDownloadCliConfig};
 use r#impl::{kmkc::KMKCCommands, musq::MUSQCommands};
@@ -89,15 +102,32 @@ async fn main() {
     let t = term::get_console(_cli.verbose);
     let mut t_mut = term::get_console(_cli.verbose);
 
-    let parsed_proxy = match _cli.proxy {
-        Some(proxy) => match reqwest::Proxy::all(proxy) {
-            Ok(proxy) => Some(proxy),
-            Err(e) => {
-                t.warn(&format!("Unable to parse proxy: {}", e));
-                std::process::exit(1);
-            }
-        },
-        None => None,
+    match config::resolve_user_path(_cli.config_dir.clone(), _cli.profile.clone(), _cli.portable) {
+        Ok(user_path) => config::init_user_path(user_path),
+        Err(e) => {
+            t.error(&format!("Unable to use the config directory: {}", e));
+            std::process::exit(1);
+        }
+    }
+
+    let settings = match try_get_settings() {
+        Ok(settings) => settings,
+        // the config commands can fix the settings file
+        Err(e) if matches!(_cli.command, ToshoCommands::Config { .. }) => {
+            t.warn(&e.to_string());
+            get_settings()
+        }
+        Err(e) => {
+            t.error(&e.to_string());
+            std::process::exit(1);
+        }
+    };
+    let proxies = match ProxyRouter::new(_cli.proxy.clone(), settings, &t) {
+        Ok(proxies) => proxies,
+        Err(e) => {
+            t.warn(&format!("Unable to parse proxy: {}", e));
+            std::process::exit(1);
+        }
     };
 
     check_for_update(&t).await.unwrap_or_else(|e| {
@@ -109,6 +139,7 @@ async fn main() {
             account_id,
             subcommand,
         } => {
+            let source_settings = settings.resolve(Implementations::Musq);
             let early_exit = match subcommand.clone() {
                 MUSQCommands::Auth { session_id, r#type } => {
                     Some(r#impl::musq::accounts::musq_auth_session(session_id, r#type, &t).await)
@@ -135,7 +166,8 @@ async fn main() {
             };
 
             let client = r#impl::client::make_musq_client(&config);
-            let client = if let Some(proxy) = parsed_proxy {
+            let proxy = proxies.for_account(Implementations::Musq, &config.id).await;
+            let client = if let Some(proxy) = proxy {
                 client.with_proxy(proxy)
             } else {
                 client
@@ -153,29 +185,47 @@ async fn main() {
                 MUSQCommands::AutoDownload {
                     title_id,
                     no_purchase,
+                    purchase,
                     start_from,
                     end_until,
                     no_paid_coins,
+                    paid_coins,
                     no_xp_coins,
+                    xp_coins,
                     quality,
                     output,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let mu_config = MUDownloadCliConfig {
-                        auto_purchase: !no_purchase,
+                        auto_purchase: !flag_pair(no_purchase, purchase)
+                            .or(source_settings.no_purchase)
+                            .unwrap_or_default(),
                         no_input: true,
-                        quality,
+                        quality: quality.unwrap_or_else(|| source_settings.image_quality()),
                         start_from,
                         end_at: end_until,
-                        no_paid_point: no_paid_coins,
-                        no_xp_point: no_xp_coins,
+                        no_paid_point: flag_pair(no_paid_coins, paid_coins)
+                            .or(source_settings.no_paid)
+                            .unwrap_or_default(),
+                        no_xp_point: flag_pair(no_xp_coins, xp_coins)
+                            .or(source_settings.no_xp)
+                            .unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Musq, &config.id),
                         ..Default::default()
                     };
 
                     r#impl::musq::download::musq_download(
                         title_id,
                         mu_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &client,
+                        &config,
                         &mut t_mut,
                     )
                     .await
@@ -190,20 +240,29 @@ async fn main() {
                     auto_purchase,
                     quality,
                     output,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let mu_config = MUDownloadCliConfig {
                         auto_purchase,
                         show_all,
                         chapter_ids: chapters.unwrap_or_default(),
-                        quality,
+                        quality: quality.unwrap_or_else(|| source_settings.image_quality()),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Musq, &config.id),
                         ..Default::default()
                     };
 
                     r#impl::musq::download::musq_download(
                         title_id,
                         mu_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &client,
+                        &config,
                         &mut t_mut,
                     )
                     .await
@@ -241,6 +300,21 @@ async fn main() {
                 MUSQCommands::Search { query } => {
                     r#impl::musq::manga::musq_search(query.as_str(), &client, &t).await
                 }
+                MUSQCommands::Sync {
+                    title_id,
+                    download,
+                    output,
+                } => {
+                    r#impl::musq::download::musq_sync(
+                        title_id,
+                        download,
+                        source_settings.output_dir(output),
+                        &client,
+                        &config,
+                        &mut t_mut,
+                    )
+                    .await
+                }
                 MUSQCommands::Weekly { weekday } => {
                     let weekday: WeeklyCode = match weekday {
                         Some(week) => week.into(),
@@ -257,6 +331,7 @@ async fn main() {
             account_id,
             subcommand,
         } => {
+            let source_settings = settings.resolve(Implementations::Kmkc);
             let early_exit = match subcommand.clone() {
                 KMKCCommands::Auth {
                     email,
@@ -303,7 +378,10 @@ async fn main() {
             };
 
             let client = r#impl::client::make_kmkc_client(&config.clone().into());
-            let client = if let Some(proxy) = parsed_proxy {
+            let proxy = proxies
+                .for_account(Implementations::Kmkc, config.get_id())
+                .await;
+            let client = if let Some(proxy) = proxy {
                 client.with_proxy(proxy)
             } else {
                 client
@@ -329,28 +407,48 @@ async fn main() {
                 KMKCCommands::AutoDownload {
                     title_id,
                     no_purchase,
+                    purchase,
                     start_from,
                     end_until,
                     no_ticket,
+                    ticket,
                     no_point,
+                    point,
                     output,
                     parallel,
+                    no_parallel,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let main_config = KMDownloadCliConfig {
-                        auto_purchase: !no_purchase,
+                        auto_purchase: !flag_pair(no_purchase, purchase)
+                            .or(source_settings.no_purchase)
+                            .unwrap_or_default(),
                         no_input: true,
                         start_from,
                         end_at: end_until,
-                        no_point,
-                        no_ticket,
-                        parallel,
+                        no_point: flag_pair(no_point, point)
+                            .or(source_settings.no_point)
+                            .unwrap_or_default(),
+                        no_ticket: flag_pair(no_ticket, ticket)
+                            .or(source_settings.no_ticket)
+                            .unwrap_or_default(),
+                        parallel: flag_pair(parallel, no_parallel)
+                            .or(source_settings.parallel)
+                            .unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Kmkc, config.get_id()),
                         ..Default::default()
                     };
 
                     r#impl::kmkc::download::kmkc_download(
                         title_id,
                         main_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &client,
                         &config,
                         &mut t_mut,
@@ -367,19 +465,30 @@ async fn main() {
                     auto_purchase,
                     output,
                     parallel,
+                    no_parallel,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let main_config = KMDownloadCliConfig {
                         auto_purchase,
                         show_all,
                         chapter_ids: chapters.unwrap_or_default(),
-                        parallel,
+                        parallel: flag_pair(parallel, no_parallel)
+                            .or(source_settings.parallel)
+                            .unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Kmkc, config.get_id()),
                         ..Default::default()
                     };
 
                     r#impl::kmkc::download::kmkc_download(
                         title_id,
                         main_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &client,
                         &config,
                         &mut t_mut,
@@ -419,6 +528,21 @@ async fn main() {
                 KMKCCommands::Search { query } => {
                     r#impl::kmkc::manga::kmkc_search(query.as_str(), &client, &t).await
                 }
+                KMKCCommands::Sync {
+                    title_id,
+                    download,
+                    output,
+                } => {
+                    r#impl::kmkc::download::kmkc_sync(
+                        title_id,
+                        download,
+                        source_settings.output_dir(output),
+                        &client,
+                        &config,
+                        &mut t_mut,
+                    )
+                    .await
+                }
                 KMKCCommands::Weekly { weekday } => {
                     let weekday: WeeklyCodeCli = match weekday {
                         Some(week) => week,
@@ -435,6 +559,7 @@ async fn main() {
             account_id,
             subcommand,
         } => {
+            let source_settings = settings.resolve(Implementations::Amap);
             let early_exit = match subcommand.clone() {
                 AMAPCommands::Auth { email, password } => {
                     Some(r#impl::amap::accounts::amap_account_login(email, password, &t).await)
@@ -461,7 +586,8 @@ async fn main() {
             };
 
             let client = r#impl::client::make_amap_client(&config.clone().into());
-            let client = if let Some(proxy) = parsed_proxy {
+            let proxy = proxies.for_account(Implementations::Amap, &config.id).await;
+            let client = if let Some(proxy) = proxy {
                 client.with_proxy(proxy)
             } else {
                 client
@@ -479,26 +605,43 @@ async fn main() {
                 AMAPCommands::AutoDownload {
                     title_id,
                     no_purchase,
+                    purchase,
                     start_from,
                     end_until,
                     no_paid_ticket,
+                    paid_ticket,
                     no_premium_ticket,
+                    premium_ticket,
                     output,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let dl_config = AMDownloadCliConfig {
-                        auto_purchase: !no_purchase,
+                        auto_purchase: !flag_pair(no_purchase, purchase)
+                            .or(source_settings.no_purchase)
+                            .unwrap_or_default(),
                         no_input: true,
                         start_from,
                         end_at: end_until,
-                        no_premium: no_paid_ticket,
-                        no_purchased: no_premium_ticket,
+                        no_premium: flag_pair(no_paid_ticket, paid_ticket)
+                            .or(source_settings.no_paid)
+                            .unwrap_or_default(),
+                        no_purchased: flag_pair(no_premium_ticket, premium_ticket)
+                            .or(source_settings.no_premium)
+                            .unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Amap, &config.id),
                         ..Default::default()
                     };
 
                     r#impl::amap::download::amap_download(
                         title_id,
                         dl_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &client,
                         &config,
                         &mut t_mut,
@@ -517,18 +660,26 @@ async fn main() {
                     show_all,
                     auto_purchase,
                     output,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let dl_config = AMDownloadCliConfig {
                         auto_purchase,
                         show_all,
                         chapter_ids: chapters.unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Amap, &config.id),
                         ..Default::default()
                     };
 
                     r#impl::amap::download::amap_download(
                         title_id,
                         dl_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &client,
                         &config,
                         &mut t_mut,
@@ -558,6 +709,21 @@ async fn main() {
                 AMAPCommands::Search { query } => {
                     r#impl::amap::manga::amap_search(query.as_str(), &client, &config, &t).await
                 }
+                AMAPCommands::Sync {
+                    title_id,
+                    download,
+                    output,
+                } => {
+                    r#impl::amap::download::amap_sync(
+                        title_id,
+                        download,
+                        source_settings.output_dir(output),
+                        &client,
+                        &config,
+                        &mut t_mut,
+                    )
+                    .await
+                }
             };
 
             std::process::exit(exit_code as i32);
@@ -566,6 +732,7 @@ async fn main() {
             account_id,
             subcommand,
         } => {
+            let source_settings = settings.resolve(Implementations::Sjv);
             let early_exit = match subcommand.clone() {
                 SJVCommands::Auth {
                     email,
@@ -598,7 +765,8 @@ async fn main() {
             };
 
             let client = r#impl::client::make_sjv_client(&config.clone());
-            let client = if let Some(proxy) = parsed_proxy {
+            let proxy = proxies.for_account(Implementations::Sjv, &config.id).await;
+            let client = if let Some(proxy) = proxy {
                 client.with_proxy(proxy)
             } else {
                 client
@@ -619,20 +787,32 @@ async fn main() {
                     end_until,
                     output,
                     parallel,
+                    no_parallel,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let dl_config = SJDownloadCliConfig {
                         start_from,
                         end_at: end_until,
                         no_input: true,
-                        parallel,
+                        parallel: flag_pair(parallel, no_parallel)
+                            .or(source_settings.parallel)
+                            .unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Sjv, &config.id),
                         ..Default::default()
                     };
 
                     r#impl::sjv::download::sjv_download(
                         title_or_slug,
                         dl_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &client,
+                        &config,
                         &mut t_mut,
                     )
                     .await
@@ -642,18 +822,30 @@ async fn main() {
                     chapters,
                     output,
                     parallel,
+                    no_parallel,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let dl_config = SJDownloadCliConfig {
                         chapter_ids: chapters.unwrap_or_default(),
-                        parallel,
+                        parallel: flag_pair(parallel, no_parallel)
+                            .or(source_settings.parallel)
+                            .unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Sjv, &config.id),
                         ..Default::default()
                     };
 
                     r#impl::sjv::download::sjv_download(
                         title_or_slug,
                         dl_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &client,
+                        &config,
                         &mut t_mut,
                     )
                     .await
@@ -672,6 +864,21 @@ async fn main() {
                 SJVCommands::Subscription => {
                     r#impl::sjv::accounts::sjv_account_subscriptions(&client, &config, &t).await
                 }
+                SJVCommands::Sync {
+                    title_or_slug,
+                    download,
+                    output,
+                } => {
+                    r#impl::sjv::download::sjv_sync(
+                        title_or_slug,
+                        download,
+                        source_settings.output_dir(output),
+                        &client,
+                        &config,
+                        &mut t_mut,
+                    )
+                    .await
+                }
             };
 
             std::process::exit(exit_code as i32);
@@ -680,6 +887,7 @@ async fn main() {
             subcommand,
             account_id,
         } => {
+            let source_settings = settings.resolve(Implementations::Rbean);
             let early_exit = match subcommand.clone() {
                 RBeanCommands::Auth {
                     email,
@@ -711,14 +919,15 @@ async fn main() {
             };
 
             let client = r#impl::client::make_rbean_client(&config);
-            let mut client = if let Some(proxy) = parsed_proxy {
+            let proxy = proxies
+                .for_account(Implementations::Rbean, &config.id)
+                .await;
+            let mut client = if let Some(proxy) = proxy {
                 client.with_proxy(proxy)
             } else {
                 client
             };
 
-            client.set_expiry_at(Some(config.expiry));
-
             let exit_code = match subcommand {
                 RBeanCommands::Auth {
                     email: _,
@@ -734,17 +943,28 @@ async fn main() {
                     output,
                     format,
                     parallel,
+                    no_parallel,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let dl_config = RBDownloadConfigCli {
                         no_input: true,
-                        format,
-                        parallel,
+                        format: format.unwrap_or_else(|| source_settings.image_format()),
+                        parallel: flag_pair(parallel, no_parallel)
+                            .or(source_settings.parallel)
+                            .unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Rbean, &config.id),
                         ..Default::default()
                     };
                     r#impl::rbean::download::rbean_download(
                         &uuid,
                         dl_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &mut client,
                         &config,
                         &mut t_mut,
@@ -757,17 +977,28 @@ async fn main() {
                     output,
                     format,
                     parallel,
+                    no_parallel,
+                    trim,
+                    post_chapter_hook,
+                    post_title_hook,
+                    dry_run,
                 } => {
                     let dl_config = RBDownloadConfigCli {
-                        format,
+                        format: format.unwrap_or_else(|| source_settings.image_format()),
                         chapter_ids: chapters.unwrap_or_default(),
-                        parallel,
+                        parallel: flag_pair(parallel, no_parallel)
+                            .or(source_settings.parallel)
+                            .unwrap_or_default(),
+                        trim: trim.or(source_settings.trim),
+                        hooks: PostHooks::new(post_chapter_hook, post_title_hook),
+                        dry_run,
+                        proxy: proxies.lease(Implementations::Rbean, &config.id),
                         ..Default::default()
                     };
                     r#impl::rbean::download::rbean_download(
                         &uuid,
                         dl_config,
-                        output.unwrap_or_else(get_default_download_dir),
+                        source_settings.output_dir(output),
                         &mut client,
                         &config,
                         &mut t_mut,
@@ -805,6 +1036,21 @@ async fn main() {
                     )
                     .await
                 }
+                RBeanCommands::Sync {
+                    uuid,
+                    download,
+                    output,
+                } => {
+                    r#impl::rbean::download::rbean_sync(
+                        &uuid,
+                        download,
+                        source_settings.output_dir(output),
+                        &mut client,
+                        &config,
+                        &mut t_mut,
+                    )
+                    .await
+                }
             };
 
             std::process::exit(exit_code as i32);
@@ -814,11 +1060,17 @@ async fn main() {
                 ToolsCommands::AutoMerge {
                     input_folder,
                     skip_last,
+                    rules_file,
+                    no_input,
+                    dry_run,
                 } => {
                     let config = r#impl::tools::merger::ToolsMergeConfig {
                         skip_last,
-                        no_input: true,
+                        auto_merge: true,
                         ignore_manual_info: true,
+                        no_input,
+                        rules_file,
+                        dry_run,
                     };
 
                     r#impl::tools::merger::tools_split_merge(&input_folder, config, &mut t_mut)
@@ -827,18 +1079,58 @@ async fn main() {
                 ToolsCommands::Merge {
                     input_folder,
                     ignore_manual_merge,
+                    dry_run,
                 } => {
                     let config = r#impl::tools::merger::ToolsMergeConfig {
                         ignore_manual_info: ignore_manual_merge,
+                        dry_run,
                         ..Default::default()
                     };
 
                     r#impl::tools::merger::tools_split_merge(&input_folder, config, &mut t_mut)
                         .await
                 }
+                ToolsCommands::Unmerge { input_folder } => {
+                    r#impl::tools::merger::tools_split_unmerge(&input_folder, &mut t_mut).await
+                }
+                ToolsCommands::Trim {
+                    input_folder,
+                    tolerance,
+                    spread,
+                    spread_offset,
+                } => {
+                    let config = r#impl::tools::trim::ToolsTrimConfig {
+                        tolerance,
+                        spread,
+                        spread_offset,
+                    };
+
+                    r#impl::tools::trim::tools_trim(&input_folder, config, &mut t_mut).await
+                }
+                ToolsCommands::Stats { input_folder, json } => {
+                    r#impl::tools::stats::tools_stats(&input_folder, json, &mut t_mut).await
+                }
             };
             std::process::exit(exit_code as i32)
         }
+        ToshoCommands::Sync {
+            source,
+            title_id,
+            download,
+            output,
+        } => {
+            let exit_code = r#impl::sync::sync_all(
+                &settings.default.output_dir(output),
+                source,
+                title_id.as_deref(),
+                download,
+                &proxies,
+                &mut t_mut,
+            )
+            .await;
+
+            std::process::exit(exit_code as i32)
+        }
         ToshoCommands::Update => {
             updater::perform_update(&t).await.unwrap_or_else(|e| {
                 t.error(&format!("Failed to update: {}", e));
@@ -847,5 +1139,236 @@ async fn main() {
 
             std::process::exit(0)
         }
+        ToshoCommands::Notify { subcommand } => {
+            let exit_code = match subcommand {
+                NotifyCommands::Add {
+                    kind,
+                    target,
+                    events,
+                } => r#impl::notify::config::notify_add(kind, target, events, &t),
+                NotifyCommands::Remove { index } => {
+                    r#impl::notify::config::notify_remove(index, &t)
+                }
+                NotifyCommands::List => r#impl::notify::config::notify_list(&t),
+                NotifyCommands::Test => r#impl::notify::config::notify_test(&t).await,
+            };
+
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Budget { subcommand } => {
+            let exit_code = match subcommand {
+                BudgetCommands::Set {
+                    source,
+                    account_id,
+                    title_id,
+                    daily,
+                    monthly,
+                    max_per_chapter,
+                    free_only,
+                } => {
+                    let budget = r#impl::budget::config::Budget {
+                        source,
+                        account_id,
+                        title_id,
+                        daily,
+                        monthly,
+                        max_per_chapter,
+                        free_only,
+                    };
+                    r#impl::budget::config::budget_set(budget, &t)
+                }
+                BudgetCommands::Remove { index } => {
+                    r#impl::budget::config::budget_remove(index, &t)
+                }
+                BudgetCommands::List => r#impl::budget::config::budget_list(&t),
+            };
+
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Ledger {
+            source,
+            account_id,
+            title_id,
+            from,
+            to,
+            group_by,
+            period,
+            export,
+            output,
+        } => {
+            let account_id = match (source, account_id) {
+                (Some(source), Some(id)) => Some(get_aliases(&t).resolve_account_id(source, &id)),
+                (_, account_id) => account_id,
+            };
+            let config = r#impl::ledger::report::LedgerConfig {
+                filter: r#impl::ledger::report::LedgerFilter {
+                    source,
+                    account_id,
+                    title_id,
+                    from,
+                    to,
+                },
+                group_by,
+                period,
+                export,
+                output,
+            };
+
+            let exit_code = r#impl::ledger::report::ledger_report(config, &t);
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Batch { subcommand } => {
+            let exit_code = match subcommand {
+                BatchCommands::Run { manifest } => {
+                    r#impl::batch::runner::batch_run(&manifest, &proxies, &mut t_mut).await
+                }
+                BatchCommands::Check { manifest } => {
+                    r#impl::batch::runner::batch_check(&manifest, &t)
+                }
+            };
+
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Queue { subcommand } => {
+            let exit_code = match subcommand {
+                QueueCommands::List { chapters } => r#impl::queue::runner::queue_list(chapters, &t),
+                QueueCommands::Resume => {
+                    r#impl::queue::runner::queue_resume(&proxies, &mut t_mut).await
+                }
+                QueueCommands::RetryFailed => {
+                    r#impl::queue::runner::queue_retry_failed(&proxies, &mut t_mut).await
+                }
+                QueueCommands::Clear { all } => r#impl::queue::runner::queue_clear(all, &t),
+            };
+
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Accounts { subcommand } => {
+            let subcommand = subcommand.unwrap_or(AccountsCommands::List {
+                filter: Default::default(),
+                check: false,
+                json: false,
+            });
+            let exit_code = match subcommand {
+                AccountsCommands::List {
+                    filter,
+                    check,
+                    json,
+                } => {
+                    r#impl::accounts::runner::accounts_list(filter, check, json, &proxies, &t).await
+                }
+                AccountsCommands::Revoke { filter, all, yes } => {
+                    r#impl::accounts::runner::accounts_revoke(filter, all, yes, &proxies, &t).await
+                }
+                AccountsCommands::Export {
+                    output,
+                    filter,
+                    all,
+                    encrypt,
+                } => {
+                    r#impl::accounts::runner::accounts_export(
+                        &output, filter, all, encrypt, &proxies, &t,
+                    )
+                    .await
+                }
+                AccountsCommands::Import { input, overwrite } => {
+                    r#impl::accounts::runner::accounts_import(&input, overwrite, &t)
+                }
+                AccountsCommands::Alias {
+                    source,
+                    account_id,
+                    alias,
+                } => r#impl::accounts::runner::accounts_alias(source, &account_id, &alias, &t),
+                AccountsCommands::Unalias { source, alias } => {
+                    r#impl::accounts::runner::accounts_unalias(source, &alias, &t)
+                }
+                AccountsCommands::Default {
+                    source,
+                    account_id,
+                    unset,
+                } => r#impl::accounts::runner::accounts_default(source, account_id, unset, &t),
+                AccountsCommands::Check {
+                    sources,
+                    json,
+                    revoke,
+                } => {
+                    r#impl::accounts::runner::accounts_check(sources, json, revoke, &proxies, &t)
+                        .await
+                }
+            };
+
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Config { subcommand } => {
+            let exit_code = match subcommand {
+                ConfigCommands::Encrypt => r#impl::config::runner::config_encrypt(&t),
+                ConfigCommands::Decrypt => r#impl::config::runner::config_decrypt(&t),
+                ConfigCommands::ChangePassphrase => {
+                    r#impl::config::runner::config_change_passphrase(&t)
+                }
+                ConfigCommands::Get { key } => r#impl::config::runner::config_get(key, &t),
+                ConfigCommands::Set { key, value } => {
+                    r#impl::config::runner::config_set(key, value, &t)
+                }
+                ConfigCommands::Unset { key } => r#impl::config::runner::config_unset(key, &t),
+                ConfigCommands::Proxies { reset } => {
+                    r#impl::config::runner::config_proxies(reset, &t)
+                }
+            };
+
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Serve { host, port, token } => {
+            let config = r#impl::serve::ServeConfig { host, port, token };
+
+            let exit_code = r#impl::serve::serve(config, &proxies, &t).await;
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Watch { subcommand } => {
+            let exit_code = match subcommand {
+                WatchCommands::Add {
+                    source,
+                    title_id,
+                    account_id,
+                    output,
+                    purchase,
+                    trim,
+                    schedule,
+                } => {
+                    let entry = r#impl::watch::watchlist::WatchEntry {
+                        source,
+                        account_id: account_id.unwrap_or_default(),
+                        title_id,
+                        output: Some(settings.resolve(source).output_dir(output)),
+                        purchase,
+                        trim,
+                        schedule,
+                    };
+
+                    r#impl::watch::watchlist::watch_add(entry, &t)
+                }
+                WatchCommands::Remove { source, title_id } => {
+                    r#impl::watch::watchlist::watch_remove(source, title_id, &t)
+                }
+                WatchCommands::List => r#impl::watch::watchlist::watch_list(&t),
+                WatchCommands::Run => r#impl::watch::runner::watch_run(&proxies, &mut t_mut).await,
+            };
+
+            std::process::exit(exit_code as i32)
+        }
+        ToshoCommands::Daemon {
+            interval,
+            jitter,
+            now,
+        } => {
+            let config = r#impl::watch::daemon::DaemonConfig {
+                interval,
+                jitter,
+                run_now: now,
+            };
+
+            let exit_code = r#impl::watch::daemon::daemon_run(config, &proxies, &mut t_mut).await;
+            std::process::exit(exit_code as i32)
+        }
     };
 }
@@ -32,7 +32,10 @@ pub(crate) async fn get_last_update_check_time() -> anyhow::Result<i64> {
 }
 
 async fn write_last_update_check_time(time: i64) -> anyhow::Result<()> {
-    let target_file = crate::config::get_user_path().join("last_update_check.tmd");
+    let user_path = crate::config::get_user_path();
+    // a fresh or portable config directory may not exist yet
+    tokio::fs::create_dir_all(&user_path).await?;
+    let target_file = user_path.join("last_update_check.tmd");
 
     tokio::fs::write(&target_file, time.to_string()).await?;
 
@@ -126,7 +126,9 @@ impl RBClient {
     /// # Arguments
     /// * `proxy` - The proxy to attach to the client
     pub fn with_proxy(&self, proxy: reqwest::Proxy) -> Self {
-        Self::make_client(self.config.clone(), Some(proxy))
+        let mut client = Self::make_client(self.config.clone(), Some(proxy));
+        client.expiry_at = self.expiry_at;
+        client
     }
 
     fn make_client(config: RBConfig, proxy: Option<reqwest::Proxy>) -> Self {
@@ -166,6 +168,9 @@ impl RBClient {
         }
     }
 
+    /// Set the expiry time of the current token, as an unix timestamp.
+    ///
+    /// The token will not be refreshed before this time.
     pub fn set_expiry_at(&mut self, expiry_at: Option<i64>) {
         self.expiry_at = expiry_at;
     }
@@ -201,6 +206,17 @@ impl RBClient {
             .send()
             .await?;
 
+        if !request.status().is_success() {
+            // ex: {"error": {"code": 400, "message": "TOKEN_EXPIRED"}}
+            let status = request.status();
+            let body = request
+                .json::<serde_json::Value>()
+                .await
+                .unwrap_or_default();
+            let message = body["error"]["message"].as_str().unwrap_or("unknown error");
+            anyhow::bail!("Failed to refresh token ({}): {}", status, message);
+        }
+
         let response = request
             .json::<crate::models::accounts::google::SecureTokenResponse>()
             .await?;
@@ -224,6 +240,13 @@ impl RBClient {
         self.expiry_at
     }
 
+    /// Get the current config of the client.
+    ///
+    /// The token is updated on each refresh, use this to save the refreshed token.
+    pub fn get_config(&self) -> &RBConfig {
+        &self.config
+    }
+
     // <-- Common Helper
 
     async fn request<T>(
@@ -239,9 +262,14 @@ impl RBClient {
 
         let endpoint = format!("{}{}", *BASE_API, url);
 
+        // the default header has the token the client is created with
+        let request = self
+            .inner
+            .request(method, endpoint)
+            .header("x-user-token", &self.token);
         let request = match json_body {
-            Some(json_body) => self.inner.request(method, endpoint).json(&json_body),
-            None => self.inner.request(method, endpoint),
+            Some(json_body) => request.json(&json_body),
+            None => request,
         };
 
         let response = request.send().await?;